@@ -0,0 +1,220 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::{Action, CKANError, Params, CKAN};
+
+/// Settings of the [`CKAN::batch`] call.
+///
+/// # Examples
+/// ```
+/// # use ckanapi::BatchOptions;
+/// let options = BatchOptions::default().concurrency(8).rate_limit(10.0);
+/// assert_eq!(8, options.concurrency);
+/// assert_eq!(Some(10.0), options.rate_limit);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOptions {
+    /// Maximum number of requests that are in flight at the same time.
+    pub concurrency: usize,
+    /// Maximum number of requests per second sent by this batch. It applies
+    /// in addition to the limit of the client, set by
+    /// [`CKAN::set_rate_limit`] and shared with other batches.
+    pub rate_limit: Option<f64>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            rate_limit: None,
+        }
+    }
+}
+
+impl BatchOptions {
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.rate_limit = Some(requests_per_second);
+        self
+    }
+}
+
+/// Outcome of the batch. `results` follow the order of the calls.
+#[derive(Debug)]
+pub struct Batch<T> {
+    pub results: Vec<Result<T, CKANError>>,
+    pub summary: BatchSummary,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+impl<T> Batch<T> {
+    /// Pair every failed call with its position in the input.
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &CKANError)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, r)| r.as_ref().err().map(|err| (idx, err)))
+    }
+}
+
+/// Spreads requests evenly so that no more than the given number of requests
+/// per second is started.
+#[derive(Debug)]
+pub(crate) struct Throttle {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(per_second: f64) -> Option<Self> {
+        if per_second.is_finite() && per_second > 0.0 {
+            Some(Self {
+                interval: Duration::from_secs_f64(1.0 / per_second),
+                next: Mutex::new(Instant::now()),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn per_second(&self) -> f64 {
+        1.0 / self.interval.as_secs_f64()
+    }
+
+    pub(crate) async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+impl CKAN {
+    /// Invoke multiple actions, keeping at most `options.concurrency` of them
+    /// in flight. Requests are spread according to the rate limit of the
+    /// client and `options.rate_limit`.
+    ///
+    /// A failed call does not interrupt the batch: its error is stored in the
+    /// corresponding position of [`Batch::results`].
+    ///
+    /// ```no_run
+    /// # use ckanapi::{BatchOptions, CKAN, Params};
+    /// # use serde_json::{json, Value};
+    /// # async fn run(client: CKAN) {
+    /// let calls = ["first", "second"].into_iter().map(|id| {
+    ///     ("package_patch", Params::Json(json!({"id": id, "notes": "updated"})))
+    /// });
+    /// let batch = client
+    ///     .batch::<_, _, Value>(calls, &BatchOptions::default().rate_limit(5.0))
+    ///     .await;
+    /// println!("{} of {} failed", batch.summary.failed, batch.summary.total);
+    /// # }
+    /// ```
+    pub async fn batch<I, A, T>(&self, calls: I, options: &BatchOptions) -> Batch<T>
+    where
+        I: IntoIterator<Item = (A, Params)>,
+        A: Into<Action>,
+        T: for<'de> Deserialize<'de> + Debug,
+    {
+        let started = Instant::now();
+        let throttle = options.rate_limit.and_then(Throttle::new);
+        let throttles: Vec<&Throttle> = self.throttle().into_iter().chain(&throttle).collect();
+        let throttles = &throttles;
+
+        let results: Vec<Result<T, CKANError>> = stream::iter(calls)
+            .map(|(action, params)| async move {
+                for throttle in throttles {
+                    throttle.wait().await;
+                }
+                self.build(action).params(params).send().await?.extract()
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await;
+
+        let succeeded = results.iter().filter(|r| r.is_ok()).count();
+        let summary = BatchSummary {
+            total: results.len(),
+            succeeded,
+            failed: results.len() - succeeded,
+            elapsed: started.elapsed(),
+        };
+
+        Batch { results, summary }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn test_batch_keeps_order_of_failures() {
+        let client = CKAN::from("http://127.0.0.1:9");
+        let calls = ["first", "second", "third"]
+            .into_iter()
+            .map(|name| (name, Params::Empty));
+
+        let batch = client
+            .batch::<_, _, Value>(calls, &BatchOptions::default().concurrency(3))
+            .await;
+
+        assert_eq!(3, batch.summary.total);
+        assert_eq!(0, batch.summary.succeeded);
+        assert_eq!(3, batch.summary.failed);
+        for (idx, name) in ["first", "second", "third"].iter().enumerate() {
+            match &batch.results[idx] {
+                Err(CKANError::Request(msg)) => assert!(msg.contains(name), "{}", msg),
+                other => panic!("Unexpected result: {:?}", other),
+            }
+        }
+        assert_eq!(3, batch.errors().count());
+    }
+
+    #[tokio::test]
+    async fn test_throttle_spreads_requests() {
+        let throttle = Throttle::new(50.0).unwrap();
+        let started = Instant::now();
+        for _ in 0..5 {
+            throttle.wait().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        assert!(Throttle::new(0.0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_batches_share_rate_limit_of_client() {
+        let mut client = CKAN::from("http://127.0.0.1:9");
+        client.set_rate_limit(Some(50.0));
+        let other = client.clone();
+        let calls = || (0..3).map(|_| ("status_show", Params::Empty));
+
+        let started = Instant::now();
+        let options = BatchOptions::default();
+        tokio::join!(
+            client.batch::<_, _, Value>(calls(), &options),
+            other.batch::<_, _, Value>(calls(), &options),
+        );
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -1,13 +1,20 @@
+use futures::{stream, TryStreamExt};
 use reqwest::{
+    header::HeaderMap,
     multipart::{Form, Part},
-    Client,
-    Url
+    Body, Client, Url,
 };
+use tokio_util::io::ReaderStream;
+
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 
+use crate::batch::Throttle;
+
 /// Client for the CKAN API.
 ///
 /// It can be created from the string with a URL of the CKAN
@@ -38,11 +45,13 @@ use thiserror::Error;
 /// If the application mounted under non-root path, this must be reflected in
 /// the URL.
 ///
-#[derive(Debug)]
+/// Clones share the HTTP client and the rate limit.
+#[derive(Debug, Clone)]
 pub struct CKAN {
     url: String,
     token: Option<String>,
     client: Client,
+    throttle: Option<Arc<Throttle>>,
 }
 
 impl CKAN {
@@ -90,6 +99,32 @@ impl CKAN {
         self.token.take()
     }
 
+    /// Limit the number of requests per second that [`CKAN::batch`] sends
+    /// to the portal. The limit is shared by all batches of the client and
+    /// its clones. `None` removes the limit.
+    ///
+    /// # Examples
+    /// ```
+    /// # let mut client = ckanapi::CKAN::from("http://demo.ckan.org");
+    /// client.set_rate_limit(Some(10.0));
+    /// assert_eq!(Some(10.0), client.rate_limit());
+    ///
+    /// client.set_rate_limit(None);
+    /// assert_eq!(None, client.rate_limit());
+    /// ```
+    pub fn set_rate_limit(&mut self, requests_per_second: Option<f64>) {
+        self.throttle = requests_per_second.and_then(Throttle::new).map(Arc::new);
+    }
+
+    /// Requests per second allowed by [`CKAN::set_rate_limit`].
+    pub fn rate_limit(&self) -> Option<f64> {
+        self.throttle.as_ref().map(|t| t.per_second())
+    }
+
+    pub(crate) fn throttle(&self) -> Option<&Throttle> {
+        self.throttle.as_deref()
+    }
+
     pub fn build<A>(&self, action: A) -> RequestBuilder
     where
         A: Into<Action>,
@@ -133,6 +168,34 @@ impl RequestBuilder {
                                 .mime_str("application/octet-stream")
                                 .expect("Unexpected content type"),
                         ),
+                        MultipartField::NamedBlob(name, v) => form.part(
+                            k,
+                            Part::bytes(v)
+                                .file_name(name)
+                                .mime_str("application/octet-stream")
+                                .expect("Unexpected content type"),
+                        ),
+                        MultipartField::NamedFile(name, path) => {
+                            // the file is read while the request is sent, so
+                            // errors of reading are reported as transport
+                            // errors.
+                            let length = std::fs::metadata(&path).map(|m| m.len()).ok();
+                            let body = Body::wrap_stream(
+                                stream::once(tokio::fs::File::open(path))
+                                    .map_ok(ReaderStream::new)
+                                    .try_flatten(),
+                            );
+                            let part = match length {
+                                Some(length) => Part::stream_with_length(body, length),
+                                None => Part::stream(body),
+                            };
+                            form.part(
+                                k,
+                                part.file_name(name)
+                                    .mime_str("application/octet-stream")
+                                    .expect("Unexpected content type"),
+                            )
+                        }
                     };
                 }
 
@@ -142,11 +205,28 @@ impl RequestBuilder {
         };
         self
     }
+    /// Send the request and parse the body of the response.
+    ///
+    /// Only transport errors are reported here. Errors of the action itself
+    /// are kept inside the [`Response`] and turned into [`CKANError`] by
+    /// [`Response::extract`].
     pub async fn send<T>(self) -> Result<Response<T>, CKANError>
     where
         T: for<'de> Deserialize<'de>,
     {
-        Ok(self.request.send().await?.json::<Response<T>>().await?)
+        let resp = self.request.send().await?;
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let body = resp.bytes().await?;
+
+        Ok(match serde_json::from_slice::<Value>(&body) {
+            Ok(data) => Response::from_value(data, headers),
+            Err(_) => Response::Exception(format!(
+                "{}: {}",
+                status,
+                String::from_utf8_lossy(&body).trim()
+            )),
+        })
     }
 }
 
@@ -164,6 +244,7 @@ where
             url,
             client: Client::new(),
             token: None,
+            throttle: None,
         }
     }
 }
@@ -185,6 +266,11 @@ pub enum CKANError {
     #[error("{0}")]
     Complex(Value),
 
+    /// Action succeeded, but its result cannot be deserialized into the
+    /// requested type. `raw` contains the whole body of the response.
+    #[error("Unexpected result: {message}")]
+    Mismatch { message: String, raw: Value },
+
     #[error("some error")]
     Plain,
 }
@@ -195,19 +281,80 @@ impl From<reqwest::Error> for CKANError {
     }
 }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
+/// Parsed body of the API response.
+///
+/// The variant is chosen by the `success` flag of the body: successful
+/// responses whose `result` cannot be deserialized into `T` end up in
+/// `Mismatch`, bodies without the flag(e.g. HTML error pages) in
+/// `Exception`.
+#[derive(Debug)]
 pub enum Response<T> {
     Result(Success<T>),
     Error(Fail),
+    Mismatch { message: String, raw: Value },
     Exception(String),
 }
 
-impl<T: std::fmt::Debug> Response<T> {
-    pub fn extract(self) -> Result<T, CKANError> {
+impl<T> Response<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    /// Build the response from the JSON body and headers.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ckanapi::Response;
+    /// # use serde_json::json;
+    /// let resp = Response::<u8>::from_value(
+    ///     json!({"success": true, "help": "", "result": "ten"}),
+    ///     Default::default(),
+    /// );
+    /// assert!(matches!(resp, Response::Mismatch { .. }));
+    /// ```
+    pub fn from_value(mut data: Value, headers: HeaderMap) -> Self {
+        let help = data["help"].as_str().unwrap_or_default().to_string();
+
+        match data.get("success").and_then(Value::as_bool) {
+            Some(true) => match T::deserialize(&data["result"]) {
+                Ok(result) => Response::Result(Success {
+                    help,
+                    result,
+                    headers,
+                }),
+                Err(err) => Response::Mismatch {
+                    message: err.to_string(),
+                    raw: data,
+                },
+            },
+            Some(false) => Response::Error(Fail {
+                help,
+                error: data["error"].take(),
+            }),
+            None => match data {
+                Value::String(msg) => Response::Exception(msg),
+                other => Response::Exception(other.to_string()),
+            },
+        }
+    }
+}
+
+impl<T> Response<T> {
+    /// Documentation of the action, if the portal sent it.
+    pub fn help(&self) -> Option<&str> {
         match self {
+            Response::Result(Success { help, .. }) | Response::Error(Fail { help, .. }) => {
+                Some(help)
+            }
+            _ => None,
+        }
+    }
+
+    /// Keep the whole successful response, including `help` and headers.
+    pub fn into_success(self) -> Result<Success<T>, CKANError> {
+        match self {
+            Response::Result(success) => Ok(success),
             Response::Exception(msg) => Err(CKANError::Request(msg)),
-            Response::Result(Success { result, .. }) => Ok(result),
+            Response::Mismatch { message, raw } => Err(CKANError::Mismatch { message, raw }),
             Response::Error(Fail { mut error, .. }) => {
                 match error["__type"] {
                     Value::String(ref t) if t == "Not Found Error" => {
@@ -227,7 +374,6 @@ impl<T: std::fmt::Debug> Response<T> {
                     Value::String(ref t) if t == "Validation Error" => {
                         if let Some(map) = error.as_object_mut() {
                             map.remove("__type");
-                        } else {
                         }
                         Err(CKANError::Validation(error))
                     }
@@ -239,12 +385,19 @@ impl<T: std::fmt::Debug> Response<T> {
             }
         }
     }
+
+    pub fn extract(self) -> Result<T, CKANError> {
+        self.into_success().map(|success| success.result)
+    }
 }
 
 #[derive(Deserialize, Debug)]
 pub struct Success<T> {
     pub help: String,
     pub result: T,
+    /// Headers of the HTTP response.
+    #[serde(skip)]
+    pub headers: HeaderMap,
 }
 
 #[derive(Deserialize, Debug)]
@@ -361,6 +514,63 @@ impl Params {
         }
         self
     }
+
+    /// Add a file to the multipart payload, keeping its original name.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ckanapi::{Params, MultipartField};
+    /// let mut payload = Params::multipart();
+    /// payload.add_named_blob("upload", "data.csv", vec![53, 64, 55]);
+    ///
+    /// assert_eq!(Params::Multipart(
+    ///     vec![("upload".into(), MultipartField::NamedBlob("data.csv".into(), vec![53, 64, 55]))]),
+    ///     payload
+    /// );
+    /// ```
+    pub fn add_named_blob<N: Into<String>, F: Into<String>, V: IntoIterator<Item = u8>>(
+        &mut self,
+        name: N,
+        filename: F,
+        value: V,
+    ) -> &mut Self {
+        if let Params::Multipart(fields) = self {
+            fields.push((
+                name.into(),
+                MultipartField::NamedBlob(filename.into(), value.into_iter().collect()),
+            ));
+        }
+        self
+    }
+
+    /// Add a file to the multipart payload, streaming it from `path` instead
+    /// of loading it into memory.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ckanapi::{Params, MultipartField};
+    /// let mut payload = Params::multipart();
+    /// payload.add_named_file("upload", "data.csv", "/tmp/data.csv");
+    ///
+    /// assert_eq!(Params::Multipart(
+    ///     vec![("upload".into(), MultipartField::NamedFile("data.csv".into(), "/tmp/data.csv".into()))]),
+    ///     payload
+    /// );
+    /// ```
+    pub fn add_named_file<N: Into<String>, F: Into<String>, P: Into<PathBuf>>(
+        &mut self,
+        name: N,
+        filename: F,
+        path: P,
+    ) -> &mut Self {
+        if let Params::Multipart(fields) = self {
+            fields.push((
+                name.into(),
+                MultipartField::NamedFile(filename.into(), path.into()),
+            ));
+        }
+        self
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -369,6 +579,10 @@ pub enum MultipartField {
     Literal(String),
     // Filepath(String),
     Blob(Vec<u8>),
+    /// Content of the file together with its name.
+    NamedBlob(String, Vec<u8>),
+    /// File streamed from the disk, together with its name.
+    NamedFile(String, PathBuf),
 }
 
 #[cfg(test)]
@@ -412,7 +626,7 @@ mod tests {
             .extract()
             .unwrap();
         assert!(resp["count"].as_i64().unwrap() > 0);
-        assert!(resp["results"].as_array().unwrap().len() == 0);
+        assert!(resp["results"].as_array().unwrap().is_empty());
     }
 
     #[tokio::test]
@@ -429,7 +643,7 @@ mod tests {
             .extract()
             .unwrap();
         assert!(resp["count"].as_i64().unwrap() > 0);
-        assert!(resp["results"].as_array().unwrap().len() == 0);
+        assert!(resp["results"].as_array().unwrap().is_empty());
     }
 
     #[tokio::test]
@@ -495,4 +709,55 @@ mod tests {
 
     #[tokio::test]
     async fn test_async() {}
+
+    #[test]
+    fn test_response_success() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "42".parse().unwrap());
+        let resp = Response::<Vec<String>>::from_value(
+            serde_json::json!({"success": true, "help": "http://localhost/help", "result": ["a"]}),
+            headers,
+        );
+        assert_eq!(Some("http://localhost/help"), resp.help());
+
+        let success = resp.into_success().unwrap();
+        assert_eq!(vec!["a".to_string()], success.result);
+        assert_eq!("42", success.headers["x-request-id"]);
+    }
+
+    #[test]
+    fn test_response_mismatch_keeps_raw_body() {
+        let body = serde_json::json!({"success": true, "help": "", "result": {"count": 1}});
+        let err = Response::<Vec<String>>::from_value(body.clone(), HeaderMap::new())
+            .extract()
+            .err()
+            .unwrap();
+        match err {
+            CKANError::Mismatch { raw, .. } => assert_eq!(body, raw),
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_response_failure_ignores_result_type() {
+        let err = Response::<Vec<String>>::from_value(
+            serde_json::json!({
+                "success": false,
+                "help": "",
+                "error": {"__type": "Not Found Error", "message": "Not found"}
+            }),
+            HeaderMap::new(),
+        )
+        .extract()
+        .err()
+        .unwrap();
+        match err {
+            CKANError::NotFound(msg) => assert_eq!("Not found", msg),
+            _ => panic!("Unexpected error: {:?}", err),
+        }
+
+        let resp =
+            Response::<Value>::from_value(serde_json::json!("Bad request"), HeaderMap::new());
+        assert!(matches!(resp, Response::Exception(msg) if msg == "Bad request"));
+    }
 }
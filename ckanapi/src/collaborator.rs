@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{CKANError, Params, CKAN};
+
+/// Level of access granted to a dataset collaborator.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Capacity {
+    Member,
+    Editor,
+    Admin,
+}
+
+/// User with an explicit access to a single dataset.
+///
+/// Collaborators must be enabled on the portal via
+/// `ckan.auth.allow_dataset_collaborators` config option.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Collaborator {
+    pub package_id: String,
+    pub user_id: String,
+    pub capacity: Capacity,
+    #[serde(default)]
+    pub modified: Option<String>,
+}
+
+impl CKAN {
+    /// List collaborators of the dataset, optionally filtered by capacity.
+    pub async fn package_collaborator_list(
+        &self,
+        id: &str,
+        capacity: Option<Capacity>,
+    ) -> Result<Vec<Collaborator>, CKANError> {
+        let mut payload = json!({ "id": id });
+        if let Some(capacity) = capacity {
+            payload["capacity"] = json!(capacity);
+        }
+
+        self.build("package_collaborator_list")
+            .params(Params::Json(payload))
+            .send()
+            .await?
+            .extract()
+    }
+
+    /// Grant the user access to the dataset or change the capacity of the
+    /// existing collaborator.
+    pub async fn package_collaborator_create(
+        &self,
+        id: &str,
+        user_id: &str,
+        capacity: Capacity,
+    ) -> Result<Collaborator, CKANError> {
+        self.build("package_collaborator_create")
+            .params(Params::Json(
+                json!({"id": id, "user_id": user_id, "capacity": capacity}),
+            ))
+            .send()
+            .await?
+            .extract()
+    }
+
+    /// Revoke access to the dataset from the user.
+    pub async fn package_collaborator_delete(
+        &self,
+        id: &str,
+        user_id: &str,
+    ) -> Result<(), CKANError> {
+        self.build("package_collaborator_delete")
+            .params(Params::Json(json!({"id": id, "user_id": user_id})))
+            .send::<Value>()
+            .await?
+            .extract()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collaborator_parsing() {
+        let collaborator: Collaborator = serde_json::from_value(json!({
+            "package_id": "pkg",
+            "user_id": "user",
+            "capacity": "editor",
+            "modified": "2022-08-01T10:00:00"
+        }))
+        .unwrap();
+
+        assert_eq!(Capacity::Editor, collaborator.capacity);
+        assert_eq!(Some("2022-08-01T10:00:00".into()), collaborator.modified);
+        assert_eq!(json!("admin"), json!(Capacity::Admin));
+    }
+}
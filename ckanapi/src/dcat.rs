@@ -0,0 +1,572 @@
+//! Conversion of CKAN package dicts to and from DCAT.
+//!
+//! Packages are the dicts returned by `package_show` or the `results` of
+//! `package_search`. They can be exported as DCAT in JSON-LD or Turtle
+//! serialization and as Project Open Data `data.json`. Parsed documents are
+//! turned back into dicts that can be sent to `package_create`.
+//!
+//! ```no_run
+//! # use ckanapi::dcat::{self, DcatMapping, FieldMapping};
+//! # fn run(packages: Vec<serde_json::Value>) {
+//! let mapping = DcatMapping::new("https://data.example.com")
+//!     .prefix("flood", "https://data.example.com/ns#")
+//!     .dataset_field(FieldMapping::literal("flood_studies", "flood:study").data_json("floodStudy"));
+//!
+//! let turtle = dcat::to_turtle(&packages, &mapping);
+//! let restored = dcat::from_turtle(&turtle, &mapping).unwrap();
+//! # }
+//! ```
+mod graph;
+mod pod;
+mod turtle;
+
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+pub use self::graph::{Graph, Term, Triple};
+pub use self::pod::{from_data_json, to_data_json};
+
+#[derive(Error, Debug)]
+pub enum DcatError {
+    #[error("Syntax error on line {line}: {message}")]
+    Syntax { line: usize, message: String },
+
+    #[error("{0}")]
+    Format(String),
+}
+
+/// How the value of the field is represented in RDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Literal,
+    Iri,
+    DateTime,
+    Integer,
+}
+
+/// Correspondence between the field of the CKAN dict and the RDF predicate
+/// (and, optionally, the key of the `data.json` entry).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub field: String,
+    pub predicate: String,
+    pub kind: FieldKind,
+    pub data_json: Option<String>,
+}
+
+impl FieldMapping {
+    pub fn new<F: Into<String>, P: Into<String>>(field: F, predicate: P, kind: FieldKind) -> Self {
+        Self {
+            field: field.into(),
+            predicate: predicate.into(),
+            kind,
+            data_json: None,
+        }
+    }
+
+    pub fn literal<F: Into<String>, P: Into<String>>(field: F, predicate: P) -> Self {
+        Self::new(field, predicate, FieldKind::Literal)
+    }
+
+    pub fn iri<F: Into<String>, P: Into<String>>(field: F, predicate: P) -> Self {
+        Self::new(field, predicate, FieldKind::Iri)
+    }
+
+    pub fn date<F: Into<String>, P: Into<String>>(field: F, predicate: P) -> Self {
+        Self::new(field, predicate, FieldKind::DateTime)
+    }
+
+    pub fn integer<F: Into<String>, P: Into<String>>(field: F, predicate: P) -> Self {
+        Self::new(field, predicate, FieldKind::Integer)
+    }
+
+    /// Use the field as the `key` of `data.json` entries.
+    pub fn data_json<K: Into<String>>(mut self, key: K) -> Self {
+        self.data_json = Some(key.into());
+        self
+    }
+}
+
+/// Set of field mappings for datasets and distributions.
+///
+/// Standard CKAN fields are mapped by default. Custom fields(e.g. the ones
+/// added by ckanext-scheming) can be added with
+/// [`DcatMapping::dataset_field`] and [`DcatMapping::distribution_field`].
+/// Values of custom fields are read either from the top level of the dict or
+/// from its `extras`; parsed values are always placed at the top level.
+///
+/// Tags, author, maintainer and publisher are handled separately and
+/// converted into `dcat:keyword`, `dct:creator`, `dcat:contactPoint` and
+/// `dct:publisher`. Publisher is not restored when parsing because CKAN
+/// requires an ID of the existing organization.
+#[derive(Debug, Clone)]
+pub struct DcatMapping {
+    /// Public URL of the portal, used for IRIs of datasets and distributions.
+    pub base_url: String,
+    pub prefixes: Vec<(String, String)>,
+    pub dataset: Vec<FieldMapping>,
+    pub distribution: Vec<FieldMapping>,
+}
+
+impl DcatMapping {
+    pub fn new<T: Into<String>>(base_url: T) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self {
+            base_url,
+            prefixes: Vec::new(),
+            dataset: vec![
+                FieldMapping::literal("title", "dct:title").data_json("title"),
+                FieldMapping::literal("notes", "dct:description").data_json("description"),
+                FieldMapping::iri("url", "dcat:landingPage").data_json("landingPage"),
+                FieldMapping::literal("version", "owl:versionInfo"),
+                FieldMapping::literal("license_id", "dct:license").data_json("license"),
+                FieldMapping::date("metadata_created", "dct:issued").data_json("issued"),
+                FieldMapping::date("metadata_modified", "dct:modified").data_json("modified"),
+            ],
+            distribution: vec![
+                FieldMapping::literal("name", "dct:title").data_json("title"),
+                FieldMapping::literal("description", "dct:description").data_json("description"),
+                FieldMapping::iri("url", "dcat:accessURL").data_json("downloadURL"),
+                FieldMapping::literal("format", "dct:format").data_json("format"),
+                FieldMapping::literal("mimetype", "dcat:mediaType").data_json("mediaType"),
+                FieldMapping::integer("size", "dcat:byteSize"),
+                FieldMapping::date("created", "dct:issued"),
+                FieldMapping::date("last_modified", "dct:modified"),
+            ],
+        }
+    }
+
+    /// Register namespace used by predicates of custom fields.
+    pub fn prefix<P: Into<String>, N: Into<String>>(mut self, prefix: P, namespace: N) -> Self {
+        self.prefixes.push((prefix.into(), namespace.into()));
+        self
+    }
+
+    /// Add mapping for the dataset field, replacing the existing one.
+    pub fn dataset_field(mut self, mapping: FieldMapping) -> Self {
+        self.dataset.retain(|m| m.field != mapping.field);
+        self.dataset.push(mapping);
+        self
+    }
+
+    /// Add mapping for the resource field, replacing the existing one.
+    pub fn distribution_field(mut self, mapping: FieldMapping) -> Self {
+        self.distribution.retain(|m| m.field != mapping.field);
+        self.distribution.push(mapping);
+        self
+    }
+
+    fn graph(&self) -> Graph {
+        Graph::new(&self.prefixes)
+    }
+
+    fn dataset_iri(&self, name: &str) -> String {
+        format!("{}/dataset/{}", self.base_url, name)
+    }
+}
+
+/// Value of the field from the top level of the dict or from its extras.
+fn field_value(data: &Value, field: &str) -> Option<String> {
+    let value = match data.get(field) {
+        Some(value) if !value.is_null() => value,
+        _ => data
+            .get("extras")?
+            .as_array()?
+            .iter()
+            .find(|extra| extra["key"] == field)
+            .map(|extra| &extra["value"])?,
+    };
+
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Value that can be stored in the dict according to the field kind.
+fn typed_value(value: &str, kind: FieldKind) -> Value {
+    match kind {
+        FieldKind::Integer => value
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::from(value)),
+        _ => Value::from(value),
+    }
+}
+
+/// Make a valid CKAN name out of arbitrary text.
+fn slugify(text: &str) -> Option<String> {
+    let mut slug = String::new();
+    for c in text.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_matches('-').chars().take(100).collect();
+    if slug.len() < 2 {
+        None
+    } else {
+        Some(slug)
+    }
+}
+
+fn tag_names(package: &Value) -> Vec<String> {
+    package["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|t| t["name"].as_str().or_else(|| t["display_name"].as_str()))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn mapped_term(value: String, kind: FieldKind) -> Term {
+    match kind {
+        FieldKind::Literal => Term::literal(value),
+        FieldKind::Iri => Term::Iri(value),
+        FieldKind::DateTime if value.contains('T') => Term::typed(value, "dateTime"),
+        FieldKind::DateTime => Term::typed(value, "date"),
+        FieldKind::Integer => Term::typed(value, "integer"),
+    }
+}
+
+fn add_mapped(graph: &mut Graph, subject: &Term, data: &Value, mappings: &[FieldMapping]) {
+    for mapping in mappings {
+        if let Some(value) = field_value(data, &mapping.field) {
+            graph.add(
+                subject.clone(),
+                &mapping.predicate,
+                mapped_term(value, mapping.kind),
+            );
+        }
+    }
+}
+
+fn add_agent(
+    graph: &mut Graph,
+    subject: &Term,
+    predicate: &str,
+    class: &str,
+    (name_predicate, name): (&str, Option<String>),
+    (email_predicate, email): (&str, Option<String>),
+) {
+    if name.is_none() && email.is_none() {
+        return;
+    }
+    let agent = graph.blank();
+    let class = Term::Iri(graph.expand(class));
+    graph.add(subject.clone(), predicate, agent.clone());
+    graph.add(agent.clone(), graph::RDF_TYPE, class);
+    if let Some(name) = name {
+        graph.add(agent.clone(), name_predicate, Term::literal(name));
+    }
+    if let Some(email) = email {
+        graph.add(
+            agent,
+            email_predicate,
+            Term::Iri(format!("mailto:{}", email)),
+        );
+    }
+}
+
+/// Build DCAT graph describing the packages.
+pub fn to_graph(packages: &[Value], mapping: &DcatMapping) -> Graph {
+    let mut graph = mapping.graph();
+
+    for package in packages {
+        let name = field_value(package, "name")
+            .or_else(|| field_value(package, "id"))
+            .unwrap_or_default();
+        let dataset = Term::Iri(mapping.dataset_iri(&name));
+        let class = Term::Iri(graph.expand("dcat:Dataset"));
+        graph.add(dataset.clone(), graph::RDF_TYPE, class);
+
+        if let Some(id) = field_value(package, "id") {
+            graph.add(dataset.clone(), "dct:identifier", Term::literal(id));
+        }
+        add_mapped(&mut graph, &dataset, package, &mapping.dataset);
+
+        for tag in tag_names(package) {
+            graph.add(dataset.clone(), "dcat:keyword", Term::literal(tag));
+        }
+
+        add_agent(
+            &mut graph,
+            &dataset,
+            "dct:creator",
+            "foaf:Agent",
+            ("foaf:name", field_value(package, "author")),
+            ("foaf:mbox", field_value(package, "author_email")),
+        );
+        add_agent(
+            &mut graph,
+            &dataset,
+            "dcat:contactPoint",
+            "vcard:Kind",
+            ("vcard:fn", field_value(package, "maintainer")),
+            ("vcard:hasEmail", field_value(package, "maintainer_email")),
+        );
+        add_agent(
+            &mut graph,
+            &dataset,
+            "dct:publisher",
+            "foaf:Organization",
+            ("foaf:name", field_value(&package["organization"], "title")),
+            ("foaf:mbox", None),
+        );
+
+        for resource in package["resources"].as_array().into_iter().flatten() {
+            let distribution = match field_value(resource, "id") {
+                Some(id) => Term::Iri(format!("{}/resource/{}", dataset.as_str(), id)),
+                None => graph.blank(),
+            };
+            let class = Term::Iri(graph.expand("dcat:Distribution"));
+            graph.add(dataset.clone(), "dcat:distribution", distribution.clone());
+            graph.add(distribution.clone(), graph::RDF_TYPE, class);
+            add_mapped(&mut graph, &distribution, resource, &mapping.distribution);
+        }
+    }
+
+    graph
+}
+
+fn read_mapped(graph: &Graph, subject: &Term, mappings: &[FieldMapping]) -> Map<String, Value> {
+    let mut data = Map::new();
+    for mapping in mappings {
+        if let Some(object) = graph.object(subject, &mapping.predicate) {
+            data.insert(
+                mapping.field.clone(),
+                typed_value(object.as_str(), mapping.kind),
+            );
+        }
+    }
+    data
+}
+
+fn read_agent(
+    graph: &Graph,
+    subject: &Term,
+    predicate: &str,
+    name_predicate: &str,
+    email_predicate: &str,
+) -> (Option<String>, Option<String>) {
+    match graph.object(subject, predicate) {
+        Some(agent) => (
+            graph
+                .object(agent, name_predicate)
+                .map(|t| t.as_str().to_string()),
+            graph
+                .object(agent, email_predicate)
+                .map(|t| t.as_str().trim_start_matches("mailto:").to_string()),
+        ),
+        None => (None, None),
+    }
+}
+
+/// Extract package dicts from every `dcat:Dataset` of the graph.
+pub fn from_graph(graph: &Graph, mapping: &DcatMapping) -> Vec<Value> {
+    graph
+        .instances("dcat:Dataset")
+        .iter()
+        .map(|dataset| {
+            let mut package = read_mapped(graph, dataset, &mapping.dataset);
+
+            if !package.contains_key("name") {
+                let name = match dataset {
+                    Term::Iri(iri) => iri.rsplit('/').find(|s| !s.is_empty()).and_then(slugify),
+                    _ => None,
+                }
+                .or_else(|| {
+                    package
+                        .get("title")
+                        .and_then(|t| t.as_str())
+                        .and_then(slugify)
+                });
+                if let Some(name) = name {
+                    package.insert("name".into(), Value::from(name));
+                }
+            }
+
+            let tags: Vec<Value> = graph
+                .objects(dataset, "dcat:keyword")
+                .map(|t| json!({"name": t.as_str()}))
+                .collect();
+            if !tags.is_empty() {
+                package.insert("tags".into(), Value::from(tags));
+            }
+
+            for (predicate, name_predicate, email_predicate, name_field, email_field) in [
+                (
+                    "dct:creator",
+                    "foaf:name",
+                    "foaf:mbox",
+                    "author",
+                    "author_email",
+                ),
+                (
+                    "dcat:contactPoint",
+                    "vcard:fn",
+                    "vcard:hasEmail",
+                    "maintainer",
+                    "maintainer_email",
+                ),
+            ] {
+                let (name, email) =
+                    read_agent(graph, dataset, predicate, name_predicate, email_predicate);
+                if let Some(name) = name {
+                    package.insert(name_field.into(), Value::from(name));
+                }
+                if let Some(email) = email {
+                    package.insert(email_field.into(), Value::from(email));
+                }
+            }
+
+            let resources: Vec<Value> = graph
+                .objects(dataset, "dcat:distribution")
+                .map(|distribution| {
+                    Value::Object(read_mapped(graph, distribution, &mapping.distribution))
+                })
+                .collect();
+            if !resources.is_empty() {
+                package.insert("resources".into(), Value::from(resources));
+            }
+
+            Value::Object(package)
+        })
+        .collect()
+}
+
+/// Serialize packages as DCAT JSON-LD document.
+pub fn to_jsonld(packages: &[Value], mapping: &DcatMapping) -> Value {
+    to_graph(packages, mapping).to_jsonld()
+}
+
+/// Serialize packages as DCAT Turtle document.
+pub fn to_turtle(packages: &[Value], mapping: &DcatMapping) -> String {
+    to_graph(packages, mapping).to_turtle()
+}
+
+/// Parse DCAT JSON-LD document into package dicts.
+pub fn from_jsonld(data: &Value, mapping: &DcatMapping) -> Result<Vec<Value>, DcatError> {
+    let mut graph = mapping.graph();
+    graph.load_jsonld(data)?;
+    Ok(from_graph(&graph, mapping))
+}
+
+/// Parse DCAT Turtle document into package dicts.
+pub fn from_turtle(source: &str, mapping: &DcatMapping) -> Result<Vec<Value>, DcatError> {
+    let mut graph = mapping.graph();
+    graph.load_turtle(source)?;
+    Ok(from_graph(&graph, mapping))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn package() -> Value {
+        json!({
+            "id": "2b9c6e4e-1b4b-4a36-9a8e-4c1e0a0f4d11",
+            "name": "river-levels",
+            "title": "River levels",
+            "notes": "Daily \"river\" levels\nfor the catchment",
+            "url": "https://example.com/river",
+            "license_id": "cc-by",
+            "metadata_modified": "2022-08-01T10:00:00.123456",
+            "author": "Hydrology team",
+            "author_email": "hydro@example.com",
+            "maintainer": "Data desk",
+            "maintainer_email": "data@example.com",
+            "organization": {"title": "Water Agency"},
+            "tags": [{"name": "water"}, {"name": "rivers"}],
+            "extras": [{"key": "flood_studies", "value": "study-1"}],
+            "resources": [{
+                "id": "res-1",
+                "name": "Levels",
+                "url": "https://example.com/levels.csv",
+                "format": "CSV",
+                "size": 2048
+            }]
+        })
+    }
+
+    pub(super) fn mapping() -> DcatMapping {
+        DcatMapping::new("https://data.example.com/")
+            .prefix("flood", "https://data.example.com/ns#")
+            .dataset_field(
+                FieldMapping::literal("flood_studies", "flood:study").data_json("floodStudy"),
+            )
+    }
+
+    fn expected() -> Value {
+        json!({
+            "name": "river-levels",
+            "title": "River levels",
+            "notes": "Daily \"river\" levels\nfor the catchment",
+            "url": "https://example.com/river",
+            "license_id": "cc-by",
+            "metadata_modified": "2022-08-01T10:00:00.123456",
+            "author": "Hydrology team",
+            "author_email": "hydro@example.com",
+            "maintainer": "Data desk",
+            "maintainer_email": "data@example.com",
+            "flood_studies": "study-1",
+            "tags": [{"name": "water"}, {"name": "rivers"}],
+            "resources": [{
+                "name": "Levels",
+                "url": "https://example.com/levels.csv",
+                "format": "CSV",
+                "size": 2048
+            }]
+        })
+    }
+
+    #[test]
+    fn test_jsonld_roundtrip() {
+        let data = to_jsonld(&[package()], &mapping());
+        let dataset = &data["@graph"][0];
+        assert_eq!(
+            json!("https://data.example.com/dataset/river-levels"),
+            dataset["@id"]
+        );
+        assert_eq!(json!("study-1"), dataset["flood:study"]);
+
+        let packages = from_jsonld(&data, &mapping()).unwrap();
+        assert_eq!(vec![expected()], packages);
+    }
+
+    #[test]
+    fn test_turtle_roundtrip() {
+        let turtle = to_turtle(&[package()], &mapping());
+        assert!(turtle.contains("a dcat:Dataset"), "{}", turtle);
+
+        let packages = from_turtle(&turtle, &mapping()).unwrap();
+        assert_eq!(vec![expected()], packages);
+    }
+
+    #[test]
+    fn test_name_from_title() {
+        let packages = from_turtle(
+            r#"
+@prefix dcat: <http://www.w3.org/ns/dcat#> .
+@prefix dct: <http://purl.org/dc/terms/> .
+[] a dcat:Dataset ; dct:title "Flood Study (2022)" .
+"#,
+            &mapping(),
+        )
+        .unwrap();
+        assert_eq!(json!("flood-study-2022"), packages[0]["name"]);
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(Some("a-b_c".into()), slugify(" A b_c! "));
+        assert_eq!(None, slugify("?"));
+    }
+}
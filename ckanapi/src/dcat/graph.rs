@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Map, Value};
+
+use super::DcatError;
+
+pub(crate) const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub(crate) const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+const DEFAULT_PREFIXES: &[(&str, &str)] = &[
+    ("dcat", "http://www.w3.org/ns/dcat#"),
+    ("dct", "http://purl.org/dc/terms/"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("vcard", "http://www.w3.org/2006/vcard/ns#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("xsd", XSD),
+];
+
+/// Node or value of the RDF graph.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Term {
+    Iri(String),
+    Blank(String),
+    Literal {
+        value: String,
+        language: Option<String>,
+        datatype: Option<String>,
+    },
+}
+
+impl Term {
+    pub fn literal<T: Into<String>>(value: T) -> Self {
+        Self::Literal {
+            value: value.into(),
+            language: None,
+            datatype: None,
+        }
+    }
+
+    /// Literal with a datatype from the XML Schema namespace, e.g. `dateTime`.
+    pub fn typed<T: Into<String>>(value: T, xsd_type: &str) -> Self {
+        Self::Literal {
+            value: value.into(),
+            language: None,
+            datatype: Some(format!("{}{}", XSD, xsd_type)),
+        }
+    }
+
+    /// Lexical value of the literal, IRI itself or label of the blank node.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Iri(v) | Self::Blank(v) => v,
+            Self::Literal { value, .. } => value,
+        }
+    }
+
+    pub fn is_node(&self) -> bool {
+        !matches!(self, Self::Literal { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Triple {
+    pub subject: Term,
+    /// Absolute IRI of the predicate.
+    pub predicate: String,
+    pub object: Term,
+}
+
+/// Set of triples together with the prefixes used for the compact
+/// serialization.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    pub prefixes: Vec<(String, String)>,
+    pub triples: Vec<Triple>,
+    blanks: usize,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+impl Graph {
+    /// Create an empty graph that knows DCAT-related prefixes and the
+    /// additional `prefixes`.
+    pub fn new(prefixes: &[(String, String)]) -> Self {
+        let mut graph = Self {
+            prefixes: Vec::new(),
+            triples: Vec::new(),
+            blanks: 0,
+        };
+        for (prefix, namespace) in DEFAULT_PREFIXES {
+            graph.add_prefix(*prefix, *namespace);
+        }
+        for (prefix, namespace) in prefixes {
+            graph.add_prefix(prefix.as_str(), namespace.as_str());
+        }
+        graph
+    }
+
+    pub fn add_prefix<P: Into<String>, N: Into<String>>(&mut self, prefix: P, namespace: N) {
+        let (prefix, namespace) = (prefix.into(), namespace.into());
+        match self.prefixes.iter_mut().find(|(p, _)| *p == prefix) {
+            Some(entry) => entry.1 = namespace,
+            None => self.prefixes.push((prefix, namespace)),
+        }
+    }
+
+    /// Turn compact IRI(`dct:title`) into the absolute one. Absolute IRIs and
+    /// names with unknown prefixes are returned unchanged.
+    pub fn expand(&self, name: &str) -> String {
+        if let Some((prefix, local)) = name.split_once(':') {
+            if !local.starts_with("//") {
+                if let Some((_, ns)) = self.prefixes.iter().find(|(p, _)| p == prefix) {
+                    return format!("{}{}", ns, local);
+                }
+            }
+        }
+        name.to_string()
+    }
+
+    /// Shorten IRI using the longest matching namespace.
+    pub fn compact(&self, iri: &str) -> Option<String> {
+        self.prefixes
+            .iter()
+            .filter(|(_, ns)| iri.starts_with(ns.as_str()))
+            .max_by_key(|(_, ns)| ns.len())
+            .map(|(prefix, ns)| (prefix, &iri[ns.len()..]))
+            .filter(|(_, local)| {
+                !local.is_empty()
+                    && local
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            })
+            .map(|(prefix, local)| format!("{}:{}", prefix, local))
+    }
+
+    /// Allocate a new blank node.
+    pub fn blank(&mut self) -> Term {
+        self.blanks += 1;
+        Term::Blank(format!("b{}", self.blanks))
+    }
+
+    /// Add a triple. Predicate may be either compact or absolute IRI.
+    pub fn add(&mut self, subject: Term, predicate: &str, object: Term) {
+        let predicate = self.expand(predicate);
+        self.triples.push(Triple {
+            subject,
+            predicate,
+            object,
+        });
+    }
+
+    /// Objects of the triples with the given subject and predicate.
+    pub fn objects<'a>(
+        &'a self,
+        subject: &'a Term,
+        predicate: &str,
+    ) -> impl Iterator<Item = &'a Term> + 'a {
+        let predicate = self.expand(predicate);
+        self.triples
+            .iter()
+            .filter(move |t| t.subject == *subject && t.predicate == predicate)
+            .map(|t| &t.object)
+    }
+
+    /// First object of the triples with the given subject and predicate.
+    pub fn object<'a>(&'a self, subject: &'a Term, predicate: &str) -> Option<&'a Term> {
+        self.objects(subject, predicate).next()
+    }
+
+    /// Nodes declared with `rdf:type` equal to `class`, in order of appearance.
+    pub fn instances(&self, class: &str) -> Vec<Term> {
+        let class = Term::Iri(self.expand(class));
+        let mut found: Vec<Term> = Vec::new();
+        for t in &self.triples {
+            if t.predicate == RDF_TYPE && t.object == class && !found.contains(&t.subject) {
+                found.push(t.subject.clone());
+            }
+        }
+        found
+    }
+
+    fn subjects(&self) -> Vec<&Term> {
+        let mut subjects: Vec<&Term> = Vec::new();
+        for t in &self.triples {
+            if !subjects.contains(&&t.subject) {
+                subjects.push(&t.subject);
+            }
+        }
+        subjects
+    }
+
+    fn compact_or_full(&self, iri: &str) -> String {
+        self.compact(iri).unwrap_or_else(|| iri.to_string())
+    }
+
+    /// Serialize the graph as JSON-LD document with a flat `@graph`.
+    pub fn to_jsonld(&self) -> Value {
+        let context: Map<String, Value> = self
+            .prefixes
+            .iter()
+            .map(|(p, ns)| (p.clone(), Value::from(ns.as_str())))
+            .collect();
+
+        let nodes: Vec<Value> = self
+            .subjects()
+            .into_iter()
+            .map(|subject| {
+                let mut node = Map::new();
+                node.insert("@id".into(), Value::from(self.node_id(subject)));
+
+                for t in self.triples.iter().filter(|t| t.subject == *subject) {
+                    let (key, value) = if t.predicate == RDF_TYPE {
+                        (
+                            "@type".to_string(),
+                            Value::from(self.compact_or_full(t.object.as_str())),
+                        )
+                    } else {
+                        (
+                            self.compact_or_full(&t.predicate),
+                            self.jsonld_value(&t.object),
+                        )
+                    };
+                    match node.get_mut(&key) {
+                        None => {
+                            node.insert(key, value);
+                        }
+                        Some(Value::Array(items)) => items.push(value),
+                        Some(existing) => *existing = json!([existing.take(), value]),
+                    }
+                }
+                Value::Object(node)
+            })
+            .collect();
+
+        json!({"@context": context, "@graph": nodes})
+    }
+
+    fn node_id(&self, term: &Term) -> String {
+        match term {
+            Term::Blank(label) => format!("_:{}", label),
+            other => other.as_str().to_string(),
+        }
+    }
+
+    fn jsonld_value(&self, term: &Term) -> Value {
+        match term {
+            Term::Iri(_) | Term::Blank(_) => json!({"@id": self.node_id(term)}),
+            Term::Literal {
+                value,
+                language: Some(lang),
+                ..
+            } => json!({"@value": value, "@language": lang}),
+            Term::Literal {
+                value,
+                datatype: Some(dt),
+                ..
+            } => json!({"@value": value, "@type": self.compact_or_full(dt)}),
+            Term::Literal { value, .. } => Value::from(value.as_str()),
+        }
+    }
+
+    /// Add triples from the JSON-LD document.
+    ///
+    /// Only embedded contexts are taken into account: remote contexts are
+    /// ignored and prefixes known to the graph are used instead.
+    pub fn load_jsonld(&mut self, data: &Value) -> Result<(), DcatError> {
+        if let Some(context) = data.get("@context").and_then(|c| c.as_object()) {
+            for (term, definition) in context {
+                let iri = match definition {
+                    Value::String(iri) => iri.as_str(),
+                    Value::Object(def) => match def.get("@id").and_then(|id| id.as_str()) {
+                        Some(iri) => iri,
+                        None => continue,
+                    },
+                    _ => continue,
+                };
+                if !term.starts_with('@') {
+                    let iri = self.expand(iri);
+                    self.add_prefix(term.as_str(), iri);
+                }
+            }
+        }
+
+        let nodes = match data {
+            Value::Array(nodes) => nodes.iter().collect::<Vec<_>>(),
+            Value::Object(obj) => match obj.get("@graph") {
+                Some(Value::Array(nodes)) => nodes.iter().collect(),
+                Some(node) => vec![node],
+                None => vec![data],
+            },
+            _ => {
+                return Err(DcatError::Format(
+                    "JSON-LD document must be an object".into(),
+                ))
+            }
+        };
+
+        let mut blanks = HashMap::new();
+        for node in nodes {
+            self.jsonld_node(node, &mut blanks)?;
+        }
+        Ok(())
+    }
+
+    fn jsonld_id(&mut self, id: Option<&str>, blanks: &mut HashMap<String, Term>) -> Term {
+        match id {
+            Some(label) if label.starts_with("_:") => {
+                if let Some(term) = blanks.get(label) {
+                    return term.clone();
+                }
+                let term = self.blank();
+                blanks.insert(label.to_string(), term.clone());
+                term
+            }
+            Some(iri) => Term::Iri(self.expand(iri)),
+            None => self.blank(),
+        }
+    }
+
+    fn jsonld_node(
+        &mut self,
+        node: &Value,
+        blanks: &mut HashMap<String, Term>,
+    ) -> Result<Term, DcatError> {
+        let obj = node
+            .as_object()
+            .ok_or_else(|| DcatError::Format(format!("Node must be an object: {}", node)))?;
+        let subject = self.jsonld_id(obj.get("@id").and_then(|id| id.as_str()), blanks);
+
+        for (key, value) in obj {
+            let values = match value {
+                Value::Array(items) => items.iter().collect(),
+                single => vec![single],
+            };
+
+            if key == "@type" {
+                for class in values.into_iter().filter_map(|v| v.as_str()) {
+                    let class = Term::Iri(self.expand(class));
+                    self.add(subject.clone(), RDF_TYPE, class);
+                }
+                continue;
+            } else if key.starts_with('@') {
+                continue;
+            }
+
+            let predicate = self.expand(key);
+            for value in values {
+                if let Some(object) = self.jsonld_object(value, blanks)? {
+                    self.add(subject.clone(), &predicate, object);
+                }
+            }
+        }
+
+        Ok(subject)
+    }
+
+    fn jsonld_object(
+        &mut self,
+        value: &Value,
+        blanks: &mut HashMap<String, Term>,
+    ) -> Result<Option<Term>, DcatError> {
+        Ok(Some(match value {
+            Value::Null => return Ok(None),
+            Value::String(s) => Term::literal(s.as_str()),
+            Value::Bool(b) => Term::typed(b.to_string(), "boolean"),
+            Value::Number(n) if n.is_f64() => Term::typed(n.to_string(), "decimal"),
+            Value::Number(n) => Term::typed(n.to_string(), "integer"),
+            Value::Array(_) => {
+                return Err(DcatError::Format("Nested lists are not supported".into()))
+            }
+            Value::Object(obj) => match obj.get("@value") {
+                Some(literal) => {
+                    let value = match literal {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    Term::Literal {
+                        value,
+                        language: obj
+                            .get("@language")
+                            .and_then(|l| l.as_str())
+                            .map(String::from),
+                        datatype: obj
+                            .get("@type")
+                            .and_then(|t| t.as_str())
+                            .map(|t| self.expand(t)),
+                    }
+                }
+                None if obj.len() == 1 && obj.contains_key("@id") => {
+                    self.jsonld_id(obj["@id"].as_str(), blanks)
+                }
+                None => self.jsonld_node(value, blanks)?,
+            },
+        }))
+    }
+
+    /// Serialize the graph as Turtle document.
+    pub fn to_turtle(&self) -> String {
+        let mut out = String::new();
+        for (prefix, ns) in &self.prefixes {
+            out.push_str(&format!("@prefix {}: <{}> .\n", prefix, ns));
+        }
+
+        for subject in self.subjects() {
+            out.push('\n');
+            out.push_str(&self.turtle_term(subject));
+
+            let mut predicates: Vec<&str> = Vec::new();
+            for t in self.triples.iter().filter(|t| t.subject == *subject) {
+                if !predicates.contains(&t.predicate.as_str()) {
+                    predicates.push(&t.predicate);
+                }
+            }
+
+            let lines: Vec<String> = predicates
+                .into_iter()
+                .map(|predicate| {
+                    let verb = if predicate == RDF_TYPE {
+                        "a".to_string()
+                    } else {
+                        self.turtle_term(&Term::Iri(predicate.to_string()))
+                    };
+                    let objects: Vec<String> = self
+                        .triples
+                        .iter()
+                        .filter(|t| t.subject == *subject && t.predicate == predicate)
+                        .map(|t| self.turtle_term(&t.object))
+                        .collect();
+                    format!("    {} {}", verb, objects.join(", "))
+                })
+                .collect();
+            out.push('\n');
+            out.push_str(&lines.join(" ;\n"));
+            out.push_str(" .\n");
+        }
+        out
+    }
+
+    fn turtle_term(&self, term: &Term) -> String {
+        match term {
+            Term::Iri(iri) => self.compact(iri).unwrap_or_else(|| format!("<{}>", iri)),
+            Term::Blank(label) => format!("_:{}", label),
+            Term::Literal {
+                value,
+                language,
+                datatype,
+            } => {
+                let mut escaped = String::with_capacity(value.len() + 2);
+                escaped.push('"');
+                for c in value.chars() {
+                    match c {
+                        '"' => escaped.push_str("\\\""),
+                        '\\' => escaped.push_str("\\\\"),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        '\t' => escaped.push_str("\\t"),
+                        c => escaped.push(c),
+                    }
+                }
+                escaped.push('"');
+                if let Some(lang) = language {
+                    escaped.push('@');
+                    escaped.push_str(lang);
+                } else if let Some(dt) = datatype {
+                    escaped.push_str("^^");
+                    escaped.push_str(&self.turtle_term(&Term::Iri(dt.clone())));
+                }
+                escaped
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_and_compact() {
+        let graph = Graph::default();
+        assert_eq!("http://purl.org/dc/terms/title", graph.expand("dct:title"));
+        assert_eq!("http://example.com/a", graph.expand("http://example.com/a"));
+        assert_eq!("unknown:a", graph.expand("unknown:a"));
+        assert_eq!(
+            Some("dct:title".into()),
+            graph.compact("http://purl.org/dc/terms/title")
+        );
+        assert_eq!(None, graph.compact("http://purl.org/dc/terms/a/b"));
+    }
+
+    #[test]
+    fn test_jsonld_roundtrip() {
+        let mut graph = Graph::default();
+        let dataset = Term::Iri("http://example.com/dataset/a".into());
+        let agent = graph.blank();
+        graph.add(
+            dataset.clone(),
+            RDF_TYPE,
+            Term::Iri(graph.expand("dcat:Dataset")),
+        );
+        graph.add(dataset.clone(), "dcat:keyword", Term::literal("one"));
+        graph.add(dataset.clone(), "dcat:keyword", Term::literal("two"));
+        graph.add(
+            dataset.clone(),
+            "dct:issued",
+            Term::typed("2022-01-01", "date"),
+        );
+        graph.add(dataset, "dct:creator", agent.clone());
+        graph.add(agent, "foaf:name", Term::literal("Author"));
+
+        let data = graph.to_jsonld();
+        assert_eq!(json!(["one", "two"]), data["@graph"][0]["dcat:keyword"]);
+        assert_eq!(json!("dcat:Dataset"), data["@graph"][0]["@type"]);
+
+        let mut parsed = Graph::default();
+        parsed.load_jsonld(&data).unwrap();
+        assert_eq!(graph.triples.len(), parsed.triples.len());
+        for triple in &graph.triples {
+            assert!(parsed.triples.contains(triple), "Missing {:?}", triple);
+        }
+    }
+
+    #[test]
+    fn test_jsonld_embedded_nodes() {
+        let mut graph = Graph::default();
+        graph
+            .load_jsonld(&json!({
+                "@context": {"ex": "http://example.com/ns#"},
+                "@id": "http://example.com/a",
+                "@type": ["dcat:Dataset"],
+                "ex:size": 10,
+                "dcat:contactPoint": {"vcard:fn": "Contact"}
+            }))
+            .unwrap();
+
+        let subject = Term::Iri("http://example.com/a".into());
+        assert_eq!(vec![subject.clone()], graph.instances("dcat:Dataset"));
+        assert_eq!(
+            Some(&Term::typed("10", "integer")),
+            graph.object(&subject, "http://example.com/ns#size")
+        );
+        let contact = graph.object(&subject, "dcat:contactPoint").unwrap().clone();
+        assert_eq!(
+            "Contact",
+            graph.object(&contact, "vcard:fn").unwrap().as_str()
+        );
+    }
+}
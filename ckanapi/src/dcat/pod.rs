@@ -0,0 +1,224 @@
+use serde_json::{json, Map, Value};
+
+use super::{field_value, slugify, tag_names, typed_value, DcatError, DcatMapping, FieldMapping};
+
+const CONFORMS_TO: &str = "https://project-open-data.cio.gov/v1.1/schema";
+const CONTEXT: &str = "https://project-open-data.cio.gov/v1.1/schema/catalog.jsonld";
+
+fn write_mapped(data: &Value, mappings: &[FieldMapping], entry: &mut Map<String, Value>) {
+    for mapping in mappings {
+        if let (Some(key), Some(value)) = (&mapping.data_json, field_value(data, &mapping.field)) {
+            entry.insert(key.clone(), typed_value(&value, mapping.kind));
+        }
+    }
+}
+
+fn read_mapped(entry: &Value, mappings: &[FieldMapping]) -> Map<String, Value> {
+    let mut data = Map::new();
+    for mapping in mappings {
+        if let Some(key) = &mapping.data_json {
+            if let Some(value) = field_value(entry, key) {
+                data.insert(mapping.field.clone(), typed_value(&value, mapping.kind));
+            }
+        }
+    }
+    data
+}
+
+/// Serialize packages as Project Open Data(v1.1) catalog.
+pub fn to_data_json(packages: &[Value], mapping: &DcatMapping) -> Value {
+    let datasets: Vec<Value> = packages
+        .iter()
+        .map(|package| {
+            let mut entry = Map::new();
+            entry.insert("@type".into(), json!("dcat:Dataset"));
+            write_mapped(package, &mapping.dataset, &mut entry);
+
+            if let Some(id) = field_value(package, "id").or_else(|| field_value(package, "name")) {
+                entry.insert("identifier".into(), Value::from(id));
+            }
+            let access = if package["private"].as_bool().unwrap_or(false) {
+                "non-public"
+            } else {
+                "public"
+            };
+            entry.insert("accessLevel".into(), json!(access));
+            entry.insert("keyword".into(), json!(tag_names(package)));
+
+            if let Some(name) = field_value(&package["organization"], "title") {
+                entry.insert(
+                    "publisher".into(),
+                    json!({"@type": "org:Organization", "name": name}),
+                );
+            }
+
+            let contact =
+                field_value(package, "maintainer").or_else(|| field_value(package, "author"));
+            let email = field_value(package, "maintainer_email")
+                .or_else(|| field_value(package, "author_email"));
+            if contact.is_some() || email.is_some() {
+                let mut point = json!({"@type": "vcard:Contact"});
+                if let Some(contact) = contact {
+                    point["fn"] = Value::from(contact);
+                }
+                if let Some(email) = email {
+                    point["hasEmail"] = Value::from(format!("mailto:{}", email));
+                }
+                entry.insert("contactPoint".into(), point);
+            }
+
+            let distributions: Vec<Value> = package["resources"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|resource| {
+                    let mut distribution = Map::new();
+                    distribution.insert("@type".into(), json!("dcat:Distribution"));
+                    write_mapped(resource, &mapping.distribution, &mut distribution);
+                    Value::Object(distribution)
+                })
+                .collect();
+            if !distributions.is_empty() {
+                entry.insert("distribution".into(), Value::from(distributions));
+            }
+
+            Value::Object(entry)
+        })
+        .collect();
+
+    json!({
+        "@context": CONTEXT,
+        "@type": "dcat:Catalog",
+        "conformsTo": CONFORMS_TO,
+        "dataset": datasets,
+    })
+}
+
+/// Parse Project Open Data catalog(or a list of its datasets) into package
+/// dicts.
+pub fn from_data_json(data: &Value, mapping: &DcatMapping) -> Result<Vec<Value>, DcatError> {
+    let datasets = match data {
+        Value::Array(datasets) => datasets,
+        Value::Object(catalog) => match catalog.get("dataset") {
+            Some(Value::Array(datasets)) => datasets,
+            _ => return Err(DcatError::Format("Catalog has no `dataset` list".into())),
+        },
+        _ => return Err(DcatError::Format("data.json must be an object".into())),
+    };
+
+    datasets
+        .iter()
+        .map(|entry| {
+            if !entry.is_object() {
+                return Err(DcatError::Format(format!(
+                    "Dataset must be an object: {}",
+                    entry
+                )));
+            }
+            let mut package = read_mapped(entry, &mapping.dataset);
+
+            if !package.contains_key("name") {
+                let name = package
+                    .get("title")
+                    .and_then(|t| t.as_str())
+                    .and_then(slugify);
+                if let Some(name) = name {
+                    package.insert("name".into(), Value::from(name));
+                }
+            }
+
+            let tags: Vec<Value> = entry["keyword"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|kw| kw.as_str())
+                .map(|kw| json!({ "name": kw }))
+                .collect();
+            if !tags.is_empty() {
+                package.insert("tags".into(), Value::from(tags));
+            }
+
+            let contact = &entry["contactPoint"];
+            if let Some(name) = field_value(contact, "fn") {
+                package.insert("maintainer".into(), Value::from(name));
+            }
+            if let Some(email) = field_value(contact, "hasEmail") {
+                let email = email.trim_start_matches("mailto:");
+                package.insert("maintainer_email".into(), Value::from(email));
+            }
+            if entry["accessLevel"] == "non-public" {
+                package.insert("private".into(), Value::from(true));
+            }
+
+            let resources: Vec<Value> = entry["distribution"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|distribution| Value::Object(read_mapped(distribution, &mapping.distribution)))
+                .collect();
+            if !resources.is_empty() {
+                package.insert("resources".into(), Value::from(resources));
+            }
+
+            Ok(Value::Object(package))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{mapping, package};
+    use super::*;
+
+    #[test]
+    fn test_data_json_roundtrip() {
+        let data = to_data_json(&[package()], &mapping());
+        assert_eq!(json!(CONFORMS_TO), data["conformsTo"]);
+
+        let dataset = &data["dataset"][0];
+        assert_eq!(
+            json!("2b9c6e4e-1b4b-4a36-9a8e-4c1e0a0f4d11"),
+            dataset["identifier"]
+        );
+        assert_eq!(json!("public"), dataset["accessLevel"]);
+        assert_eq!(json!(["water", "rivers"]), dataset["keyword"]);
+        assert_eq!(
+            json!("mailto:data@example.com"),
+            dataset["contactPoint"]["hasEmail"]
+        );
+        assert_eq!(json!("Water Agency"), dataset["publisher"]["name"]);
+        assert_eq!(json!("study-1"), dataset["floodStudy"]);
+        assert_eq!(
+            json!("https://example.com/levels.csv"),
+            dataset["distribution"][0]["downloadURL"]
+        );
+
+        let packages = from_data_json(&data, &mapping()).unwrap();
+        assert_eq!(
+            vec![json!({
+                "name": "river-levels",
+                "title": "River levels",
+                "notes": "Daily \"river\" levels\nfor the catchment",
+                "url": "https://example.com/river",
+                "license_id": "cc-by",
+                "metadata_modified": "2022-08-01T10:00:00.123456",
+                "maintainer": "Data desk",
+                "maintainer_email": "data@example.com",
+                "flood_studies": "study-1",
+                "tags": [{"name": "water"}, {"name": "rivers"}],
+                "resources": [{
+                    "name": "Levels",
+                    "url": "https://example.com/levels.csv",
+                    "format": "CSV"
+                }]
+            })],
+            packages
+        );
+    }
+
+    #[test]
+    fn test_data_json_invalid() {
+        assert!(from_data_json(&json!("catalog"), &mapping()).is_err());
+        assert!(from_data_json(&json!({"dataset": [1]}), &mapping()).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+
+use super::graph::{Graph, Term, RDF_TYPE};
+use super::DcatError;
+
+/// Recursive-descent parser for the subset of Turtle produced by the DCAT
+/// tooling: prefixes, base, IRIs, literals, blank nodes and property lists.
+/// Collections are not supported.
+struct Parser<'a> {
+    graph: &'a mut Graph,
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    base: Option<String>,
+    blanks: HashMap<String, Term>,
+}
+
+impl Graph {
+    /// Add triples from the Turtle document.
+    pub fn load_turtle(&mut self, source: &str) -> Result<(), DcatError> {
+        let mut parser = Parser {
+            graph: self,
+            chars: source.chars().collect(),
+            pos: 0,
+            line: 1,
+            base: None,
+            blanks: HashMap::new(),
+        };
+        parser.document()
+    }
+}
+
+impl<'a> Parser<'a> {
+    fn error<T, M: Into<String>>(&self, message: M) -> Result<T, DcatError> {
+        Err(DcatError::Syntax {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), DcatError> {
+        self.skip_ws();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.error(format!("Expected '{}', found '{}'", expected, c)),
+            None => self.error(format!("Expected '{}', found end of document", expected)),
+        }
+    }
+
+    fn starts_with_keyword(&self, keyword: &str) -> bool {
+        let len = keyword.chars().count();
+        let word: String = self.chars.iter().skip(self.pos).take(len).collect();
+        word.eq_ignore_ascii_case(keyword)
+            && self
+                .peek_at(len)
+                .is_none_or(|c| c.is_whitespace() || c == '<')
+    }
+
+    fn document(&mut self) -> Result<(), DcatError> {
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(()),
+                Some('@') => {
+                    self.bump();
+                    let name = self.name();
+                    match name.as_str() {
+                        "prefix" => self.prefix()?,
+                        "base" => self.base()?,
+                        _ => return self.error(format!("Unknown directive @{}", name)),
+                    }
+                    self.expect('.')?;
+                }
+                Some(_) if self.starts_with_keyword("prefix") => {
+                    self.pos += "prefix".len();
+                    self.prefix()?;
+                }
+                Some(_) if self.starts_with_keyword("base") => {
+                    self.pos += "base".len();
+                    self.base()?;
+                }
+                Some(_) => {
+                    self.triples()?;
+                    self.expect('.')?;
+                }
+            }
+        }
+    }
+
+    fn prefix(&mut self) -> Result<(), DcatError> {
+        self.skip_ws();
+        let name = self.name();
+        let prefix = match name.strip_suffix(':') {
+            Some(prefix) => prefix.to_string(),
+            None => return self.error(format!("Invalid prefix declaration '{}'", name)),
+        };
+        self.skip_ws();
+        let namespace = self.iri_ref()?;
+        self.graph.add_prefix(prefix, namespace);
+        Ok(())
+    }
+
+    fn base(&mut self) -> Result<(), DcatError> {
+        self.skip_ws();
+        self.base = Some(self.iri_ref()?);
+        Ok(())
+    }
+
+    /// Read prefixed name, keyword or number. Trailing dot is left in place
+    /// because it terminates the statement.
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '%' | '+') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        while self.pos > start && self.chars[self.pos - 1] == '.' {
+            self.pos -= 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn iri_ref(&mut self) -> Result<String, DcatError> {
+        if self.bump() != Some('<') {
+            return self.error("Expected IRI");
+        }
+        let mut iri = String::new();
+        loop {
+            match self.bump() {
+                Some('>') => break,
+                Some('\\') => iri.push(self.escape()?),
+                Some(c) if c.is_whitespace() => return self.error("Whitespace inside IRI"),
+                Some(c) => iri.push(c),
+                None => return self.error("Unterminated IRI"),
+            }
+        }
+        match &self.base {
+            Some(base) if !iri.contains(':') => Ok(format!("{}{}", base, iri)),
+            _ => Ok(iri),
+        }
+    }
+
+    fn iri(&mut self) -> Result<String, DcatError> {
+        self.skip_ws();
+        if self.peek() == Some('<') {
+            return self.iri_ref();
+        }
+        let name = self.name();
+        match name.split_once(':') {
+            Some((prefix, local)) => match self.graph.prefixes.iter().find(|(p, _)| p == prefix) {
+                Some((_, ns)) => Ok(format!("{}{}", ns, local)),
+                None => self.error(format!("Unknown prefix '{}'", prefix)),
+            },
+            None => self.error(format!("Expected IRI, found '{}'", name)),
+        }
+    }
+
+    fn blank_label(&mut self) -> Term {
+        self.pos += 2;
+        let label = self.name();
+        if let Some(term) = self.blanks.get(&label) {
+            return term.clone();
+        }
+        let term = self.graph.blank();
+        self.blanks.insert(label, term.clone());
+        term
+    }
+
+    fn triples(&mut self) -> Result<(), DcatError> {
+        self.skip_ws();
+        if self.peek() == Some('[') {
+            let subject = self.property_list()?;
+            self.skip_ws();
+            if self.peek() != Some('.') {
+                self.predicate_objects(&subject)?;
+            }
+            return Ok(());
+        }
+
+        let subject = self.subject()?;
+        self.predicate_objects(&subject)
+    }
+
+    fn subject(&mut self) -> Result<Term, DcatError> {
+        self.skip_ws();
+        if self.peek() == Some('_') && self.peek_at(1) == Some(':') {
+            Ok(self.blank_label())
+        } else {
+            Ok(Term::Iri(self.iri()?))
+        }
+    }
+
+    fn predicate_objects(&mut self, subject: &Term) -> Result<(), DcatError> {
+        loop {
+            self.skip_ws();
+            let predicate =
+                if self.peek() == Some('a') && self.peek_at(1).is_none_or(|c| c.is_whitespace()) {
+                    self.bump();
+                    RDF_TYPE.to_string()
+                } else {
+                    self.iri()?
+                };
+
+            loop {
+                let object = self.object()?;
+                self.graph.add(subject.clone(), &predicate, object);
+                self.skip_ws();
+                if self.peek() == Some(',') {
+                    self.bump();
+                } else {
+                    break;
+                }
+            }
+
+            self.skip_ws();
+            if self.peek() != Some(';') {
+                return Ok(());
+            }
+            while self.peek() == Some(';') {
+                self.bump();
+                self.skip_ws();
+            }
+            if matches!(self.peek(), Some('.') | Some(']')) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn property_list(&mut self) -> Result<Term, DcatError> {
+        self.expect('[')?;
+        let node = self.graph.blank();
+        self.skip_ws();
+        if self.peek() != Some(']') {
+            self.predicate_objects(&node)?;
+        }
+        self.expect(']')?;
+        Ok(node)
+    }
+
+    fn object(&mut self) -> Result<Term, DcatError> {
+        self.skip_ws();
+        match self.peek() {
+            Some('[') => self.property_list(),
+            Some('(') => self.error("Collections are not supported"),
+            Some('"') | Some('\'') => self.literal(),
+            Some('_') if self.peek_at(1) == Some(':') => Ok(self.blank_label()),
+            Some('<') => Ok(Term::Iri(self.iri_ref()?)),
+            Some(c) if c.is_ascii_digit() || c == '+' || c == '-' => {
+                let number = self.name();
+                let xsd_type = if number.contains(['e', 'E']) {
+                    "double"
+                } else if number.contains('.') {
+                    "decimal"
+                } else {
+                    "integer"
+                };
+                Ok(Term::typed(number, xsd_type))
+            }
+            Some(_) if self.starts_with_keyword("true") || self.starts_with_keyword("false") => {
+                let value = self.name();
+                Ok(Term::typed(value, "boolean"))
+            }
+            Some(_) => Ok(Term::Iri(self.iri()?)),
+            None => self.error("Expected object, found end of document"),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Term, DcatError> {
+        let quote = self.bump().unwrap_or('"');
+        let long = self.peek() == Some(quote) && self.peek_at(1) == Some(quote);
+        if long {
+            self.pos += 2;
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => return self.error("Unterminated string"),
+                Some('\\') => value.push(self.escape()?),
+                Some(c) if c == quote && !long => break,
+                Some(c)
+                    if c == quote
+                        && self.peek() == Some(quote)
+                        && self.peek_at(1) == Some(quote) =>
+                {
+                    self.pos += 2;
+                    break;
+                }
+                Some('\n') if !long => return self.error("Line break inside short string"),
+                Some(c) => value.push(c),
+            }
+        }
+
+        let mut language = None;
+        let mut datatype = None;
+        if self.peek() == Some('@') {
+            self.bump();
+            language = Some(self.name());
+        } else if self.peek() == Some('^') && self.peek_at(1) == Some('^') {
+            self.pos += 2;
+            datatype = Some(self.iri()?);
+        }
+
+        Ok(Term::Literal {
+            value,
+            language,
+            datatype,
+        })
+    }
+
+    fn escape(&mut self) -> Result<char, DcatError> {
+        let c = match self.bump() {
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some(c @ ('"' | '\'' | '\\')) => c,
+            Some(u @ ('u' | 'U')) => {
+                let len = if u == 'u' { 4 } else { 8 };
+                let code: String = (0..len).filter_map(|_| self.bump()).collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(c) => c,
+                    None => return self.error(format!("Invalid unicode escape '{}'", code)),
+                }
+            }
+            Some(c) => return self.error(format!("Unknown escape sequence '\\{}'", c)),
+            None => return self.error("Unterminated escape sequence"),
+        };
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_turtle() {
+        let mut graph = Graph::default();
+        graph
+            .load_turtle(
+                r#"
+@prefix ex: <http://example.com/> .
+PREFIX dcat: <http://www.w3.org/ns/dcat#>
+
+# comment
+ex:a a dcat:Dataset ;
+    <http://purl.org/dc/terms/title> "Title"@en ;
+    dcat:keyword "one", 'two' ;
+    ex:size 10 ;
+    ex:long """multi
+line""" ;
+    dcat:contactPoint [ ex:name "Contact \"quoted\"" ] ;
+    ex:other _:x .
+
+_:x ex:name "X"^^<http://www.w3.org/2001/XMLSchema#string> .
+"#,
+            )
+            .unwrap();
+
+        let a = Term::Iri("http://example.com/a".into());
+        assert_eq!(vec![a.clone()], graph.instances("dcat:Dataset"));
+        assert_eq!(
+            Some(&Term::Literal {
+                value: "Title".into(),
+                language: Some("en".into()),
+                datatype: None
+            }),
+            graph.object(&a, "dct:title")
+        );
+        assert_eq!(2, graph.objects(&a, "dcat:keyword").count());
+        assert_eq!(
+            Some(&Term::typed("10", "integer")),
+            graph.object(&a, "ex:size")
+        );
+        assert_eq!("multi\nline", graph.object(&a, "ex:long").unwrap().as_str());
+
+        let contact = graph.object(&a, "dcat:contactPoint").unwrap().clone();
+        assert_eq!(
+            "Contact \"quoted\"",
+            graph.object(&contact, "ex:name").unwrap().as_str()
+        );
+        let other = graph.object(&a, "ex:other").unwrap().clone();
+        assert_eq!("X", graph.object(&other, "ex:name").unwrap().as_str());
+    }
+
+    #[test]
+    fn test_syntax_error_has_line() {
+        let mut graph = Graph::default();
+        match graph.load_turtle("\n\nunknown:a a <b> .") {
+            Err(DcatError::Syntax { line, .. }) => assert_eq!(3, line),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_turtle_roundtrip() {
+        let mut graph = Graph::default();
+        let node = Term::Iri("http://example.com/dataset/a".into());
+        let blank = graph.blank();
+        graph.add(
+            node.clone(),
+            RDF_TYPE,
+            Term::Iri(graph.expand("dcat:Dataset")),
+        );
+        graph.add(
+            node.clone(),
+            "dct:title",
+            Term::literal("Line\n\"quoted\"\\"),
+        );
+        graph.add(
+            node.clone(),
+            "dct:issued",
+            Term::typed("2022-01-01", "date"),
+        );
+        graph.add(node, "dcat:contactPoint", blank.clone());
+        graph.add(blank, "vcard:fn", Term::literal("Name"));
+
+        let mut parsed = Graph::default();
+        parsed.load_turtle(&graph.to_turtle()).unwrap();
+        assert_eq!(graph.triples, parsed.triples);
+    }
+}
@@ -1,5 +1,15 @@
 #![doc = include_str!("../README.md")]
+mod batch;
 mod ckan;
+mod collaborator;
+pub mod dcat;
+pub mod linkcheck;
+mod view;
 
 
-pub use ckan::{CKAN, Action, Params, MultipartField, CKANError};
+pub use batch::{Batch, BatchOptions, BatchSummary};
+pub use ckan::{
+    Action, CKANError, Fail, MultipartField, Params, RequestBuilder, Response, Success, CKAN,
+};
+pub use collaborator::{Capacity, Collaborator};
+pub use view::{default_view_types, ResourceView};
@@ -0,0 +1,563 @@
+//! Availability check of the resource URLs.
+//!
+//! Resources are collected from the `package_search` results and every URL is
+//! requested with `HEAD`. Servers that do not support `HEAD` are asked for
+//! the first byte of the file with a ranged `GET`. Redirects are followed
+//! manually, so the whole chain ends up in the report.
+//!
+//! ```no_run
+//! # use ckanapi::CKAN;
+//! # use ckanapi::linkcheck::{LinkCheckOptions, Scope};
+//! # async fn run(client: CKAN) {
+//! let options = LinkCheckOptions::default().writeback("link_status");
+//! let report = client
+//!     .check_links(&Scope::Organization("water".into()), &options)
+//!     .await
+//!     .unwrap();
+//! std::fs::write("links.csv", report.to_csv().unwrap()).unwrap();
+//! # }
+//! ```
+use std::time::Duration;
+
+use futures::stream::{self, StreamExt};
+use reqwest::{header, redirect::Policy, Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{BatchOptions, CKANError, Params, CKAN};
+
+const PAGE_SIZE: u64 = 100;
+
+/// Datasets whose resources are checked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    /// Solr query, passed as `q` to `package_search`.
+    Query(String),
+    /// Name or ID of the organization.
+    Organization(String),
+}
+
+impl Scope {
+    /// Filter query of the organization scope. `organization` holds the
+    /// name and `owner_org` the ID, so either of them matches.
+    ///
+    /// # Examples
+    /// ```
+    /// # use ckanapi::linkcheck::Scope;
+    /// assert_eq!(
+    ///     Some(r#"organization:"water" OR owner_org:"water""#.to_string()),
+    ///     Scope::Organization("water".into()).filter()
+    /// );
+    /// assert_eq!(None, Scope::Query("flood".into()).filter());
+    /// ```
+    pub fn filter(&self) -> Option<String> {
+        match self {
+            Scope::Query(_) => None,
+            Scope::Organization(org) => {
+                let org = org.replace('\\', "\\\\").replace('"', "\\\"");
+                Some(format!("organization:\"{}\" OR owner_org:\"{}\"", org, org))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkCheckOptions {
+    /// Maximum number of URLs checked at the same time.
+    pub concurrency: usize,
+    /// Timeout of every individual request.
+    pub timeout: Duration,
+    pub max_redirects: usize,
+    /// Resource field that receives the serialized [`LinkStatus`].
+    pub writeback: Option<String>,
+}
+
+impl Default for LinkCheckOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            timeout: Duration::from_secs(30),
+            max_redirects: 10,
+            writeback: None,
+        }
+    }
+}
+
+impl LinkCheckOptions {
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn writeback<T: Into<String>>(mut self, field: T) -> Self {
+        self.writeback = Some(field.into());
+        self
+    }
+}
+
+/// Result of the check of a single resource.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct LinkStatus {
+    pub package_id: String,
+    pub resource_id: String,
+    pub url: String,
+    pub ok: bool,
+    pub method: Option<String>,
+    pub status: Option<u16>,
+    pub content_type: Option<String>,
+    pub size: Option<u64>,
+    /// URLs visited before the final response, in order.
+    pub redirects: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct LinkReport {
+    pub results: Vec<LinkStatus>,
+}
+
+impl LinkReport {
+    pub fn broken(&self) -> impl Iterator<Item = &LinkStatus> {
+        self.results.iter().filter(|r| !r.ok)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    /// Render the report as CSV with a header row. Redirect chain is joined
+    /// with spaces.
+    pub fn to_csv(&self) -> Result<String, csv::Error> {
+        let mut wtr = csv::Writer::from_writer(Vec::new());
+        wtr.write_record([
+            "package_id",
+            "resource_id",
+            "url",
+            "ok",
+            "method",
+            "status",
+            "content_type",
+            "size",
+            "redirects",
+            "error",
+        ])?;
+        for r in &self.results {
+            wtr.write_record([
+                r.package_id.as_str(),
+                r.resource_id.as_str(),
+                r.url.as_str(),
+                if r.ok { "true" } else { "false" },
+                r.method.as_deref().unwrap_or_default(),
+                &r.status.map(|s| s.to_string()).unwrap_or_default(),
+                r.content_type.as_deref().unwrap_or_default(),
+                &r.size.map(|s| s.to_string()).unwrap_or_default(),
+                &r.redirects.join(" "),
+                r.error.as_deref().unwrap_or_default(),
+            ])?;
+        }
+        let data = wtr
+            .into_inner()
+            .map_err(|err| std::io::Error::new(err.error().kind(), err.error().to_string()))?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+/// HTTP client suitable for [`check_url`]: redirects are not followed
+/// automatically and every request is limited by the configured timeout.
+pub fn checker(options: &LinkCheckOptions) -> Result<Client, CKANError> {
+    Ok(Client::builder()
+        .redirect(Policy::none())
+        .timeout(options.timeout)
+        .build()?)
+}
+
+/// Request the URL, following redirects up to the configured limit.
+async fn request(
+    client: &Client,
+    method: Method,
+    url: &str,
+    options: &LinkCheckOptions,
+    status: &mut LinkStatus,
+) -> Result<reqwest::Response, String> {
+    let mut url = Url::parse(url).map_err(|err| err.to_string())?;
+    status.redirects.clear();
+    status.method = Some(method.to_string());
+
+    loop {
+        let mut req = client.request(method.clone(), url.clone());
+        if method == Method::GET {
+            req = req.header(header::RANGE, "bytes=0-0");
+        }
+        let resp = req.send().await.map_err(|err| err.to_string())?;
+        if !resp.status().is_redirection() {
+            return Ok(resp);
+        }
+
+        let location = resp
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .ok_or("Redirect without location")?;
+        let next = url.join(location).map_err(|err| err.to_string())?;
+        status.redirects.push(url.to_string());
+        if status.redirects.len() > options.max_redirects {
+            return Err("Too many redirects".into());
+        }
+        url = next;
+    }
+}
+
+fn content_size(resp: &reqwest::Response) -> Option<u64> {
+    let headers = resp.headers();
+    if resp.status() == StatusCode::PARTIAL_CONTENT {
+        headers
+            .get(header::CONTENT_RANGE)?
+            .to_str()
+            .ok()?
+            .rsplit('/')
+            .next()?
+            .parse()
+            .ok()
+    } else {
+        headers
+            .get(header::CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()
+    }
+}
+
+/// Check availability of the single URL.
+pub async fn check_url(client: &Client, url: &str, options: &LinkCheckOptions) -> LinkStatus {
+    let mut status = LinkStatus {
+        url: url.to_string(),
+        ..Default::default()
+    };
+
+    let mut resp = request(client, Method::HEAD, url, options, &mut status).await;
+    let head_rejected = matches!(
+        &resp,
+        Ok(r) if matches!(r.status().as_u16(), 400 | 403 | 405 | 501)
+    );
+    if head_rejected {
+        resp = request(client, Method::GET, url, options, &mut status).await;
+    }
+
+    match resp {
+        Ok(resp) => {
+            status.ok = resp.status().is_success();
+            status.status = Some(resp.status().as_u16());
+            status.size = content_size(&resp);
+            status.content_type = resp
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|t| t.to_str().ok())
+                .map(String::from);
+        }
+        Err(err) => status.error = Some(err),
+    }
+    status
+}
+
+impl CKAN {
+    /// Collect packages(with resources) matching the scope, following the
+    /// pagination of `package_search`.
+    pub async fn search_all(&self, scope: &Scope) -> Result<Vec<Value>, CKANError> {
+        let mut payload = match scope {
+            Scope::Query(q) => json!({ "q": q }),
+            Scope::Organization(_) => json!({ "fq": scope.filter() }),
+        };
+        payload["rows"] = json!(PAGE_SIZE);
+        payload["include_private"] = json!(true);
+
+        let mut packages = Vec::new();
+        loop {
+            payload["start"] = json!(packages.len());
+            let page: Value = self
+                .build("package_search")
+                .params(Params::Json(payload.clone()))
+                .send()
+                .await?
+                .extract()?;
+
+            let results = page["results"].as_array().cloned().unwrap_or_default();
+            let total = page["count"].as_u64().unwrap_or_default();
+            let done = results.is_empty();
+            packages.extend(results);
+            if done || packages.len() as u64 >= total {
+                return Ok(packages);
+            }
+        }
+    }
+
+    /// Store statuses in the resource field. `resource_patch` rewrites the
+    /// whole package, so resources of the same package are patched one by
+    /// one, while different packages are patched concurrently.
+    async fn write_statuses(
+        &self,
+        results: &[LinkStatus],
+        field: &str,
+        options: &LinkCheckOptions,
+    ) {
+        let mut packages: Vec<(&str, Vec<&LinkStatus>)> = Vec::new();
+        for status in results {
+            match packages.iter_mut().find(|(id, _)| *id == status.package_id) {
+                Some((_, statuses)) => statuses.push(status),
+                None => packages.push((&status.package_id, vec![status])),
+            }
+        }
+
+        let sequential = BatchOptions::default().concurrency(1);
+        let sequential = &sequential;
+        stream::iter(packages)
+            .for_each_concurrent(options.concurrency.max(1), |(_, statuses)| async move {
+                let calls = statuses.iter().map(|status| {
+                    let mut payload = json!({ "id": &status.resource_id });
+                    payload[field] = json!(json!(status).to_string());
+                    ("resource_patch", Params::Json(payload))
+                });
+                let batch = self.batch::<_, _, Value>(calls, sequential).await;
+                for (idx, err) in batch.errors() {
+                    log::error!(
+                        "Cannot store status of {}: {}",
+                        statuses[idx].resource_id,
+                        err
+                    );
+                }
+            })
+            .await;
+    }
+
+    /// Check URLs of every resource within the scope.
+    ///
+    /// When [`LinkCheckOptions::writeback`] is set, the status of every
+    /// resource is stored as JSON string in the given field via
+    /// `resource_patch`. Failed writebacks are logged and do not affect the
+    /// report.
+    pub async fn check_links(
+        &self,
+        scope: &Scope,
+        options: &LinkCheckOptions,
+    ) -> Result<LinkReport, CKANError> {
+        let client = checker(options)?;
+        let packages = self.search_all(scope).await?;
+
+        let targets: Vec<(String, String, String)> = packages
+            .iter()
+            .flat_map(|pkg| {
+                let package_id = pkg["id"].as_str().unwrap_or_default().to_string();
+                pkg["resources"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(move |res| {
+                        (
+                            package_id.clone(),
+                            res["id"].as_str().unwrap_or_default().to_string(),
+                            res["url"].as_str().unwrap_or_default().to_string(),
+                        )
+                    })
+            })
+            .collect();
+
+        let client = &client;
+        let results: Vec<LinkStatus> = stream::iter(targets)
+            .map(|(package_id, resource_id, url)| async move {
+                let mut status = check_url(client, &url, options).await;
+                status.package_id = package_id;
+                status.resource_id = resource_id;
+                status
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await;
+
+        if let Some(field) = &options.writeback {
+            self.write_statuses(&results, field, options).await;
+        }
+
+        Ok(LinkReport { results })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP server that answers according to the path and method.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = socket.read(&mut buf).await.unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    let mut parts = request.split_whitespace();
+                    let method = parts.next().unwrap_or_default();
+                    let path = parts.next().unwrap_or_default();
+
+                    let response = match (method, path) {
+                        (_, "/ok") => "HTTP/1.1 200 OK\r\nContent-Type: text/csv\r\nContent-Length: 5\r\n\r\nhello",
+                        (_, "/moved") => "HTTP/1.1 301 Moved\r\nLocation: /ok\r\nContent-Length: 0\r\n\r\n",
+                        (_, "/loop") => "HTTP/1.1 302 Found\r\nLocation: /loop\r\nContent-Length: 0\r\n\r\n",
+                        ("HEAD", "/nohead") => "HTTP/1.1 405 Not Allowed\r\nContent-Length: 0\r\n\r\n",
+                        ("GET", "/nohead") => "HTTP/1.1 206 Partial Content\r\nContent-Type: application/pdf\r\nContent-Range: bytes 0-0/12345\r\nContent-Length: 1\r\n\r\n%",
+                        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+                    };
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        let base = serve().await;
+        let options = LinkCheckOptions::default().max_redirects(3);
+        let client = checker(&options).unwrap();
+
+        let status = check_url(&client, &format!("{}/ok", base), &options).await;
+        assert!(status.ok);
+        assert_eq!(Some(200), status.status);
+        assert_eq!(Some(5), status.size);
+        assert_eq!(Some("text/csv".into()), status.content_type);
+
+        let status = check_url(&client, &format!("{}/moved", base), &options).await;
+        assert!(status.ok);
+        assert_eq!(vec![format!("{}/moved", base)], status.redirects);
+
+        let status = check_url(&client, &format!("{}/nohead", base), &options).await;
+        assert!(status.ok);
+        assert_eq!(Some("GET".into()), status.method);
+        assert_eq!(Some(12345), status.size);
+
+        let status = check_url(&client, &format!("{}/missing", base), &options).await;
+        assert!(!status.ok);
+        assert_eq!(Some(404), status.status);
+
+        let status = check_url(&client, &format!("{}/loop", base), &options).await;
+        assert!(!status.ok);
+        assert_eq!(Some("Too many redirects".into()), status.error);
+
+        let status = check_url(&client, "not a url", &options).await;
+        assert!(status.error.is_some());
+    }
+
+    /// Portal that answers `resource_patch` slowly and records the highest
+    /// number of concurrent patches of every package. Resource `a1` belongs
+    /// to the package `a`.
+    async fn portal(peaks: Arc<Mutex<HashMap<String, (usize, usize)>>>) -> CKAN {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let peaks = peaks.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = vec![0; 4096];
+                    while !request.ends_with(b"}") {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let body = &request[request.find('{').unwrap()..];
+                    let id = serde_json::from_str::<Value>(body).unwrap()["id"]
+                        .as_str()
+                        .unwrap()
+                        .to_string();
+                    let package = id[..1].to_string();
+
+                    {
+                        let mut peaks = peaks.lock().unwrap();
+                        let (current, peak) = peaks.entry(package.clone()).or_default();
+                        *current += 1;
+                        *peak = (*peak).max(*current);
+                    }
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                    peaks.lock().unwrap().get_mut(&package).unwrap().0 -= 1;
+
+                    let body = r#"{"success": true, "help": "", "result": {}}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        CKAN::from(format!("http://{}", addr))
+    }
+
+    #[tokio::test]
+    async fn test_writeback_is_sequential_within_package() {
+        let peaks = Arc::new(Mutex::new(HashMap::new()));
+        let client = portal(peaks.clone()).await;
+        let results: Vec<LinkStatus> = [("a", "a1"), ("b", "b1"), ("a", "a2"), ("a", "a3")]
+            .into_iter()
+            .map(|(package_id, resource_id)| LinkStatus {
+                package_id: package_id.into(),
+                resource_id: resource_id.into(),
+                ..Default::default()
+            })
+            .collect();
+
+        client
+            .write_statuses(&results, "link_status", &LinkCheckOptions::default())
+            .await;
+
+        let peaks = peaks.lock().unwrap();
+        assert_eq!(Some(&(0, 1)), peaks.get("a"));
+        assert_eq!(Some(&(0, 1)), peaks.get("b"));
+    }
+
+    #[test]
+    fn test_report_to_csv() {
+        let report = LinkReport {
+            results: vec![LinkStatus {
+                package_id: "pkg".into(),
+                resource_id: "res".into(),
+                url: "http://example.com/a,b".into(),
+                ok: true,
+                method: Some("HEAD".into()),
+                status: Some(200),
+                redirects: vec!["http://a".into(), "http://b".into()],
+                ..Default::default()
+            }],
+        };
+
+        assert_eq!(
+            "package_id,resource_id,url,ok,method,status,content_type,size,redirects,error\n\
+             pkg,res,\"http://example.com/a,b\",true,HEAD,200,,,http://a http://b,\n",
+            report.to_csv().unwrap()
+        );
+        assert_eq!(0, report.broken().count());
+    }
+}
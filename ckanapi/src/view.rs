@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{CKANError, Params, CKAN};
+
+/// Preview of the resource rendered by one of the view plugins.
+///
+/// Plugin-specific options(`image_url`, `page_url`, etc.) are kept inside
+/// `config` and sent to the portal as top-level fields of the view.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ResourceView {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub resource_id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub view_type: String,
+    #[serde(flatten)]
+    pub config: Map<String, Value>,
+}
+
+impl ResourceView {
+    /// Create a view definition without plugin-specific options.
+    pub fn new<R, T, V>(resource_id: R, view_type: V, title: T) -> Self
+    where
+        R: Into<String>,
+        T: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            id: None,
+            resource_id: resource_id.into(),
+            title: title.into(),
+            description: None,
+            view_type: view_type.into(),
+            config: Map::new(),
+        }
+    }
+}
+
+/// View plugins that can render the resource with the given format.
+///
+/// Format is compared case-insensitively and may be either a file extension
+/// or a MIME type. Plugins are returned in the order of preference; unknown
+/// formats have no default views.
+///
+/// # Examples
+/// ```
+/// # use ckanapi::default_view_types;
+/// assert_eq!(vec!["datatables_view"], default_view_types("CSV"));
+/// assert!(default_view_types("unknown").is_empty());
+/// ```
+pub fn default_view_types(format: &str) -> Vec<&'static str> {
+    let format = format.trim().to_lowercase();
+    let format = format
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('.');
+
+    match format {
+        "csv" | "tsv" | "xls" | "xlsx" | "vnd.ms-excel" => vec!["datatables_view"],
+        "png" | "jpg" | "jpeg" | "gif" | "bmp" | "svg" | "svg+xml" | "webp" => {
+            vec!["image_view"]
+        }
+        "txt" | "plain" | "json" | "xml" | "rdf" | "rdf+xml" | "n3" | "ttl" | "turtle" => {
+            vec!["text_view"]
+        }
+        "html" | "htm" => vec!["webpage_view"],
+        "pdf" => vec!["pdf_view"],
+        "mp4" | "webm" | "ogv" => vec!["video_view"],
+        "mp3" | "wav" | "ogg" | "mpeg" => vec!["audio_view"],
+        "geojson" | "kml" | "wms" | "wfs" | "gml" => vec!["geo_view"],
+        _ => Vec::new(),
+    }
+}
+
+fn default_view_title(view_type: &str) -> &'static str {
+    match view_type {
+        "datatables_view" => "Table",
+        "image_view" => "Image",
+        "text_view" => "Text",
+        "webpage_view" => "Website",
+        "pdf_view" => "PDF",
+        "video_view" => "Video",
+        "audio_view" => "Audio",
+        "geo_view" => "Map",
+        _ => "View",
+    }
+}
+
+impl CKAN {
+    /// List views of the resource in the order they are displayed.
+    pub async fn resource_view_list(&self, id: &str) -> Result<Vec<ResourceView>, CKANError> {
+        self.build("resource_view_list")
+            .params(Params::Json(json!({ "id": id })))
+            .send()
+            .await?
+            .extract()
+    }
+
+    pub async fn resource_view_create(
+        &self,
+        view: &ResourceView,
+    ) -> Result<ResourceView, CKANError> {
+        self.build("resource_view_create")
+            .params(Params::Json(json!(view)))
+            .send()
+            .await?
+            .extract()
+    }
+
+    /// Replace the existing view. `id` of the view must be set.
+    pub async fn resource_view_update(
+        &self,
+        view: &ResourceView,
+    ) -> Result<ResourceView, CKANError> {
+        self.build("resource_view_update")
+            .params(Params::Json(json!(view)))
+            .send()
+            .await?
+            .extract()
+    }
+
+    pub async fn resource_view_delete(&self, id: &str) -> Result<(), CKANError> {
+        self.build("resource_view_delete")
+            .params(Params::Json(json!({ "id": id })))
+            .send::<Value>()
+            .await?
+            .extract()?;
+        Ok(())
+    }
+
+    /// Change the order of resource views and return IDs of views in the new
+    /// order. Views that are not mentioned in `order` are moved to the end.
+    pub async fn resource_view_reorder<T: AsRef<str>>(
+        &self,
+        id: &str,
+        order: &[T],
+    ) -> Result<Vec<String>, CKANError> {
+        let order: Vec<&str> = order.iter().map(|id| id.as_ref()).collect();
+        let result: Value = self
+            .build("resource_view_reorder")
+            .params(Params::Json(json!({"id": id, "order": order})))
+            .send()
+            .await?
+            .extract()?;
+
+        match result["order"].as_array() {
+            Some(order) => Ok(order
+                .iter()
+                .filter_map(|id| id.as_str().map(String::from))
+                .collect()),
+            None => Err(CKANError::Complex(result)),
+        }
+    }
+
+    /// Create views for every plugin suggested by [`default_view_types`].
+    ///
+    /// Views of types that already exist for the resource are not duplicated.
+    pub async fn create_default_views(
+        &self,
+        resource_id: &str,
+        format: &str,
+    ) -> Result<Vec<ResourceView>, CKANError> {
+        let existing = self.resource_view_list(resource_id).await?;
+        let mut created = Vec::new();
+
+        for view_type in default_view_types(format) {
+            if existing.iter().any(|v| v.view_type == view_type) {
+                continue;
+            }
+            let view = ResourceView::new(resource_id, view_type, default_view_title(view_type));
+            created.push(self.resource_view_create(&view).await?);
+        }
+
+        Ok(created)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_view_types() {
+        assert_eq!(vec!["datatables_view"], default_view_types("csv"));
+        assert_eq!(vec!["image_view"], default_view_types(" PNG "));
+        assert_eq!(vec!["image_view"], default_view_types("image/jpeg"));
+        assert_eq!(vec!["pdf_view"], default_view_types(".pdf"));
+        assert_eq!(vec!["geo_view"], default_view_types("GeoJSON"));
+        assert!(default_view_types("").is_empty());
+        assert!(default_view_types("zip").is_empty());
+    }
+
+    #[test]
+    fn test_view_config_is_flattened() {
+        let mut view = ResourceView::new("res", "image_view", "Image");
+        view.config
+            .insert("image_url".into(), json!("http://example.com/a.png"));
+
+        let data = json!(view);
+        assert_eq!(
+            json!({
+                "resource_id": "res",
+                "title": "Image",
+                "view_type": "image_view",
+                "image_url": "http://example.com/a.png"
+            }),
+            data
+        );
+
+        let parsed: ResourceView = serde_json::from_value(json!({
+            "id": "view",
+            "resource_id": "res",
+            "package_id": "pkg",
+            "title": "Image",
+            "description": "",
+            "view_type": "image_view",
+            "image_url": "http://example.com/a.png"
+        }))
+        .unwrap();
+        assert_eq!(Some("view".into()), parsed.id);
+        assert_eq!(json!("pkg"), parsed.config["package_id"]);
+    }
+}
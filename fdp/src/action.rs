@@ -1,26 +1,30 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{Read, Seek};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use crate::read_source_path;
+use crate::checksum::Checksum;
+use crate::chunk;
+use crate::profile::{Action, Profile};
 pub use crate::types::{
     AvailableProjects, Metadata, MetadataContent, ProgressedUpload, Project, RegisteredUpload,
-    User, ValidationResult,
+    Resource, User, ValidationResult,
 };
-use ckanapi::{Params, CKAN};
+use crate::{read_source_path, FdpError};
+use ckanapi::{Params, RequestBuilder, CKAN};
 use serde_json::{json, Value};
 
-const CHUNK_SIZE: usize = 5 * 1024 * 1024;
-
 #[async_trait]
-pub trait FdpClient {
+pub trait FdpClient: Send + Sync {
     async fn submission_finalize(&self) -> crate::Result<()>;
     async fn user_info(&self) -> crate::Result<User>;
-    async fn available_projects(&self, name: &str) -> Vec<Project>;
+    async fn available_projects(&self, name: &str) -> crate::Result<Vec<Project>>;
     async fn project_set(&self, id: Option<&str>) -> crate::Result<Value>;
 
-    async fn show_submission(&self) -> Option<Vec<Value>>;
+    async fn show_submission(&self) -> crate::Result<Vec<Value>>;
 
     async fn validate_dataset(&self, path: &OsStr, name: &str) -> crate::Result<ValidationResult>;
     async fn validate_resource(
@@ -30,58 +34,142 @@ pub trait FdpClient {
         name: &str,
     ) -> crate::Result<ValidationResult>;
 
-    async fn show_upload(&self, dataset: &str, name: &str) -> Option<ProgressedUpload>;
+    async fn show_upload(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload>;
+
+    /// Start the upload split into parts of `chunk_size` bytes. Portal may
+    /// reply with the different size, which must be used instead.
     async fn register_upload(
         &self,
         path: &str,
         dataset: &str,
         name: &str,
-    ) -> Option<RegisteredUpload>;
+        chunk_size: u64,
+    ) -> crate::Result<RegisteredUpload>;
+
+    /// Send a single part of the file without finalizing the upload.
+    async fn upload_part(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+        part: u64,
+        chunk_size: u64,
+    ) -> crate::Result<ProgressedUpload>;
+
+    /// Finalize the upload after all its parts are sent.
+    async fn complete_upload(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ProgressedUpload>;
+
+    /// Send a part of the file and complete the upload if it was the last
+    /// one.
     async fn progress_upload(
         &self,
         path: &str,
         dataset: &str,
         name: &str,
         part: u64,
-    ) -> Option<ProgressedUpload>;
+        chunk_size: u64,
+    ) -> crate::Result<ProgressedUpload> {
+        let flake = self
+            .upload_part(path, dataset, name, part, chunk_size)
+            .await?;
+
+        if flake.data.bytes_uploaded == flake.data.size {
+            self.complete_upload(path, dataset, name).await
+        } else {
+            Ok(flake)
+        }
+    }
+}
+
+/// Client of the portal extension, which names actions according to the
+/// [`Profile`].
+pub struct ExtensionClient {
+    ckan: CKAN,
+    profile: Arc<Profile>,
+    /// Files of the resources being uploaded, so that the source is read once
+    /// per upload rather than once per part.
+    files: Mutex<HashMap<(String, String, String), PathBuf>>,
+}
+
+impl ExtensionClient {
+    pub fn new(ckan: CKAN, profile: Arc<Profile>) -> Self {
+        Self {
+            ckan,
+            profile,
+            files: Default::default(),
+        }
+    }
+
+    fn build(&self, action: Action) -> RequestBuilder {
+        self.ckan.build(self.profile.action(action))
+    }
+
+    fn json(&self, payload: Value) -> Params {
+        Params::Json(self.profile.payload(payload))
+    }
+
+    fn field(&self, name: &str) -> String {
+        self.profile.field(name).to_string()
+    }
+
+    fn resource_file(&self, path: &str, dataset: &str, name: &str) -> crate::Result<PathBuf> {
+        let key = (path.to_string(), dataset.to_string(), name.to_string());
+        if let Some(file) = self.files.lock().unwrap().get(&key) {
+            return Ok(file.clone());
+        }
+
+        let res = find_resource(path, dataset, name)?;
+        let file = res.path.join(&res.name);
+        self.files.lock().unwrap().insert(key, file.clone());
+        Ok(file)
+    }
+}
+
+impl From<CKAN> for ExtensionClient {
+    fn from(ckan: CKAN) -> Self {
+        Self::new(ckan, Default::default())
+    }
 }
 
 #[async_trait]
-impl FdpClient for CKAN {
+impl FdpClient for ExtensionClient {
     async fn submission_finalize(&self) -> crate::Result<()> {
-        let _resp = self
-            .build("nswflood_submission_finalize")
+        let resp = self
+            .build(Action::SubmissionFinalize)
             .send::<Value>()
             .await?;
+        resp.extract()?;
         Ok(())
     }
     async fn user_info(&self) -> crate::Result<User> {
-        let resp = self.build("nswflood_me").send::<User>().await?;
+        let resp = self.build(Action::Me).send::<User>().await?;
 
         Ok(resp.extract()?)
     }
 
-    async fn available_projects(&self, name: &str) -> Vec<Project> {
-        let payload = Params::Json(json!({"name": name, "rows": 10, "fl": "id,name,title"}));
+    async fn available_projects(&self, name: &str) -> crate::Result<Vec<Project>> {
+        let payload = self.json(json!({"name": name, "rows": 10, "fl": "id,name,title"}));
 
-        match self
-            .build("nswflood_available_project_list")
+        let projects: AvailableProjects = self
+            .build(Action::AvailableProjectList)
             .params(payload)
             .send()
-            .await
-            .map_or(None, |r| r.extract().ok())
-        {
-            Some::<AvailableProjects>(projects) => projects.results,
+            .await?
+            .extract()?;
 
-            None => Vec::new(),
-        }
+        Ok(projects.results)
     }
 
     async fn project_set(&self, id: Option<&str>) -> crate::Result<Value> {
-        let payload = Params::Json(json!({ "id": id }));
+        let payload = self.json(json!({ "id": id }));
 
         let resp = self
-            .build("nswflood_submission_project_set")
+            .build(Action::SubmissionProjectSet)
             .params(payload)
             .send()
             .await?;
@@ -89,26 +177,26 @@ impl FdpClient for CKAN {
         Ok(resp.extract()?)
     }
 
-    async fn show_submission(&self) -> Option<Vec<Value>> {
-        self.build("nswflood_submission_details")
+    async fn show_submission(&self) -> crate::Result<Vec<Value>> {
+        Ok(self
+            .build(Action::SubmissionDetails)
             .params(Params::Empty)
             .send()
-            .await
-            .ok()?
-            .extract()
-            .ok()
+            .await?
+            .extract()?)
     }
 
-    async fn show_upload(&self, dataset: &str, name: &str) -> Option<ProgressedUpload> {
-        let payload = Params::Json(json!({"name": name, "dataset": dataset}));
+    async fn show_upload(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        let payload = self.json(json!({"name": name, "dataset": dataset}));
 
-        self.build("nswflood_upload_show")
+        let upload: Option<ProgressedUpload> = self
+            .build(Action::UploadShow)
             .params(payload)
             .send()
-            .await
-            .ok()?
-            .extract()
-            .ok()?
+            .await?
+            .extract()?;
+
+        upload.ok_or_else(|| FdpError::NotFound("Upload has not started yet".into()))
     }
 
     async fn validate_dataset(&self, path: &OsStr, name: &str) -> crate::Result<ValidationResult> {
@@ -120,17 +208,19 @@ impl FdpClient for CKAN {
             Metadata::Object(ref v) => v.clone(),
         };
 
-        let dataset = source.get_dataset(name).ok_or("Dataset not found")?;
+        let dataset = source
+            .get_dataset(name)
+            .ok_or_else(|| FdpError::NotFound("Dataset not found".into()))?;
 
         match &dataset.metadata {
-            Metadata::Empty => Err("Dataset has no metadata".into()),
+            Metadata::Empty => Err(FdpError::Plain("Dataset has no metadata".into())),
 
             Metadata::Object(metadata) => {
                 let req = self
 
-                    .build("nswflood_submission_validate_dataset")
+                    .build(Action::SubmissionValidateDataset)
 
-                    .params(Params::Json(
+                    .params(self.json(
 
                         json!({"data": metadata.clone(), "name": &dataset.name, "root": root_metadata}),
 
@@ -152,19 +242,23 @@ impl FdpClient for CKAN {
     ) -> crate::Result<ValidationResult> {
         let source = read_source_path(path)?;
 
-        let dataset = source.get_dataset(dataset).ok_or("Dataset not found")?;
+        let dataset = source
+            .get_dataset(dataset)
+            .ok_or_else(|| FdpError::NotFound("Dataset not found".into()))?;
 
-        let res = dataset.get_resoure(name).ok_or("Resource not found")?;
+        let res = dataset
+            .get_resoure(name)
+            .ok_or_else(|| FdpError::NotFound("Resource not found".into()))?;
 
-        match &res.metadata {
-            Metadata::Empty => Err("Resource has no metadata".into()),
+        match &res.metadata_with_format() {
+            Metadata::Empty => Err(FdpError::Plain("Resource has no metadata".into())),
 
             Metadata::Object(metadata) => {
                 let req = self
 
-                    .build("nswflood_submission_validate_resource")
+                    .build(Action::SubmissionValidateResource)
 
-                    .params(Params::Json(
+                    .params(self.json(
 
                         json!({"data": metadata.clone(), "dataset": &dataset.name, "name": &res.name, "size": res.size}),
 
@@ -177,114 +271,146 @@ impl FdpClient for CKAN {
 
     async fn register_upload(
         &self,
-
         path: &str,
-
         dataset: &str,
-
         name: &str,
-    ) -> Option<RegisteredUpload> {
-        let source = read_source_path(&path).ok()?;
+        chunk_size: u64,
+    ) -> crate::Result<RegisteredUpload> {
+        let res = find_resource(path, dataset, name)?;
 
-        let res = source.get_dataset(dataset)?.get_resoure(name)?;
+        let payload = self.json(
+            json!({"name": name, "dataset": dataset, "size": res.size(), "chunk_size": chunk_size}),
+        );
 
-        let payload = Params::Json(json!({"name": name, "dataset": dataset, "size": res.size()}));
-
-        self.build("nswflood_upload_register")
+        Ok(self
+            .build(Action::UploadRegister)
             .params(payload)
             .send()
-            .await
-            .ok()?
-            .extract()
-            .ok()
+            .await?
+            .extract()?)
     }
 
-    async fn progress_upload(
+    async fn upload_part(
         &self,
-
         path: &str,
-
         dataset: &str,
-
         name: &str,
-
         part: u64,
-    ) -> Option<ProgressedUpload> {
-        let source = read_source_path(&path).ok()?;
-
-        let res = source.get_dataset(dataset)?.get_resoure(name)?;
-
-        let mut path = res.path.clone();
+        chunk_size: u64,
+    ) -> crate::Result<ProgressedUpload> {
+        let filepath = self.resource_file(path, dataset, name)?;
+        let md5 = self.profile.md5;
 
-        path.push(&res.name);
+        let (buf, checksum) = tokio::task::spawn_blocking(move || {
+            let mut file = File::open(filepath)?;
 
-        let mut file = File::open(path).ok()?;
+            let offset = chunk::offset(part, chunk_size);
 
-        let offset = (part - 1) * CHUNK_SIZE as u64;
+            file.seek(std::io::SeekFrom::Start(offset))?;
 
-        file.seek(std::io::SeekFrom::Start(offset)).ok()?;
+            let mut buf = vec![];
 
-        let mut buf = vec![];
+            let reader = std::io::BufReader::new(file);
 
-        let reader = std::io::BufReader::new(file);
+            reader.take(chunk_size).read_to_end(&mut buf)?;
 
-        reader
-            .take(CHUNK_SIZE as u64)
-            .read_to_end(&mut buf)
-            .expect("Cannot read a chunk from file");
+            let checksum = Checksum::of(&buf, md5);
+            Ok::<_, FdpError>((buf, checksum))
+        })
+        .await
+        .map_err(|err| FdpError::Plain(err.to_string()))??;
 
         let size = buf.len();
 
         let mut payload = Params::multipart();
 
         payload
-            .add_field("dataset".to_string(), dataset.to_string())
-            .add_field("name".to_string(), name.to_string())
-            .add_field("part_number".to_string(), part.to_string())
-            .add_field("size".to_string(), size.to_string())
-            .add_blob("content".to_string(), buf);
-
-        match self
-            .build("nswflood_upload_progress")
+            .add_field(self.field("dataset"), dataset.to_string())
+            .add_field(self.field("name"), name.to_string())
+            .add_field(self.field("part_number"), part.to_string())
+            .add_field(self.field("size"), size.to_string())
+            .add_field(self.field("sha256"), checksum.sha256.clone());
+        if let Some(md5) = &checksum.md5 {
+            payload.add_field(self.field("md5"), md5.clone());
+        }
+        payload.add_blob(self.field("content"), buf);
+
+        let flake: ProgressedUpload = self
+            .build(Action::UploadProgress)
             .params(payload)
             .send()
+            .await?
+            .extract()?;
+
+        checksum.verify(&flake.data)?;
+        Ok(flake)
+    }
+
+    async fn complete_upload(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ProgressedUpload> {
+        let filepath = self.resource_file(path, dataset, name)?;
+        self.files.lock().unwrap().remove(&(
+            path.to_string(),
+            dataset.to_string(),
+            name.to_string(),
+        ));
+
+        let md5 = self.profile.md5;
+        let checksum = tokio::task::spawn_blocking(move || Checksum::of_file(filepath, md5))
             .await
-            .ok()?
-            .extract()
-            .ok()
-        {
-            None => None,
-
-            Some::<ProgressedUpload>(flake) => {
-                if flake.data.bytes_uploaded == res.size() {
-                    self.build("nswflood_upload_complete")
-                        .params(Params::Json(json!({"dataset": dataset, "name": name})))
-                        .send()
-                        .await
-                        .ok()?
-                        .extract()
-                        .ok()
-                } else {
-                    Some(flake)
-                }
-            }
+            .map_err(|err| FdpError::Plain(err.to_string()))??;
+
+        let mut payload = json!({
+            "dataset": dataset,
+            "name": name,
+            "sha256": &checksum.sha256,
+        });
+        if let Some(md5) = &checksum.md5 {
+            payload["md5"] = md5.as_str().into();
         }
+        let upload: ProgressedUpload = self
+            .build(Action::UploadComplete)
+            .params(self.json(payload))
+            .send()
+            .await?
+            .extract()?;
+
+        // sending the upload completion again won't fix parts that are
+        // already stored by the portal
+        checksum.verify(&upload.data).map_err(|err| match err {
+            FdpError::ChecksumMismatch(msg) => FdpError::CorruptedUpload(msg),
+            err => err,
+        })?;
+        Ok(upload)
     }
 }
 
-#[cfg(test)]
+pub(crate) fn find_resource(path: &str, dataset: &str, name: &str) -> crate::Result<Resource> {
+    let source = read_source_path(path)?;
+    source
+        .get_dataset(dataset)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", dataset)))?
+        .get_resoure(name)
+        .cloned()
+        .ok_or_else(|| FdpError::NotFound(format!("Resource {} does not exist", name)))
+}
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
-    fn ckan() -> CKAN {
+    fn ckan() -> ExtensionClient {
         let mut ckan = CKAN::from("http://localhost:5000");
 
         if let Some(token) = std::env::var_os("CKAN_TOKEN") {
             ckan.login(token.into_string().unwrap());
         }
 
-        ckan
+        ckan.into()
     }
 
     #[tokio::test]
@@ -0,0 +1,126 @@
+//! Integrity data sent together with the uploaded content.
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::types::UploadData;
+use crate::FdpError;
+
+/// Hex-encoded digests of the part or of the whole file. MD5 is only
+/// computed when it's requested.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Checksum {
+    pub sha256: String,
+    pub md5: Option<String>,
+}
+
+impl Checksum {
+    pub fn of(data: &[u8], md5: bool) -> Self {
+        Self {
+            sha256: format!("{:x}", Sha256::digest(data)),
+            md5: md5.then(|| format!("{:x}", Md5::digest(data))),
+        }
+    }
+
+    /// Digests of the file, computed without reading it into memory.
+    pub fn of_file<P: AsRef<Path>>(path: P, md5: bool) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut sha256 = Sha256::new();
+        let mut md5 = md5.then(Md5::new);
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            sha256.update(&buf[..n]);
+            if let Some(md5) = &mut md5 {
+                md5.update(&buf[..n]);
+            }
+        }
+        Ok(Self {
+            sha256: format!("{:x}", sha256.finalize()),
+            md5: md5.map(|md5| format!("{:x}", md5.finalize())),
+        })
+    }
+
+    /// Compare with the digests reported by the portal. Digests that are not
+    /// reported or not computed are not checked.
+    pub fn verify(&self, data: &UploadData) -> crate::Result<()> {
+        let pairs = [
+            ("SHA-256", Some(&self.sha256), &data.sha256),
+            ("MD5", self.md5.as_ref(), &data.md5),
+        ];
+        for (algorithm, local, remote) in pairs {
+            match (local, remote) {
+                (Some(local), Some(remote)) if !remote.eq_ignore_ascii_case(local) => {
+                    return Err(FdpError::ChecksumMismatch(format!(
+                        "{} of {}/{} is {}, portal received {}",
+                        algorithm, data.dataset, data.name, local, remote
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(sha256: Option<&str>, md5: Option<&str>) -> UploadData {
+        UploadData {
+            key: "key".into(),
+            size: 5,
+            dataset: "dataset".into(),
+            name: "file".into(),
+            completed: false,
+            bytes_uploaded: 5,
+            chunk_size: None,
+            sha256: sha256.map(Into::into),
+            md5: md5.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let checksum = Checksum::of(b"hello", true);
+        assert_eq!(
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            checksum.sha256
+        );
+        assert_eq!(
+            Some("5d41402abc4b2a76b9719d911017c592"),
+            checksum.md5.as_deref()
+        );
+        assert_eq!(None, Checksum::of(b"hello", false).md5);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, "hello").unwrap();
+        assert_eq!(checksum, Checksum::of_file(&path, true).unwrap());
+        assert_eq!(None, Checksum::of_file(&path, false).unwrap().md5);
+    }
+
+    #[test]
+    fn test_verify() {
+        let checksum = Checksum::of(b"hello", true);
+        assert!(checksum.verify(&data(None, None)).is_ok());
+        assert!(checksum
+            .verify(&data(Some(&checksum.sha256.to_uppercase()), None))
+            .is_ok());
+        assert!(matches!(
+            checksum.verify(&data(None, Some("0000"))),
+            Err(FdpError::ChecksumMismatch(_))
+        ));
+        assert!(Checksum::of(b"hello", false)
+            .verify(&data(None, Some("0000")))
+            .is_ok());
+    }
+}
@@ -0,0 +1,122 @@
+//! Size of the parts the uploaded file is split into.
+//!
+//! The size is chosen when the upload is registered and stays the same until
+//! the upload is completed, because offsets of the parts are computed from
+//! it. [`ChunkSizer`] measures the throughput of sent parts and suggests the
+//! size for the uploads registered afterwards.
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const MIB: u64 = 1024 * 1024;
+
+pub const DEFAULT_CHUNK_SIZE: u64 = 5 * MIB;
+pub const MIN_CHUNK_SIZE: u64 = MIB;
+pub const MAX_CHUNK_SIZE: u64 = 64 * MIB;
+
+/// Byte offset of the 1-based part.
+pub fn offset(part: u64, chunk_size: u64) -> u64 {
+    part.saturating_sub(1) * chunk_size
+}
+
+/// Size of the 1-based part of the file.
+pub fn part_size(part: u64, chunk_size: u64, size: u64) -> u64 {
+    chunk_size.min(size.saturating_sub(offset(part, chunk_size)))
+}
+
+/// Chunk size reported by the portal, or `fallback` if the portal doesn't
+/// know it. Zero is never accepted, so the file can always be split.
+pub fn negotiated(reported: Option<u64>, fallback: u64) -> u64 {
+    reported.filter(|size| *size > 0).unwrap_or(fallback).max(1)
+}
+
+/// Number of parts of the file. Empty file still has a single part.
+pub fn parts(chunk_size: u64, size: u64) -> u64 {
+    size.div_ceil(chunk_size).max(1)
+}
+
+/// Suggests chunk size, so that a single part takes about `target` to send.
+/// Clones share measurements.
+#[derive(Debug, Clone)]
+pub struct ChunkSizer {
+    target: Duration,
+    /// Moving average of the throughput, bytes per second.
+    throughput: Arc<Mutex<Option<f64>>>,
+}
+
+impl Default for ChunkSizer {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10))
+    }
+}
+
+impl ChunkSizer {
+    pub fn new(target: Duration) -> Self {
+        Self {
+            target,
+            throughput: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Register the part of `bytes` that was sent in `elapsed`.
+    pub fn record(&self, bytes: u64, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if bytes == 0 || seconds <= 0.0 {
+            return;
+        }
+        let sample = bytes as f64 / seconds;
+        let mut throughput = self.throughput.lock().unwrap();
+        *throughput = Some(match *throughput {
+            Some(average) => average * 0.7 + sample * 0.3,
+            None => sample,
+        });
+    }
+
+    /// Chunk size for the next upload, or `fallback` until anything is
+    /// measured. Result is rounded to MiB.
+    pub fn suggest(&self, fallback: u64) -> u64 {
+        match *self.throughput.lock().unwrap() {
+            Some(rate) => {
+                let size = (rate * self.target.as_secs_f64()) as u64;
+                (size / MIB * MIB).clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
+            }
+            None => fallback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_arithmetic() {
+        assert_eq!(0, offset(1, 10));
+        assert_eq!(20, offset(3, 10));
+        assert_eq!(10, part_size(1, 10, 25));
+        assert_eq!(5, part_size(3, 10, 25));
+        assert_eq!(0, part_size(4, 10, 25));
+        assert_eq!(3, parts(10, 25));
+        assert_eq!(1, parts(10, 0));
+    }
+
+    #[test]
+    fn test_sizer() {
+        let sizer = ChunkSizer::new(Duration::from_secs(1));
+        assert_eq!(DEFAULT_CHUNK_SIZE, sizer.suggest(DEFAULT_CHUNK_SIZE));
+
+        sizer.record(8 * MIB, Duration::from_secs(1));
+        assert_eq!(8 * MIB, sizer.suggest(DEFAULT_CHUNK_SIZE));
+
+        sizer.clone().record(MIB, Duration::from_secs(10));
+        assert!(sizer.suggest(DEFAULT_CHUNK_SIZE) < 8 * MIB);
+
+        sizer.record(MIB, Duration::from_secs(1000));
+        sizer.record(MIB, Duration::from_secs(1000));
+        sizer.record(MIB, Duration::from_secs(1000));
+        assert_eq!(MIN_CHUNK_SIZE, sizer.suggest(DEFAULT_CHUNK_SIZE));
+
+        let fast = ChunkSizer::new(Duration::from_secs(10));
+        fast.record(100 * MIB, Duration::from_secs(1));
+        assert_eq!(MAX_CHUNK_SIZE, fast.suggest(DEFAULT_CHUNK_SIZE));
+    }
+}
@@ -0,0 +1,244 @@
+//! On-disk record of the uploads started from the source directory.
+//!
+//! The journal lives inside the source as `.fdp-journal.json` and keeps, for
+//! every registered upload, the state of the file at the moment of
+//! registration and the parts that were already sent. It allows continuing
+//! unfinished uploads after the application is restarted and detects files
+//! that were modified in between.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::FdpError;
+
+pub const JOURNAL_FILENAME: &str = ".fdp-journal.json";
+
+/// Serializes modifications of journals made from different handles.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Size and modification time(milliseconds since epoch) of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FileState {
+    pub size: u64,
+    pub modified: u64,
+}
+
+impl FileState {
+    pub fn of<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Ok(Self {
+            size: meta.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct JournalEntry {
+    pub dataset: String,
+    pub name: String,
+    pub upload_id: String,
+    pub file: FileState,
+    pub chunk_size: u64,
+    #[serde(default)]
+    pub parts: BTreeSet<u64>,
+    #[serde(default)]
+    pub completed: bool,
+}
+
+impl JournalEntry {
+    pub fn new(
+        dataset: &str,
+        name: &str,
+        upload_id: &str,
+        file: FileState,
+        chunk_size: u64,
+    ) -> Self {
+        Self {
+            dataset: dataset.to_string(),
+            name: name.to_string(),
+            upload_id: upload_id.to_string(),
+            file,
+            chunk_size: chunk_size.max(1),
+            parts: BTreeSet::new(),
+            completed: false,
+        }
+    }
+
+    /// Fail if the file differs from the one that was registered.
+    pub fn verify(&self, file: &FileState) -> crate::Result<()> {
+        if self.file == *file {
+            Ok(())
+        } else {
+            Err(FdpError::FileChanged(format!(
+                "{}/{} was modified after the upload had started",
+                self.dataset, self.name
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Content {
+    uploads: BTreeMap<String, JournalEntry>,
+}
+
+/// Handle of the journal that belongs to the source at `path`.
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    pub fn new<P: AsRef<Path>>(source: P) -> Self {
+        Self {
+            path: source.as_ref().join(JOURNAL_FILENAME),
+        }
+    }
+
+    fn key(dataset: &str, name: &str) -> String {
+        format!("{}/{}", dataset, name)
+    }
+
+    fn read(&self) -> crate::Result<Content> {
+        match fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|err| FdpError::Io(format!("Corrupted upload journal: {}", err))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Content::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write(&self, content: &Content) -> crate::Result<()> {
+        let data = serde_json::to_vec_pretty(content)
+            .map_err(|err| FdpError::Io(format!("Cannot serialize upload journal: {}", err)))?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn modify<T>(&self, f: impl FnOnce(&mut Content) -> T) -> crate::Result<T> {
+        let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let mut content = self.read()?;
+        let result = f(&mut content);
+        self.write(&content)?;
+        Ok(result)
+    }
+
+    pub fn entries(&self) -> crate::Result<Vec<JournalEntry>> {
+        let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        Ok(self.read()?.uploads.into_values().collect())
+    }
+
+    /// Uploads that were registered but not completed.
+    pub fn unfinished(&self) -> crate::Result<Vec<JournalEntry>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|e| !e.completed)
+            .collect())
+    }
+
+    pub fn get(&self, dataset: &str, name: &str) -> crate::Result<Option<JournalEntry>> {
+        let _guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        Ok(self.read()?.uploads.remove(&Self::key(dataset, name)))
+    }
+
+    /// Add the entry, replacing the previous upload of the same resource.
+    pub fn insert(&self, entry: JournalEntry) -> crate::Result<()> {
+        self.modify(|c| {
+            c.uploads
+                .insert(Self::key(&entry.dataset, &entry.name), entry);
+        })
+    }
+
+    pub fn record_part(&self, dataset: &str, name: &str, part: u64) -> crate::Result<()> {
+        self.modify(|c| {
+            if let Some(entry) = c.uploads.get_mut(&Self::key(dataset, name)) {
+                entry.parts.insert(part);
+            }
+        })
+    }
+
+    pub fn record_completed(&self, dataset: &str, name: &str) -> crate::Result<()> {
+        self.modify(|c| {
+            if let Some(entry) = c.uploads.get_mut(&Self::key(dataset, name)) {
+                entry.completed = true;
+            }
+        })
+    }
+
+    pub fn remove(&self, dataset: &str, name: &str) -> crate::Result<()> {
+        self.modify(|c| {
+            c.uploads.remove(&Self::key(dataset, name));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: FileState = FileState {
+        size: 10,
+        modified: 1000,
+    };
+
+    #[test]
+    fn test_journal_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::new(dir.path());
+        assert!(journal.entries().unwrap().is_empty());
+
+        journal
+            .insert(JournalEntry::new("dataset", "a.csv", "1", FILE, 5))
+            .unwrap();
+        journal
+            .insert(JournalEntry::new("dataset", "b.csv", "2", FILE, 5))
+            .unwrap();
+        journal.record_part("dataset", "a.csv", 1).unwrap();
+        journal.record_part("dataset", "a.csv", 2).unwrap();
+        journal.record_completed("dataset", "b.csv").unwrap();
+
+        let reopened = Journal::new(dir.path());
+        let entry = reopened.get("dataset", "a.csv").unwrap().unwrap();
+        assert_eq!(vec![1, 2], entry.parts.into_iter().collect::<Vec<_>>());
+        let unfinished = reopened.unfinished().unwrap();
+        assert_eq!(1, unfinished.len());
+        assert_eq!("a.csv", unfinished[0].name);
+
+        reopened.remove("dataset", "a.csv").unwrap();
+        assert!(journal.get("dataset", "a.csv").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_verify_file_state() {
+        let entry = JournalEntry::new("dataset", "a.csv", "1", FILE, 5);
+        assert!(entry.verify(&FILE).is_ok());
+        assert!(matches!(
+            entry.verify(&FileState { size: 11, ..FILE }),
+            Err(FdpError::FileChanged(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "hello").unwrap();
+        let state = FileState::of(&path).unwrap();
+        assert_eq!(5, state.size);
+        assert!(state.modified > 0);
+        assert!(FileState::of(dir.path().join("missing")).is_err());
+    }
+}
@@ -1,76 +1,754 @@
 pub mod action;
+pub mod checksum;
+pub mod chunk;
+pub mod journal;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod profile;
+pub mod scheduler;
+pub mod schema;
+#[cfg(any(test, feature = "test-server"))]
+pub mod server;
 pub mod state;
+pub mod stock;
 pub mod types;
+pub mod upload;
+pub mod validation;
+
+#[cfg(test)]
+mod testing;
 
 use std::ffi::OsStr;
+use std::path::Path;
 
 use ckanapi::CKANError;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use types::sheet::{self, Sheet};
+use types::{sidecar, xlsx};
 pub type Result<T> = core::result::Result<T, FdpError>;
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(untagged)]
+/// Error reported by the portal or by the local filesystem.
+///
+/// It is sent to the frontend as `{"type": "<variant>", "message": ...}`, so
+/// the application can react to the specific kind of failure. `message` of
+/// the `validation` error is an object with the list of errors for every
+/// field.
+#[derive(Debug, Error, Deserialize, Serialize)]
+#[serde(tag = "type", content = "message", rename_all = "snake_case")]
 pub enum FdpError {
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Invalid data: {0}")]
+    Validation(Value),
+
+    #[error("Filesystem error: {0}")]
+    Io(String),
+
+    #[error("Cannot parse metadata: {0}")]
+    Csv(String),
+
+    #[error("Not authorized: {0}")]
     Auth(String),
-    Request(String),
+
+    #[error("Portal is not available: {0}")]
+    Transport(String),
+
+    #[error("File changed: {0}")]
+    FileChanged(String),
+
+    #[error("Checksum mismatch: {0}")]
+    ChecksumMismatch(String),
+
+    /// Whole file received by the portal differs from the local one.
+    #[error("Upload is corrupted: {0}")]
+    CorruptedUpload(String),
+
+    #[error("Upload cancelled")]
+    Cancelled,
+
+    #[error("{0}")]
     Plain(String),
 }
 
+impl FdpError {
+    /// Whether the same request may succeed if repeated later. Corrupted
+    /// parts are sent again.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Transport(_) | Self::Plain(_) | Self::ChecksumMismatch(_)
+        )
+    }
+}
+
 impl From<&str> for FdpError {
     fn from(source: &str) -> Self {
         Self::Plain(source.into())
     }
 }
+
 impl From<CKANError> for FdpError {
     fn from(source: CKANError) -> Self {
         match source {
             CKANError::Authorization(msg) => Self::Auth(msg),
-            CKANError::Request(msg) => Self::Request(msg),
+            CKANError::Request(msg) => Self::Transport(msg),
+            CKANError::NotFound(msg) => Self::NotFound(msg),
+            CKANError::Validation(errors) => Self::Validation(errors),
+            CKANError::Complex(error) => Self::Plain(format!("Unexpected error: {}", error)),
+            CKANError::Mismatch { message, .. } => {
+                Self::Plain(format!("Unexpected response: {}", message))
+            }
             _ => Self::Plain(format!("Unexpected error: {:?}", source)),
         }
     }
 }
 
+impl From<std::io::Error> for FdpError {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io(source.to_string())
+    }
+}
+
+impl From<csv::Error> for FdpError {
+    fn from(source: csv::Error) -> Self {
+        Self::Csv(source.to_string())
+    }
+}
+
+/// Text of the metadata cell.
+fn cell_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Update the master column of metadata.csv. Other columns and rows are
+/// preserved. New file is created from the template.
 pub fn save_root_metadata<T: AsRef<OsStr>>(path: T, metadata: Value) -> Result<()> {
     let mut source = read_source_path(&path)?;
-    source.metadata = types::Metadata::Object(metadata);
-    if let Err(err) = source
-        .metadata
-        .write(&source.metadata_path(), types::dataset_comments())
-    {
-        log::error!("{}", err);
-        Err("Cannot save the metadata".into())
+    let metadata_path = source.metadata_path();
+    if !metadata_path.is_file() {
+        source.metadata = types::Metadata::Object(metadata);
+        source.metadata.write(&metadata_path)?;
+        return Ok(());
+    }
+
+    let mut sheet = Sheet::read(&metadata_path)?;
+    let comments = types::dataset_comments();
+    if let Value::Object(fields) = metadata {
+        for (field, value) in &fields {
+            sheet.set_dataset_value(sheet::MASTER_COLUMN, field, cell_value(value));
+            if let Some(comment) = comments.get(field) {
+                sheet.describe(field, &comment.join("\n"));
+            }
+        }
+    }
+    sheet.write(&metadata_path)?;
+    Ok(())
+}
+
+/// Sheet of the source, or the empty one with headers if the source has no
+/// metadata.csv yet.
+fn read_sheet(source: &types::Source) -> Result<Sheet> {
+    let path = source.metadata_path();
+    if path.is_file() {
+        Ok(Sheet::read(path)?)
     } else {
-        Ok(())
+        Ok(Sheet::with_header())
     }
 }
 
+/// Update the column of the dataset in metadata.csv, adding the column if
+/// it's missing.
+pub fn save_dataset_metadata<T: AsRef<OsStr>>(path: T, name: &str, metadata: Value) -> Result<()> {
+    let source = read_source_path(&path)?;
+    let sidecar = source
+        .get_dataset(name)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", name)))?
+        .sidecar_path();
+    if sidecar.is_file() {
+        return update_sidecar(&sidecar, metadata, Item::Dataset);
+    }
+
+    let mut sheet = read_sheet(&source)?;
+    let column = sheet.column_or_insert(name);
+    if let Value::Object(fields) = metadata {
+        for (field, value) in &fields {
+            sheet.set_dataset_value(column, field, cell_value(value));
+        }
+    }
+    sheet.write(source.metadata_path())?;
+    Ok(())
+}
+
+/// Update the group of the resource in metadata.csv, adding the column of
+/// the dataset and the group if they are missing.
+pub fn save_resource_metadata<T: AsRef<OsStr>>(
+    path: T,
+    dataset: &str,
+    name: &str,
+    metadata: Value,
+) -> Result<()> {
+    let source = read_source_path(&path)?;
+    let sidecar = source
+        .get_dataset(dataset)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", dataset)))?
+        .get_resoure(name)
+        .ok_or_else(|| FdpError::NotFound(format!("Resource {} does not exist", name)))?
+        .sidecar_path();
+    if sidecar.is_file() {
+        return update_sidecar(&sidecar, metadata, Item::Resource);
+    }
+
+    let mut sheet = read_sheet(&source)?;
+    let column = sheet.column_or_insert(dataset);
+    sheet.group_or_insert(column, name);
+    if let Value::Object(fields) = metadata {
+        for (field, value) in &fields {
+            sheet.set_resource_value(column, name, field, cell_value(value));
+        }
+    }
+    sheet.write(source.metadata_path())?;
+    Ok(())
+}
+
+/// Kind of the item described by the sidecar.
+#[derive(Clone, Copy)]
+enum Item {
+    Dataset,
+    Resource,
+}
+
+/// Write the sidecar from the template of the item.
+fn write_sidecar(path: &Path, fields: &Map<String, Value>, item: Item) -> Result<()> {
+    let schema = schema::current();
+    let fields_of_item = match item {
+        Item::Dataset => &schema.dataset,
+        Item::Resource => &schema.resource,
+    };
+    sidecar::write(
+        path,
+        fields,
+        &schema::names(fields_of_item),
+        &schema::comments(fields_of_item),
+    )
+}
+
+/// Update fields of the existing sidecar, keeping the other ones.
+fn update_sidecar(path: &Path, metadata: Value, item: Item) -> Result<()> {
+    let mut fields = sidecar::read(path)?.unwrap_or_default();
+    if let Value::Object(metadata) = metadata {
+        fields.extend(metadata);
+    }
+    write_sidecar(path, &fields, item)
+}
+
+fn metadata_fields(metadata: &types::Metadata) -> Map<String, Value> {
+    match metadata {
+        types::Metadata::Object(Value::Object(fields)) => fields.clone(),
+        _ => Map::new(),
+    }
+}
+
+/// Move metadata of every dataset and resource from metadata.csv into TOML
+/// sidecars. Root metadata stays in the sheet.
+pub fn convert_metadata_to_toml<T: AsRef<OsStr>>(path: T) -> Result<()> {
+    let source = read_source_path(&path)?;
+    let mut sheet = read_sheet(&source)?;
+    for dataset in &source.datasets {
+        write_sidecar(
+            &dataset.sidecar_path(),
+            &metadata_fields(&dataset.metadata),
+            Item::Dataset,
+        )?;
+        for resource in &dataset.resources {
+            write_sidecar(
+                &resource.sidecar_path(),
+                &metadata_fields(&resource.metadata),
+                Item::Resource,
+            )?;
+        }
+        sheet.remove_column(&dataset.name);
+    }
+    if source.metadata_path().is_file() {
+        sheet.write(source.metadata_path())?;
+    }
+    Ok(())
+}
+
+/// Move metadata from TOML sidecars into metadata.csv and remove the
+/// sidecars.
+pub fn convert_metadata_to_sheet<T: AsRef<OsStr>>(path: T) -> Result<()> {
+    let source = read_source_path(&path)?;
+    let mut sheet = read_sheet(&source)?;
+    let mut sidecars = Vec::new();
+    for dataset in &source.datasets {
+        let column = sheet.column_or_insert(&dataset.name);
+        if dataset.sidecar_path().is_file() {
+            for (field, value) in &metadata_fields(&dataset.metadata) {
+                sheet.set_dataset_value(column, field, cell_value(value));
+            }
+            sidecars.push(dataset.sidecar_path());
+        }
+        for resource in &dataset.resources {
+            if resource.sidecar_path().is_file() {
+                sheet.group_or_insert(column, &resource.name);
+                for (field, value) in &metadata_fields(&resource.metadata) {
+                    sheet.set_resource_value(column, &resource.name, field, cell_value(value));
+                }
+                sidecars.push(resource.sidecar_path());
+            }
+        }
+    }
+    sheet.write(source.metadata_path())?;
+    for path in sidecars {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Write metadata.xlsx with the content of metadata.csv, or with the header
+/// only if there is no metadata yet, and remove metadata.csv. Existing
+/// metadata.xlsx is kept as is.
+pub fn convert_metadata_to_xlsx<T: AsRef<OsStr>>(path: T) -> Result<()> {
+    let source = read_source_path(&path)?;
+    let csv = source.metadata_path();
+    if xlsx::is_xlsx(&csv) {
+        return Ok(());
+    }
+    read_sheet(&source)?.write(csv.with_extension(xlsx::EXTENSION))?;
+    if csv.is_file() {
+        std::fs::remove_file(csv)?;
+    }
+    Ok(())
+}
+
+/// Pre-populate metadata.csv with columns of the dataset folders and groups
+/// of their files.
+pub fn generate_template<T: AsRef<OsStr>>(path: T) -> Result<()> {
+    read_source_path(&path)?.generate_template()
+}
+
+/// Non-empty fields of the metadata.
+fn filled_fields(metadata: &types::Metadata) -> Map<String, Value> {
+    metadata_fields(metadata)
+        .into_iter()
+        .filter(|(_, value)| !matches!(value, Value::String(s) if s.is_empty()))
+        .collect()
+}
+
+/// Check metadata of the dataset, merged with the root metadata, without
+/// the portal.
+pub fn validate_dataset_locally<T: AsRef<OsStr>>(
+    path: T,
+    name: &str,
+) -> Result<types::ValidationResult> {
+    let source = read_source_path(&path)?;
+    let dataset = source
+        .get_dataset(name)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", name)))?;
+    let mut fields = filled_fields(&source.metadata);
+    fields.extend(filled_fields(&dataset.metadata));
+    Ok(validation::validate_dataset(&types::Metadata::Object(
+        Value::Object(fields),
+    )))
+}
+
+/// Check metadata of the resource without the portal.
+pub fn validate_resource_locally<T: AsRef<OsStr>>(
+    path: T,
+    dataset: &str,
+    name: &str,
+) -> Result<types::ValidationResult> {
+    let source = read_source_path(&path)?;
+    let resource = source
+        .get_dataset(dataset)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", dataset)))?
+        .get_resoure(name)
+        .ok_or_else(|| FdpError::NotFound(format!("Resource {} does not exist", name)))?;
+    Ok(validation::validate_resource(
+        &resource.metadata_with_format(),
+    ))
+}
+
 pub fn read_source_path<T: AsRef<OsStr>>(path: T) -> Result<types::Source> {
-    types::Source::new(path).ok_or("Directory does not exist".into())
+    types::Source::new(path).ok_or_else(|| FdpError::NotFound("Directory does not exist".into()))
 }
 
 pub fn add_dataset<T: AsRef<OsStr>>(path: T, name: &str) -> Result<()> {
     let mut source = read_source_path(&path)?;
-    match source.add_dataset(name) {
-        Ok(_) => Ok(()),
-        Err(err) => {
-            log::error!("{}", err);
-            Err("Dataset cannot be created".into())
-        }
-    }
+    source.add_dataset(name)?;
+    Ok(())
 }
 
 pub fn add_resource<T: AsRef<OsStr>>(path: T, dataset: &str, name: &str) -> Result<()> {
     let mut source = read_source_path(&path)?;
-    match source.get_dataset_mut(dataset) {
-        None => Ok(()),
-        Some(dataset) => match dataset.add_resource(name) {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                log::error!("{}", err);
-                Err("Resource cannot be created".into())
-            }
-        },
+    source
+        .get_dataset_mut(dataset)
+        .ok_or_else(|| FdpError::NotFound(format!("Dataset {} does not exist", dataset)))?
+        .add_resource(name)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_error_from_ckan() {
+        assert!(matches!(
+            FdpError::from(CKANError::NotFound("Upload".into())),
+            FdpError::NotFound(_)
+        ));
+        assert!(matches!(
+            FdpError::from(CKANError::Request("Connection refused".into())),
+            FdpError::Transport(_)
+        ));
+        match FdpError::from(CKANError::Validation(json!({"name": ["Missing value"]}))) {
+            FdpError::Validation(errors) => assert_eq!(json!({"name": ["Missing value"]}), errors),
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_error_serialization() {
+        assert_eq!(
+            json!({"type": "not_found", "message": "Upload"}),
+            json!(FdpError::NotFound("Upload".into()))
+        );
+        assert_eq!(
+            json!({"type": "validation", "message": {"name": ["Missing value"]}}),
+            json!(FdpError::Validation(json!({"name": ["Missing value"]})))
+        );
+    }
+
+    #[test]
+    fn test_save_root_metadata_keeps_datasets() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        let path = dir.path().join("metadata.csv");
+        std::fs::write(
+            &path,
+            "Dataset Field,Description,Master metadata,dataset\n\
+             title,,Root,Dataset title\n\
+             Resource Field,,,file.csv\n\
+             name,,,File\n",
+        )
+        .unwrap();
+
+        save_root_metadata(dir.path(), json!({"title": "New root", "notes": "Notes"})).unwrap();
+
+        let source = read_source_path(dir.path()).unwrap();
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "New root", "notes": "Notes"})),
+            source.metadata
+        );
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "Dataset title"})),
+            dataset.metadata
+        );
+        assert_eq!(
+            types::Metadata::Object(json!({"name": "File"})),
+            dataset.get_resoure("file.csv").unwrap().metadata
+        );
+    }
+
+    #[test]
+    fn test_save_dataset_and_resource_metadata() {
+        let dir = testing::source(&[("file.csv", 1), ("other.csv", 1)]);
+
+        save_dataset_metadata(dir.path(), "dataset", json!({"title": "Dataset"})).unwrap();
+        save_resource_metadata(dir.path(), "dataset", "file.csv", json!({"name": "File"})).unwrap();
+        save_resource_metadata(dir.path(), "dataset", "other.csv", json!({"name": "Other"}))
+            .unwrap();
+        save_dataset_metadata(dir.path(), "dataset", json!({"notes": "Notes"})).unwrap();
+        save_resource_metadata(
+            dir.path(),
+            "dataset",
+            "file.csv",
+            json!({"name": "Renamed", "format": "CSV"}),
+        )
+        .unwrap();
+
+        let source = read_source_path(dir.path()).unwrap();
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "Dataset", "notes": "Notes"})),
+            dataset.metadata
+        );
+        assert_eq!(
+            types::Metadata::Object(json!({"name": "Renamed", "format": "CSV"})),
+            dataset.get_resoure("file.csv").unwrap().metadata
+        );
+        assert_eq!(
+            types::Metadata::Object(json!({"name": "Other"})),
+            dataset.get_resoure("other.csv").unwrap().metadata
+        );
+
+        assert!(matches!(
+            save_resource_metadata(dir.path(), "dataset", "missing.csv", json!({})),
+            Err(FdpError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_xlsx_metadata_is_preferred() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        let csv = "Dataset Field,Description,Master metadata,dataset\n\
+                   title,,From CSV,CSV dataset\n";
+        std::fs::write(dir.path().join("metadata.csv"), csv).unwrap();
+        let xlsx = dir.path().join("metadata.xlsx");
+        Sheet::new(vec![
+            vec![
+                "Dataset Field".into(),
+                "Description".into(),
+                "Master metadata".into(),
+            ],
+            vec!["title".into(), "".into(), "From XLSX".into()],
+        ])
+        .write(&xlsx)
+        .unwrap();
+
+        save_dataset_metadata(
+            dir.path(),
+            "dataset",
+            json!({"publication_date": "2022-11-24"}),
+        )
+        .unwrap();
+
+        let source = read_source_path(dir.path()).unwrap();
+        assert_eq!(xlsx, source.metadata_path());
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "From XLSX", "publication_date": ""})),
+            source.metadata
+        );
+        assert_eq!(
+            types::Metadata::Object(json!({"publication_date": "2022-11-24"})),
+            source.get_dataset("dataset").unwrap().metadata
+        );
+        assert_eq!(
+            csv,
+            std::fs::read_to_string(dir.path().join("metadata.csv")).unwrap()
+        );
+        assert_eq!(
+            vec![Some("metadata.csv".into())],
+            source
+                .diagnostics
+                .iter()
+                .map(|d| d.file.clone())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_convert_metadata_to_xlsx() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        convert_metadata_to_xlsx(dir.path()).unwrap();
+        let source = read_source_path(dir.path()).unwrap();
+        assert_eq!(dir.path().join("metadata.xlsx"), source.metadata_path);
+        assert_eq!(1, Sheet::read(&source.metadata_path).unwrap().rows().len());
+
+        std::fs::remove_file(&source.metadata_path).unwrap();
+        std::fs::write(
+            dir.path().join("metadata.csv"),
+            "Dataset Field,Description,Master metadata,dataset\n\
+             title,,Root,Dataset\n",
+        )
+        .unwrap();
+        convert_metadata_to_xlsx(dir.path()).unwrap();
+        assert!(!dir.path().join("metadata.csv").exists());
+        let source = read_source_path(dir.path()).unwrap();
+        assert!(source.diagnostics.is_empty());
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "Dataset"})),
+            source.get_dataset("dataset").unwrap().metadata
+        );
+    }
+
+    fn field(metadata: &types::Metadata, name: &str) -> Option<Value> {
+        match metadata {
+            types::Metadata::Object(fields) => fields.get(name).cloned(),
+            types::Metadata::Empty => None,
+        }
+    }
+
+    #[test]
+    fn test_toml_sidecars() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        let folder = dir.path().join("dataset");
+        std::fs::write(
+            dir.path().join("metadata.csv"),
+            "Dataset Field,Description,Master metadata,dataset\n\
+             title,,Root,Sheet title\n\
+             notes,,,Sheet notes\n\
+             Resource Field,,,file.csv\n\
+             name,,,File\n",
+        )
+        .unwrap();
+        std::fs::write(folder.join("metadata.toml"), "title = \"TOML title\"\n").unwrap();
+
+        let source = read_source_path(dir.path()).unwrap();
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(
+            types::Metadata::Object(json!({"title": "TOML title", "notes": "Sheet notes"})),
+            dataset.metadata
+        );
+        assert_eq!(1, dataset.resources.len());
+
+        save_dataset_metadata(dir.path(), "dataset", json!({"author": "Author"})).unwrap();
+        let sidecar = std::fs::read_to_string(folder.join("metadata.toml")).unwrap();
+        assert!(sidecar.contains("# The name of the author\nauthor = 'Author'\n"));
+        assert!(sidecar.contains("title = 'TOML title'\n"));
+
+        convert_metadata_to_toml(dir.path()).unwrap();
+        let source = read_source_path(dir.path()).unwrap();
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(
+            Some(json!("Sheet notes")),
+            field(&dataset.metadata, "notes")
+        );
+        assert_eq!(
+            Some(json!("File")),
+            field(&dataset.get_resoure("file.csv").unwrap().metadata, "name")
+        );
+        assert!(folder.join("file.csv.toml").is_file());
+        assert_eq!(
+            None,
+            Sheet::read(dir.path().join("metadata.csv"))
+                .unwrap()
+                .column("dataset")
+        );
+
+        save_resource_metadata(dir.path(), "dataset", "file.csv", json!({"format": "CSV"}))
+            .unwrap();
+        convert_metadata_to_sheet(dir.path()).unwrap();
+        assert!(!folder.join("metadata.toml").exists());
+        assert!(!folder.join("file.csv.toml").exists());
+        let source = read_source_path(dir.path()).unwrap();
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(Some(json!("TOML title")), field(&dataset.metadata, "title"));
+        assert_eq!(Some(json!("Author")), field(&dataset.metadata, "author"));
+        assert_eq!(
+            Some(json!("CSV")),
+            field(&dataset.get_resoure("file.csv").unwrap().metadata, "format")
+        );
+    }
+
+    #[test]
+    fn test_broken_sidecar_is_reported() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        let folder = dir.path().join("dataset");
+        std::fs::write(folder.join("file.csv.toml"), "name = \n").unwrap();
+
+        let source = read_source_path(dir.path()).unwrap();
+        assert_eq!(1, source.diagnostics.len());
+        let diagnostic = &source.diagnostics[0];
+        assert_eq!(Some("dataset/file.csv.toml".into()), diagnostic.file);
+        assert!(diagnostic.message.starts_with("Cannot parse metadata"));
+        assert_eq!(
+            types::Metadata::Empty,
+            source.get_dataset("dataset").unwrap().resources[0].metadata
+        );
+    }
+
+    #[test]
+    fn test_validate_locally() {
+        let dir = testing::source(&[("file.csv", 1)]);
+        std::fs::write(
+            dir.path().join("metadata.csv"),
+            "Dataset Field,Description,Master metadata,dataset\n\
+             title,,Root,\n\
+             name,,,Dataset\n\
+             access_level,,open,\n\
+             Resource Field,,,file.csv\n\
+             format_label,,,Table\n",
+        )
+        .unwrap();
+
+        let result = validate_dataset_locally(dir.path(), "dataset").unwrap();
+        assert_eq!(
+            json!({"title": "Root", "name": "Dataset", "access_level": "open"}),
+            result.data
+        );
+        assert_eq!(
+            vec!["name"],
+            result
+                .errors
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>()
+        );
+
+        let result = validate_resource_locally(dir.path(), "dataset", "file.csv").unwrap();
+        assert!(result.errors.get("format_label").is_some());
+        assert_eq!(Some(&json!("CSV")), result.data.get("format"));
+        assert!(matches!(
+            validate_dataset_locally(dir.path(), "missing"),
+            Err(FdpError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_template() {
+        let dir = testing::source(&[("flow.csv", 1), ("report.pdf", 1), ("README", 1)]);
+        std::fs::create_dir(dir.path().join("empty")).unwrap();
+        std::fs::write(
+            dir.path().join("metadata.csv"),
+            "Dataset Field,Description,Master metadata,dataset\n\
+             title,,Root,Flows\n\
+             Resource Field,,,flow.csv\n\
+             name,,,Flow table\n",
+        )
+        .unwrap();
+
+        generate_template(dir.path()).unwrap();
+        let source = read_source_path(dir.path()).unwrap();
+        assert!(source.diagnostics.is_empty());
+        assert_eq!(Some(json!("Root")), field(&source.metadata, "title"));
+        let dataset = source.get_dataset("dataset").unwrap();
+        assert_eq!(Some(json!("Flows")), field(&dataset.metadata, "title"));
+        let resource = |name: &str| dataset.get_resoure(name).unwrap().metadata.clone();
+        assert_eq!(
+            Some(json!("Flow table")),
+            field(&resource("flow.csv"), "name")
+        );
+        assert_eq!(Some(json!("CSV")), field(&resource("flow.csv"), "format"));
+        assert_eq!(
+            Some(json!("report")),
+            field(&resource("report.pdf"), "name")
+        );
+        assert_eq!(Some(json!("PDF")), field(&resource("report.pdf"), "format"));
+        assert_eq!(
+            Some(json!("PDF")),
+            field(&resource("report.pdf"), "format_label")
+        );
+        assert_eq!(Some(json!("README")), field(&resource("README"), "name"));
+        assert_eq!(None, dataset.get_resoure("README").unwrap().detect_format());
+        assert_eq!(None, field(&resource("README"), "format"));
+
+        let sheet = Sheet::read(source.metadata_path()).unwrap();
+        assert_eq!(
+            vec![
+                "Dataset Field",
+                "Description",
+                "Master metadata",
+                "dataset",
+                "empty"
+            ],
+            sheet.rows()[0]
+        );
+        let row = sheet.dataset_field("license_id").unwrap();
+        assert!(sheet.cell(row, 1).starts_with("ID of the license"));
+
+        // running again doesn't duplicate anything
+        generate_template(dir.path()).unwrap();
+        assert_eq!(sheet, Sheet::read(source.metadata_path()).unwrap());
     }
 }
@@ -0,0 +1,466 @@
+//! In-memory implementation of [`FdpClient`].
+//!
+//! [`MockFdpClient`] behaves like the portal with the `nswflood` extension:
+//! it keeps the current project, validation results and the state of
+//! uploads, so the upload logic can be tested and the application can be
+//! demonstrated without the server. Responses and failures are scripted via
+//! `set_*` and `fail_*` methods.
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::ffi::OsStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::action::{find_resource, FdpClient};
+use crate::chunk;
+use crate::types::{
+    ProgressedUpload, Project, RegisteredUpload, UploadData, User, ValidationResult,
+};
+use crate::FdpError;
+
+type Key = (String, String);
+
+fn key(dataset: &str, name: &str) -> Key {
+    (dataset.to_string(), name.to_string())
+}
+
+#[derive(Debug, Default)]
+struct State {
+    user: Option<User>,
+    projects: Vec<Project>,
+    project: Option<String>,
+    finalized: bool,
+    submission: Vec<Value>,
+    dataset_results: HashMap<String, ValidationResult>,
+    resource_results: HashMap<Key, ValidationResult>,
+    validated: BTreeMap<Key, Value>,
+    uploads: BTreeMap<Key, UploadData>,
+    parts: Vec<(String, String, u64)>,
+    part_failures: Vec<(Key, u64, FdpError)>,
+    call_failures: HashMap<&'static str, VecDeque<FdpError>>,
+}
+
+/// Portal that lives in memory. All methods take `&self`, so the client can
+/// be scripted while it's shared with the code under test.
+#[derive(Debug, Default)]
+pub struct MockFdpClient {
+    state: Mutex<State>,
+    latency: Duration,
+    chunk_size: Option<u64>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+impl MockFdpClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every part takes `latency` to upload.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Override the chunk size requested at registration.
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+
+    pub fn set_user(&self, user: User) {
+        self.state.lock().unwrap().user = Some(user);
+    }
+
+    pub fn set_projects(&self, projects: Vec<Project>) {
+        self.state.lock().unwrap().projects = projects;
+    }
+
+    /// Extra items of the submission, reported along with validations and
+    /// uploads.
+    pub fn set_submission(&self, submission: Vec<Value>) {
+        self.state.lock().unwrap().submission = submission;
+    }
+
+    pub fn set_dataset_validation(&self, name: &str, result: ValidationResult) {
+        self.state
+            .lock()
+            .unwrap()
+            .dataset_results
+            .insert(name.to_string(), result);
+    }
+
+    pub fn set_resource_validation(&self, dataset: &str, name: &str, result: ValidationResult) {
+        self.state
+            .lock()
+            .unwrap()
+            .resource_results
+            .insert(key(dataset, name), result);
+    }
+
+    /// Replace the state of the upload, e.g. to emulate the one started
+    /// elsewhere.
+    pub fn set_upload(&self, data: UploadData) {
+        let key = key(&data.dataset, &data.name);
+        self.state.lock().unwrap().uploads.insert(key, data);
+    }
+
+    /// Reject the next attempt to send the part with `error`.
+    pub fn fail_part(&self, dataset: &str, name: &str, part: u64, error: FdpError) {
+        self.state
+            .lock()
+            .unwrap()
+            .part_failures
+            .push((key(dataset, name), part, error));
+    }
+
+    /// Fail the next call of the `FdpClient` method named `method`.
+    pub fn fail_next(&self, method: &'static str, error: FdpError) {
+        self.state
+            .lock()
+            .unwrap()
+            .call_failures
+            .entry(method)
+            .or_default()
+            .push_back(error);
+    }
+
+    pub fn project(&self) -> Option<String> {
+        self.state.lock().unwrap().project.clone()
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.state.lock().unwrap().finalized
+    }
+
+    pub fn upload(&self, dataset: &str, name: &str) -> Option<UploadData> {
+        self.state
+            .lock()
+            .unwrap()
+            .uploads
+            .get(&key(dataset, name))
+            .cloned()
+    }
+
+    /// Parts of the resource received so far, in order of arrival.
+    pub fn parts(&self, dataset: &str, name: &str) -> Vec<u64> {
+        self.state
+            .lock()
+            .unwrap()
+            .parts
+            .iter()
+            .filter(|(d, n, _)| d == dataset && n == name)
+            .map(|(_, _, part)| *part)
+            .collect()
+    }
+
+    pub fn clear_parts(&self) {
+        self.state.lock().unwrap().parts.clear();
+    }
+
+    /// Largest number of parts that were being sent at once.
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+
+    fn check(&self, method: &'static str) -> crate::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .call_failures
+            .get_mut(method)
+            .and_then(|q| q.pop_front())
+        {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn flake(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        match self.upload(dataset, name) {
+            Some(data) => Ok(ProgressedUpload {
+                id: format!("upload:{}:{}", dataset, name),
+                data,
+            }),
+            None => Err(FdpError::NotFound("Upload has not started yet".into())),
+        }
+    }
+
+    fn validated(&self, key: Key, kind: &str, result: ValidationResult) -> ValidationResult {
+        let mut extras = json!({"type": kind, "dataset": &key.0, "errors": &result.errors});
+        if kind == "validated-resource" {
+            extras["resource"] = json!(&key.1);
+        }
+        self.state
+            .lock()
+            .unwrap()
+            .validated
+            .insert(key, json!({ "extras": extras }));
+        result
+    }
+}
+
+fn passed(data: Value) -> ValidationResult {
+    ValidationResult {
+        data,
+        errors: json!({}),
+    }
+}
+
+#[async_trait]
+impl FdpClient for MockFdpClient {
+    async fn submission_finalize(&self) -> crate::Result<()> {
+        self.check("submission_finalize")?;
+        self.state.lock().unwrap().finalized = true;
+        Ok(())
+    }
+
+    async fn user_info(&self) -> crate::Result<User> {
+        self.check("user_info")?;
+        Ok(self.state.lock().unwrap().user.clone().unwrap_or(User {
+            display_name: "Mock user".into(),
+            id: "mock".into(),
+        }))
+    }
+
+    async fn available_projects(&self, name: &str) -> crate::Result<Vec<Project>> {
+        self.check("available_projects")?;
+        let name = name.to_lowercase();
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .projects
+            .iter()
+            .filter(|p| p.name.contains(&name) || p.title.to_lowercase().contains(&name))
+            .cloned()
+            .collect())
+    }
+
+    async fn project_set(&self, id: Option<&str>) -> crate::Result<Value> {
+        self.check("project_set")?;
+        self.state.lock().unwrap().project = id.map(String::from);
+        Ok(json!({ "id": id }))
+    }
+
+    async fn show_submission(&self) -> crate::Result<Vec<Value>> {
+        self.check("show_submission")?;
+        let state = self.state.lock().unwrap();
+        let uploads = state.uploads.iter().map(|((dataset, name), data)| {
+            json!({
+                "id": format!("upload:{}:{}", dataset, name),
+                "data": data,
+                "extras": {"type": "client-upload", "dataset": dataset, "resource": name},
+            })
+        });
+        Ok(state
+            .submission
+            .iter()
+            .chain(state.validated.values())
+            .cloned()
+            .chain(uploads)
+            .collect())
+    }
+
+    async fn validate_dataset(&self, _path: &OsStr, name: &str) -> crate::Result<ValidationResult> {
+        self.check("validate_dataset")?;
+        let result = self
+            .state
+            .lock()
+            .unwrap()
+            .dataset_results
+            .get(name)
+            .cloned();
+        let result = result.unwrap_or_else(|| passed(json!({})));
+        Ok(self.validated(key(name, ""), "validated-dataset", result))
+    }
+
+    async fn validate_resource(
+        &self,
+        _path: &OsStr,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ValidationResult> {
+        self.check("validate_resource")?;
+        let key = key(dataset, name);
+        let result = self
+            .state
+            .lock()
+            .unwrap()
+            .resource_results
+            .get(&key)
+            .cloned();
+        let result = result.unwrap_or_else(|| passed(json!({})));
+        Ok(self.validated(key, "validated-resource", result))
+    }
+
+    async fn show_upload(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        self.check("show_upload")?;
+        self.flake(dataset, name)
+    }
+
+    async fn register_upload(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+        chunk_size: u64,
+    ) -> crate::Result<RegisteredUpload> {
+        self.check("register_upload")?;
+        let size = find_resource(path, dataset, name)?.size;
+        let chunk_size = self.chunk_size.unwrap_or(chunk_size);
+        self.set_upload(UploadData {
+            key: format!("{}/{}", dataset, name),
+            size,
+            dataset: dataset.into(),
+            name: name.into(),
+            completed: false,
+            bytes_uploaded: 0,
+            chunk_size: Some(chunk_size),
+            sha256: None,
+            md5: None,
+        });
+        Ok(RegisteredUpload {
+            id: format!("upload:{}:{}", dataset, name),
+            chunk_size: self.chunk_size,
+        })
+    }
+
+    async fn upload_part(
+        &self,
+        _path: &str,
+        dataset: &str,
+        name: &str,
+        part: u64,
+        chunk_size: u64,
+    ) -> crate::Result<ProgressedUpload> {
+        self.check("upload_part")?;
+        {
+            let mut state = self.state.lock().unwrap();
+            let key = key(dataset, name);
+            if let Some(idx) = state
+                .part_failures
+                .iter()
+                .position(|(k, p, _)| *k == key && *p == part)
+            {
+                return Err(state.part_failures.remove(idx).2);
+            }
+        }
+
+        let running = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(self.latency).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        {
+            let mut state = self.state.lock().unwrap();
+            let data = state
+                .uploads
+                .get_mut(&key(dataset, name))
+                .ok_or_else(|| FdpError::NotFound("Upload has not started yet".into()))?;
+            data.bytes_uploaded += chunk::part_size(part, chunk_size, data.size);
+            state.parts.push((dataset.into(), name.into(), part));
+        }
+        self.flake(dataset, name)
+    }
+
+    async fn complete_upload(
+        &self,
+        _path: &str,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ProgressedUpload> {
+        self.check("complete_upload")?;
+        if let Some(data) = self
+            .state
+            .lock()
+            .unwrap()
+            .uploads
+            .get_mut(&key(dataset, name))
+        {
+            data.completed = true;
+        }
+        self.flake(dataset, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::source;
+
+    #[tokio::test]
+    async fn test_projects_and_submission() {
+        let mock = MockFdpClient::new();
+        mock.set_projects(vec![
+            Project {
+                id: "1".into(),
+                name: "river".into(),
+                title: "River study".into(),
+            },
+            Project {
+                id: "2".into(),
+                name: "coast".into(),
+                title: "Coastal study".into(),
+            },
+        ]);
+        let found = mock.available_projects("RIVER").await.unwrap();
+        assert_eq!(vec!["1"], found.iter().map(|p| &p.id).collect::<Vec<_>>());
+
+        mock.project_set(Some("1")).await.unwrap();
+        assert_eq!(Some("1".into()), mock.project());
+
+        mock.set_resource_validation(
+            "dataset",
+            "file",
+            ValidationResult {
+                data: json!({}),
+                errors: json!({"format": ["Missing value"]}),
+            },
+        );
+        mock.validate_resource("/".as_ref(), "dataset", "file")
+            .await
+            .unwrap();
+        let submission = mock.show_submission().await.unwrap();
+        assert_eq!(
+            json!({
+                "type": "validated-resource",
+                "dataset": "dataset",
+                "resource": "file",
+                "errors": {"format": ["Missing value"]}
+            }),
+            submission[0]["extras"]
+        );
+
+        mock.submission_finalize().await.unwrap();
+        assert!(mock.is_finalized());
+    }
+
+    #[tokio::test]
+    async fn test_scripted_failures() {
+        let dir = source(&[("file", 25)]);
+        let path = dir.path().to_str().unwrap();
+        let mock = MockFdpClient::new();
+
+        mock.fail_next("user_info", FdpError::Auth("Expired token".into()));
+        assert!(matches!(mock.user_info().await, Err(FdpError::Auth(_))));
+        assert!(mock.user_info().await.is_ok());
+
+        assert!(matches!(
+            mock.show_upload("dataset", "file").await,
+            Err(FdpError::NotFound(_))
+        ));
+        mock.register_upload(path, "dataset", "file", 10)
+            .await
+            .unwrap();
+        mock.fail_part("dataset", "file", 2, FdpError::Transport("Timeout".into()));
+
+        for part in 1..=3 {
+            let _ = mock.upload_part(path, "dataset", "file", part, 10).await;
+        }
+        assert_eq!(vec![1, 3], mock.parts("dataset", "file"));
+        assert_eq!(15, mock.upload("dataset", "file").unwrap().bytes_uploaded);
+    }
+}
@@ -0,0 +1,240 @@
+//! Names of the actions and payload fields used by the portal.
+//!
+//! The submission workflow is implemented by the portal extension, which
+//! registers its actions under the programme-specific prefix. Profile maps
+//! every operation of [`FdpClient`](crate::action::FdpClient) to the action
+//! name and can be loaded from the TOML file:
+//!
+//! ```toml
+//! backend = "extension"
+//! prefix = "agency_"
+//! schema = "agency_schema.toml"
+//! md5 = true
+//!
+//! [actions]
+//! me = "agency_user_show"
+//!
+//! [fields]
+//! part_number = "part"
+//! ```
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::FdpError;
+
+pub const DEFAULT_PREFIX: &str = "nswflood_";
+
+/// Set of actions provided by the portal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Submission workflow of the portal extension.
+    #[default]
+    Extension,
+    /// Core CKAN API, see [`StockClient`](crate::stock::StockClient).
+    Stock,
+}
+
+/// Operation of the portal extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Me,
+    AvailableProjectList,
+    SubmissionProjectSet,
+    SubmissionDetails,
+    SubmissionValidateDataset,
+    SubmissionValidateResource,
+    SubmissionFinalize,
+    UploadShow,
+    UploadRegister,
+    UploadProgress,
+    UploadComplete,
+}
+
+impl Action {
+    /// Name of the action without prefix.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Self::Me => "me",
+            Self::AvailableProjectList => "available_project_list",
+            Self::SubmissionProjectSet => "submission_project_set",
+            Self::SubmissionDetails => "submission_details",
+            Self::SubmissionValidateDataset => "submission_validate_dataset",
+            Self::SubmissionValidateResource => "submission_validate_resource",
+            Self::SubmissionFinalize => "submission_finalize",
+            Self::UploadShow => "upload_show",
+            Self::UploadRegister => "upload_register",
+            Self::UploadProgress => "upload_progress",
+            Self::UploadComplete => "upload_complete",
+        }
+    }
+}
+
+/// Full names of the actions that don't follow the prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Actions {
+    pub me: Option<String>,
+    pub available_project_list: Option<String>,
+    pub submission_project_set: Option<String>,
+    pub submission_details: Option<String>,
+    pub submission_validate_dataset: Option<String>,
+    pub submission_validate_resource: Option<String>,
+    pub submission_finalize: Option<String>,
+    pub upload_show: Option<String>,
+    pub upload_register: Option<String>,
+    pub upload_progress: Option<String>,
+    pub upload_complete: Option<String>,
+}
+
+impl Actions {
+    pub fn get(&self, action: Action) -> Option<&String> {
+        match action {
+            Action::Me => &self.me,
+            Action::AvailableProjectList => &self.available_project_list,
+            Action::SubmissionProjectSet => &self.submission_project_set,
+            Action::SubmissionDetails => &self.submission_details,
+            Action::SubmissionValidateDataset => &self.submission_validate_dataset,
+            Action::SubmissionValidateResource => &self.submission_validate_resource,
+            Action::SubmissionFinalize => &self.submission_finalize,
+            Action::UploadShow => &self.upload_show,
+            Action::UploadRegister => &self.upload_register,
+            Action::UploadProgress => &self.upload_progress,
+            Action::UploadComplete => &self.upload_complete,
+        }
+        .as_ref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub backend: Backend,
+    /// Prepended to the actions that are not listed in `actions`.
+    pub prefix: String,
+    pub actions: Actions,
+    /// Payload fields that are named differently by the portal.
+    pub fields: HashMap<String, String>,
+    /// Path of the metadata [`Schema`](crate::schema::Schema) that replaces
+    /// the embedded one. Relative path is resolved against the directory of
+    /// the profile by [`Profile::load`].
+    pub schema: Option<String>,
+    /// Send MD5 of the parts and of the file along with SHA-256.
+    pub md5: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            backend: Backend::default(),
+            prefix: DEFAULT_PREFIX.into(),
+            actions: Actions::default(),
+            fields: HashMap::new(),
+            schema: None,
+            md5: false,
+        }
+    }
+}
+
+impl Profile {
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(&path)?;
+        let mut profile: Self = toml::from_str(&content).map_err(|err| {
+            FdpError::Plain(format!(
+                "Invalid profile {}: {}",
+                path.as_ref().display(),
+                err
+            ))
+        })?;
+        if let (Some(schema), Some(dir)) = (&profile.schema, path.as_ref().parent()) {
+            profile.schema = Some(dir.join(schema).to_string_lossy().into());
+        }
+        Ok(profile)
+    }
+
+    pub fn action(&self, action: Action) -> String {
+        match self.actions.get(action) {
+            Some(name) => name.clone(),
+            None => format!("{}{}", self.prefix, action.suffix()),
+        }
+    }
+
+    pub fn field<'a>(&'a self, name: &'a str) -> &'a str {
+        self.fields.get(name).map(String::as_str).unwrap_or(name)
+    }
+
+    /// Rename top-level fields of the payload.
+    pub fn payload(&self, payload: Value) -> Value {
+        match payload {
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (self.field(&name).to_string(), value))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_default_profile() {
+        let profile = Profile::default();
+        assert_eq!(Backend::Extension, profile.backend);
+        assert_eq!("nswflood_me", profile.action(Action::Me));
+        assert_eq!(
+            "nswflood_upload_progress",
+            profile.action(Action::UploadProgress)
+        );
+        assert_eq!(json!({"id": "1"}), profile.payload(json!({"id": "1"})));
+    }
+
+    #[test]
+    fn test_load_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profile.toml");
+        std::fs::write(
+            &path,
+            "prefix = \"agency_\"\n\
+             [actions]\nme = \"agency_user_show\"\n\
+             [fields]\npart_number = \"part\"\n",
+        )
+        .unwrap();
+
+        let profile = Profile::load(&path).unwrap();
+        assert_eq!("agency_user_show", profile.action(Action::Me));
+        assert_eq!(
+            "agency_submission_details",
+            profile.action(Action::SubmissionDetails)
+        );
+        assert_eq!("part", profile.field("part_number"));
+        assert_eq!(
+            json!({"part": 1, "name": "file"}),
+            profile.payload(json!({"part_number": 1, "name": "file"}))
+        );
+
+        std::fs::write(
+            &path,
+            "backend = \"stock\"\nschema = \"schema.toml\"\nmd5 = true\n",
+        )
+        .unwrap();
+        let profile = Profile::load(&path).unwrap();
+        assert_eq!(Backend::Stock, profile.backend);
+        assert!(profile.md5);
+        assert_eq!(
+            Some(dir.path().join("schema.toml").to_string_lossy().into()),
+            profile.schema
+        );
+
+        std::fs::write(&path, "[actions]\nunknown = \"action\"\n").unwrap();
+        assert!(matches!(Profile::load(&path), Err(FdpError::Plain(_))));
+    }
+}
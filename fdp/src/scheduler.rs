@@ -0,0 +1,447 @@
+//! Concurrent upload of many resources of the source.
+//!
+//! [`Scheduler`] runs several [`Uploader`]s at once. All of them share the
+//! single [`Limiter`], so the number of requests in flight and the bandwidth
+//! are capped for the whole source rather than for each file. Progress of
+//! individual uploads is combined into [`Progress`] of the source.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::action::FdpClient;
+use crate::chunk::{ChunkSizer, DEFAULT_CHUNK_SIZE};
+use crate::state::UploadControls;
+use crate::upload::{RetryPolicy, UploadControl, UploadEvent, UploadStatus, Uploader};
+use crate::{read_source_path, FdpError};
+
+/// Spreads requests in time so that no more than `rate` bytes are sent per
+/// second.
+#[derive(Debug)]
+struct Bandwidth {
+    rate: u64,
+    next: Mutex<Instant>,
+}
+
+impl Bandwidth {
+    async fn wait(&self, bytes: u64) {
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(bytes as f64 / self.rate as f64);
+            start
+        };
+        tokio::time::sleep_until(start.into()).await;
+    }
+}
+
+/// Global limits shared by uploads. Clones enforce the same limits.
+#[derive(Debug, Clone, Default)]
+pub struct Limiter {
+    permits: Option<Arc<Semaphore>>,
+    bandwidth: Option<Arc<Bandwidth>>,
+}
+
+impl Limiter {
+    /// Allow `concurrency` requests at once and, optionally, no more than
+    /// `bytes_per_second`.
+    pub fn new(concurrency: usize, bytes_per_second: Option<u64>) -> Self {
+        Self {
+            permits: Some(Arc::new(Semaphore::new(concurrency.max(1)))),
+            bandwidth: bytes_per_second.filter(|r| *r > 0).map(|rate| {
+                Arc::new(Bandwidth {
+                    rate,
+                    next: Mutex::new(Instant::now()),
+                })
+            }),
+        }
+    }
+
+    /// Wait until `bytes` can be sent. The request may proceed while the
+    /// returned permit is alive.
+    pub async fn acquire(&self, bytes: u64) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.permits {
+            Some(permits) => permits.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        if let Some(bandwidth) = &self.bandwidth {
+            bandwidth.wait(bytes).await;
+        }
+        permit
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Small files first, so that the most resources are finished early.
+    SmallestFirst,
+    /// Order of datasets and resources in the source.
+    Listed,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SchedulerOptions {
+    /// Requests sent at once, across all files.
+    pub concurrency: usize,
+    /// Parts of the single file sent at once.
+    pub parallel_parts: usize,
+    /// Bandwidth cap in bytes per second.
+    pub bytes_per_second: Option<u64>,
+    /// Chunk size requested for new uploads.
+    pub chunk_size: u64,
+    /// Adapt chunk size of new uploads to the measured throughput.
+    pub adaptive_chunks: bool,
+    pub priority: Priority,
+    #[serde(skip)]
+    pub retry: RetryPolicy,
+}
+
+impl Default for SchedulerOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            parallel_parts: 2,
+            bytes_per_second: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            adaptive_chunks: false,
+            priority: Priority::SmallestFirst,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// Resource waiting for the upload.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct QueuedUpload {
+    pub dataset: String,
+    pub name: String,
+    pub size: u64,
+}
+
+/// Combined progress of all scheduled uploads.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: usize,
+    pub files_total: usize,
+    /// Estimated number of seconds until all files are uploaded.
+    pub eta: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchedulerEvent {
+    pub upload: UploadEvent,
+    pub progress: Progress,
+}
+
+/// Final state of the scheduled upload.
+#[derive(Debug, Serialize)]
+pub struct UploadOutcome {
+    pub dataset: String,
+    pub name: String,
+    pub error: Option<FdpError>,
+}
+
+#[derive(Debug)]
+struct Tracker {
+    started: Instant,
+    bytes_total: u64,
+    files_total: usize,
+    files_done: usize,
+    /// Bytes of every file at the first event and at the latest one.
+    files: HashMap<(String, String), (u64, u64)>,
+}
+
+impl Tracker {
+    fn update(&mut self, event: &UploadEvent) -> Progress {
+        let key = (event.dataset.clone(), event.name.clone());
+        let entry = self
+            .files
+            .entry(key)
+            .or_insert((event.bytes_uploaded, event.bytes_uploaded));
+        entry.1 = event.bytes_uploaded;
+        if event.status == UploadStatus::Completed {
+            self.files_done += 1;
+        }
+
+        let bytes_done: u64 = self.files.values().map(|(_, current)| current).sum();
+        let session: u64 = self
+            .files
+            .values()
+            .map(|(first, current)| current.saturating_sub(*first))
+            .sum();
+        let remaining = self.bytes_total.saturating_sub(bytes_done);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let eta = if remaining == 0 {
+            Some(0.0)
+        } else if session > 0 {
+            Some(remaining as f64 * elapsed / session as f64)
+        } else {
+            None
+        };
+
+        Progress {
+            bytes_done,
+            bytes_total: self.bytes_total,
+            files_done: self.files_done,
+            files_total: self.files_total,
+            eta,
+        }
+    }
+}
+
+type Listener = Arc<dyn Fn(SchedulerEvent) + Send + Sync>;
+
+/// Uploads resources of the source located at `path` concurrently.
+pub struct Scheduler<'a, C: FdpClient + ?Sized> {
+    client: &'a C,
+    path: String,
+    options: SchedulerOptions,
+    only: Option<Vec<(String, String)>>,
+    preferred: Vec<(String, String)>,
+    controls: Option<&'a UploadControls>,
+    listener: Option<Listener>,
+}
+
+impl<'a, C: FdpClient + ?Sized> Scheduler<'a, C> {
+    pub fn new(client: &'a C, path: &str) -> Self {
+        Self {
+            client,
+            path: path.to_string(),
+            options: SchedulerOptions::default(),
+            only: None,
+            preferred: Vec::new(),
+            controls: None,
+            listener: None,
+        }
+    }
+
+    pub fn options(mut self, options: SchedulerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Upload only the given `(dataset, name)` resources instead of the
+    /// whole source.
+    pub fn only(mut self, resources: Vec<(String, String)>) -> Self {
+        self.only = Some(resources);
+        self
+    }
+
+    /// Start with the given `(dataset, name)` resources, in the given order.
+    pub fn prefer(mut self, resources: Vec<(String, String)>) -> Self {
+        self.preferred = resources;
+        self
+    }
+
+    /// Register every upload in `controls`, so it can be paused or cancelled.
+    pub fn controls(mut self, controls: &'a UploadControls) -> Self {
+        self.controls = Some(controls);
+        self
+    }
+
+    pub fn on_event<F: Fn(SchedulerEvent) + Send + Sync + 'static>(mut self, listener: F) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Resources in the order of the upload.
+    pub fn queue(&self) -> crate::Result<Vec<QueuedUpload>> {
+        let source = read_source_path(&self.path)?;
+        let mut queue: Vec<QueuedUpload> = source
+            .datasets
+            .iter()
+            .flat_map(|dataset| {
+                dataset.resources.iter().map(|res| QueuedUpload {
+                    dataset: dataset.name.clone(),
+                    name: res.name.clone(),
+                    size: res.size,
+                })
+            })
+            .filter(|item| match &self.only {
+                Some(only) => only
+                    .iter()
+                    .any(|(d, n)| *d == item.dataset && *n == item.name),
+                None => true,
+            })
+            .collect();
+
+        if self.options.priority == Priority::SmallestFirst {
+            queue.sort_by_key(|item| item.size);
+        }
+        let rank = |item: &QueuedUpload| {
+            self.preferred
+                .iter()
+                .position(|(d, n)| *d == item.dataset && *n == item.name)
+                .unwrap_or(usize::MAX)
+        };
+        queue.sort_by_key(rank);
+        Ok(queue)
+    }
+
+    /// Upload every queued resource. Failure of one upload does not stop the
+    /// rest, so errors are reported per resource.
+    pub async fn run(&self) -> crate::Result<Vec<UploadOutcome>> {
+        let queue = self.queue()?;
+        let limiter = Limiter::new(self.options.concurrency, self.options.bytes_per_second);
+        let tracker = Arc::new(Mutex::new(Tracker {
+            started: Instant::now(),
+            bytes_total: queue.iter().map(|item| item.size).sum(),
+            files_total: queue.len(),
+            files_done: 0,
+            files: HashMap::new(),
+        }));
+
+        let sizer = self.options.adaptive_chunks.then(ChunkSizer::default);
+
+        let uploads = queue.into_iter().map(|item| {
+            let limiter = limiter.clone();
+            let sizer = sizer.clone();
+            let tracker = tracker.clone();
+            let listener = self.listener.clone();
+            async move {
+                let control = match self.controls {
+                    Some(controls) => controls.start(&item.dataset, &item.name),
+                    None => UploadControl::default(),
+                };
+                let mut uploader = Uploader::new(self.client, &self.path)
+                    .retry(self.options.retry.clone())
+                    .limiter(limiter)
+                    .parallel_parts(self.options.parallel_parts)
+                    .chunk_size(self.options.chunk_size)
+                    .control(control);
+                if let Some(sizer) = sizer {
+                    uploader = uploader.adaptive(sizer);
+                }
+                let result = uploader
+                    .on_event(move |upload| {
+                        let progress = tracker.lock().unwrap().update(&upload);
+                        if let Some(listener) = &listener {
+                            listener(SchedulerEvent { upload, progress });
+                        }
+                    })
+                    .upload(&item.dataset, &item.name)
+                    .await;
+                if let Some(controls) = self.controls {
+                    controls.finish(&item.dataset, &item.name);
+                }
+                UploadOutcome {
+                    dataset: item.dataset,
+                    name: item.name,
+                    error: result.err(),
+                }
+            }
+        });
+
+        Ok(stream::iter(uploads)
+            .buffer_unordered(self.options.concurrency.max(1))
+            .collect()
+            .await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockFdpClient;
+    use crate::testing::source;
+
+    fn names(queue: &[QueuedUpload]) -> Vec<&str> {
+        queue.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_queue_priority() {
+        let dir = source(&[("big", 30), ("small", 10), ("medium", 20)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+
+        let scheduler = Scheduler::new(&portal, path);
+        assert_eq!(
+            vec!["small", "medium", "big"],
+            names(&scheduler.queue().unwrap())
+        );
+
+        let scheduler = scheduler.prefer(vec![("dataset".into(), "big".into())]);
+        assert_eq!(
+            vec!["big", "small", "medium"],
+            names(&scheduler.queue().unwrap())
+        );
+
+        let scheduler = scheduler.only(vec![
+            ("dataset".into(), "medium".into()),
+            ("dataset".into(), "small".into()),
+        ]);
+        assert_eq!(vec!["small", "medium"], names(&scheduler.queue().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let files: Vec<(String, usize)> = (0..6).map(|i| (format!("f{}", i), 10)).collect();
+        let files: Vec<(&str, usize)> = files.iter().map(|(n, s)| (n.as_str(), *s)).collect();
+        let dir = source(&files);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().latency(Duration::from_millis(20));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+
+        let outcomes = Scheduler::new(&portal, path)
+            .options(SchedulerOptions {
+                concurrency: 3,
+                ..Default::default()
+            })
+            .on_event(move |e| sink.lock().unwrap().push(e.progress))
+            .run()
+            .await
+            .unwrap();
+
+        assert_eq!(6, outcomes.len());
+        assert!(outcomes.iter().all(|o| o.error.is_none()));
+        assert_eq!(3, portal.max_in_flight());
+
+        let last = events.lock().unwrap().last().cloned().unwrap();
+        assert_eq!(60, last.bytes_total);
+        assert_eq!(60, last.bytes_done);
+        assert_eq!(6, last.files_done);
+        assert_eq!(Some(0.0), last.eta);
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_reported_per_file() {
+        let dir = source(&[("a", 10), ("b", 20)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        portal.fail_part("dataset", "b", 2, FdpError::Auth("Denied".into()));
+
+        let outcomes = Scheduler::new(&portal, path)
+            .options(SchedulerOptions {
+                chunk_size: 10,
+                ..Default::default()
+            })
+            .run()
+            .await
+            .unwrap();
+        let failed: Vec<&str> = outcomes
+            .iter()
+            .filter(|o| o.error.is_some())
+            .map(|o| o.name.as_str())
+            .collect();
+        assert_eq!(vec!["b"], failed);
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_limit() {
+        let limiter = Limiter::new(4, Some(1000));
+        let started = Instant::now();
+        for _ in 0..3 {
+            limiter.acquire(50).await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+}
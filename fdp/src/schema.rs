@@ -0,0 +1,228 @@
+//! Description of the metadata fields.
+//!
+//! The schema lists dataset and resource fields with their types, allowed
+//! values, defaults and help text. Templates, local validation and forms of
+//! the application are built from it. The schema is embedded into the crate
+//! and can be replaced by the file referenced from the portal
+//! [`Profile`](crate::profile::Profile).
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::validation::{Rule, Rules};
+use crate::FdpError;
+
+const EMBEDDED: &str = include_str!("schema.toml");
+
+static DEFAULT: OnceLock<Arc<Schema>> = OnceLock::new();
+static CURRENT: RwLock<Option<Arc<Schema>>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    #[default]
+    Text,
+    LongText,
+    Integer,
+    /// Date in YYYY-MM-DD format.
+    Date,
+    Choice,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub choices: Vec<String>,
+    pub default: Option<String>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    #[serde(default)]
+    pub help: Vec<String>,
+    /// Validation rules in addition to the ones implied by the type.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Field {
+    /// Allowed values, including every number of the integer range.
+    pub fn choices(&self) -> Vec<String> {
+        match (self.kind, self.min, self.max) {
+            (FieldType::Integer, Some(min), Some(max)) if self.choices.is_empty() => {
+                (min..=max).map(|n| n.to_string()).collect()
+            }
+            _ => self.choices.clone(),
+        }
+    }
+
+    pub fn rules(&self) -> Vec<Rule> {
+        let mut rules = Vec::new();
+        if self.required {
+            rules.push(Rule::Required);
+        }
+        match self.kind {
+            FieldType::Integer => rules.push(Rule::Range {
+                min: self.min.unwrap_or(i64::MIN),
+                max: self.max.unwrap_or(i64::MAX),
+            }),
+            FieldType::Date => rules.push(Rule::Date),
+            _ => {}
+        }
+        if self.kind != FieldType::Integer && !self.choices.is_empty() {
+            rules.push(Rule::OneOf {
+                values: self.choices.clone(),
+            });
+        }
+        rules.extend(self.rules.iter().cloned());
+        rules
+    }
+}
+
+/// Help text of the fields by their names.
+pub fn comments(fields: &[Field]) -> HashMap<String, Vec<String>> {
+    fields
+        .iter()
+        .filter(|f| !f.help.is_empty())
+        .map(|f| (f.name.clone(), f.help.clone()))
+        .collect()
+}
+
+/// Allowed values of the fields that have them.
+pub fn choices(fields: &[Field]) -> HashMap<String, Vec<String>> {
+    fields
+        .iter()
+        .map(|f| (f.name.clone(), f.choices()))
+        .filter(|(_, choices)| !choices.is_empty())
+        .collect()
+}
+
+pub fn rules(fields: &[Field]) -> Rules {
+    fields
+        .iter()
+        .map(|f| (f.name.clone(), f.rules()))
+        .filter(|(_, rules)| !rules.is_empty())
+        .collect()
+}
+
+/// Default values of the fields that have them.
+pub fn defaults(fields: &[Field]) -> Map<String, Value> {
+    fields
+        .iter()
+        .filter_map(|f| Some((f.name.clone(), Value::from(f.default.clone()?))))
+        .collect()
+}
+
+pub fn names(fields: &[Field]) -> Vec<&str> {
+    fields.iter().map(|f| f.name.as_str()).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    pub dataset: Vec<Field>,
+    pub resource: Vec<Field>,
+}
+
+impl Schema {
+    pub fn parse(content: &str) -> crate::Result<Self> {
+        toml::from_str(content).map_err(|err| FdpError::Plain(format!("Invalid schema: {}", err)))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let content = std::fs::read_to_string(&path)?;
+        Self::parse(&content)
+            .map_err(|err| FdpError::Plain(format!("{} in {}", err, path.as_ref().display())))
+    }
+
+    /// Schema shipped with the crate.
+    pub fn embedded() -> Arc<Self> {
+        DEFAULT
+            .get_or_init(|| Arc::new(Self::parse(EMBEDDED).expect("embedded schema is valid")))
+            .clone()
+    }
+}
+
+/// Schema in use: the one installed from the profile, or the embedded one.
+pub fn current() -> Arc<Schema> {
+    match CURRENT.read().unwrap().as_ref() {
+        Some(schema) => schema.clone(),
+        None => Schema::embedded(),
+    }
+}
+
+/// Replace the schema in use. `None` restores the embedded schema.
+pub fn install(schema: Option<Schema>) {
+    *CURRENT.write().unwrap() = schema.map(Arc::new);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_embedded_schema() {
+        let schema = Schema::embedded();
+        assert_eq!("dataset_type", schema.dataset[0].name);
+        let choices = choices(&schema.dataset);
+        assert_eq!(18, choices["dataset_type"].len());
+        assert_eq!(vec!["yes", "no"], choices["spatial_data"]);
+        assert!(comments(&schema.resource)["format_label"].len() > 1);
+        assert_eq!(
+            json!({
+                "access_level": "open",
+                "dataset_status": "draft",
+                "language": "en",
+                "theme": "Emergency Management",
+                "update_freq": "daily",
+            }),
+            Value::Object(defaults(&schema.dataset))
+        );
+        assert_eq!(
+            vec![
+                Rule::Required,
+                Rule::Length { min: 2, max: 100 },
+                Rule::Slug
+            ],
+            rules(&schema.dataset)["name"]
+        );
+        assert_eq!(
+            vec![Rule::Range { min: 1, max: 18 }],
+            rules(&schema.dataset)["dataset_type"]
+        );
+    }
+
+    #[test]
+    fn test_load_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schema.toml");
+        std::fs::write(
+            &path,
+            "[[dataset]]\nname = \"code\"\ntype = \"choice\"\nchoices = [\"a\"]\n\
+             [[resource]]\nname = \"name\"\nrequired = true\n",
+        )
+        .unwrap();
+
+        let schema = Schema::load(&path).unwrap();
+        assert_eq!(vec!["code"], names(&schema.dataset));
+        assert_eq!(
+            vec![Rule::OneOf {
+                values: vec!["a".into()]
+            }],
+            rules(&schema.dataset)["code"]
+        );
+        assert_eq!(vec![Rule::Required], rules(&schema.resource)["name"]);
+
+        std::fs::write(&path, "[[dataset]]\nname = \"code\"\nkind = \"text\"\n").unwrap();
+        assert!(matches!(Schema::load(&path), Err(FdpError::Plain(_))));
+    }
+}
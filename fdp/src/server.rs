@@ -0,0 +1,805 @@
+//! Local stand-in for the portal with the `nswflood` extension.
+//!
+//! [`TestServer`] listens on a random local port and implements the actions
+//! used by [`ExtensionClient`], so the real client, multipart encoding
+//! and offsets of the upload parts are exercised without the portal. Parts
+//! are assembled in memory and can be compared with the original file. Core
+//! actions used by [`StockClient`](crate::stock::StockClient) are
+//! implemented as well.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use ckanapi::CKAN;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Request, Response, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+
+use crate::action::ExtensionClient;
+use crate::checksum::Checksum;
+use crate::chunk;
+
+/// Token accepted by the server.
+pub const TOKEN: &str = "test-token";
+
+#[derive(Debug, Default)]
+struct Upload {
+    size: u64,
+    chunk_size: u64,
+    parts: BTreeMap<u64, Vec<u8>>,
+    sha256: Option<String>,
+    completed: bool,
+}
+
+impl Upload {
+    fn bytes_uploaded(&self) -> u64 {
+        self.parts.values().map(|p| p.len() as u64).sum()
+    }
+
+    fn content(&self) -> Vec<u8> {
+        self.parts.values().flatten().copied().collect()
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    projects: Vec<Value>,
+    project: Option<String>,
+    finalized: bool,
+    max_chunk_size: Option<u64>,
+    validated: BTreeMap<(String, String), Value>,
+    uploads: BTreeMap<(String, String), Upload>,
+    failures: HashSet<(String, String, u64)>,
+    corruptions: HashSet<(String, String, u64)>,
+    received: Vec<(String, String, u64)>,
+    packages: Vec<Value>,
+    /// Content of the resources uploaded via core API, by resource ID.
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            projects: vec![
+                json!({"id": "1", "name": "river-study", "title": "River study"}),
+                json!({"id": "2", "name": "coastal-study", "title": "Coastal study"}),
+            ],
+            project: None,
+            finalized: false,
+            max_chunk_size: None,
+            validated: BTreeMap::new(),
+            uploads: BTreeMap::new(),
+            failures: HashSet::new(),
+            corruptions: HashSet::new(),
+            received: Vec::new(),
+            packages: Vec::new(),
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+/// Error of the action, reported in the CKAN format.
+struct Fail(StatusCode, Value);
+
+impl Fail {
+    fn not_found(message: &str) -> Self {
+        Self(
+            StatusCode::NOT_FOUND,
+            json!({"__type": "Not Found Error", "message": message}),
+        )
+    }
+
+    fn validation(field: &str, message: &str) -> Self {
+        Self(
+            StatusCode::CONFLICT,
+            json!({"__type": "Validation Error", field: [message]}),
+        )
+    }
+}
+
+type ActionResult = Result<Value, Fail>;
+
+/// Field of the multipart form. Files are kept as raw bytes.
+#[derive(Debug, Default)]
+struct Part {
+    filename: Option<String>,
+    content: Vec<u8>,
+}
+
+fn parse_multipart(body: &[u8], boundary: &str) -> HashMap<String, Part> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut fields = HashMap::new();
+
+    let mut sections = Vec::new();
+    let mut rest = body;
+    while let Some(pos) = find(rest, &delimiter) {
+        sections.push(&rest[..pos]);
+        rest = &rest[pos + delimiter.len()..];
+    }
+
+    for section in sections.into_iter().skip(1) {
+        let section = section.strip_prefix(b"\r\n").unwrap_or(section);
+        let section = section.strip_suffix(b"\r\n").unwrap_or(section);
+        let Some(split) = find(section, b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&section[..split]);
+        let param = |key: &str| {
+            headers
+                .split([';', '\n'])
+                .map(str::trim)
+                .find_map(|p| p.strip_prefix(key))
+                .map(|v| v.trim_matches('"').to_string())
+        };
+        if let Some(name) = param("name=") {
+            let part = Part {
+                filename: param("filename="),
+                content: section[split + 4..].to_vec(),
+            };
+            fields.insert(name, part);
+        }
+    }
+    fields
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn text(fields: &HashMap<String, Part>, name: &str) -> String {
+    fields
+        .get(name)
+        .map(|p| String::from_utf8_lossy(&p.content).into_owned())
+        .unwrap_or_default()
+}
+
+fn key(data: &Value) -> (String, String) {
+    (
+        data["dataset"].as_str().unwrap_or_default().to_string(),
+        data["name"].as_str().unwrap_or_default().to_string(),
+    )
+}
+
+/// Empty values and missing title are reported as errors.
+fn validate(data: &Value) -> Value {
+    let mut errors = serde_json::Map::new();
+    if let Value::Object(fields) = data {
+        for (field, value) in fields {
+            if value == "" {
+                errors.insert(field.clone(), json!(["Missing value"]));
+            }
+        }
+    }
+    Value::Object(errors)
+}
+
+impl State {
+    /// Index of the package with the given ID or name.
+    fn package(&self, id: &Value) -> Option<usize> {
+        self.packages
+            .iter()
+            .position(|p| !id.is_null() && (p["id"] == *id || p["name"] == *id))
+    }
+
+    fn flake(&self, key: &(String, String)) -> Value {
+        match self.uploads.get(key) {
+            Some(upload) => json!({
+                "id": format!("upload:{}:{}", key.0, key.1),
+                "data": {
+                    "key": format!("{}/{}", key.0, key.1),
+                    "size": upload.size,
+                    "dataset": &key.0,
+                    "name": &key.1,
+                    "completed": upload.completed,
+                    "bytes_uploaded": upload.bytes_uploaded(),
+                    "chunk_size": upload.chunk_size,
+                    "sha256": upload.sha256,
+                },
+                "extras": {"type": "client-upload", "dataset": &key.0, "resource": &key.1},
+            }),
+            None => Value::Null,
+        }
+    }
+
+    fn action(&mut self, name: &str, data: Value, files: HashMap<String, Part>) -> ActionResult {
+        match name {
+            "nswflood_me" => Ok(json!({"id": "test-user", "display_name": "Test user"})),
+
+            "nswflood_available_project_list" => {
+                let q = data["name"].as_str().unwrap_or_default().to_lowercase();
+                let rows = data["rows"].as_u64().unwrap_or(10) as usize;
+                let results: Vec<&Value> = self
+                    .projects
+                    .iter()
+                    .filter(|p| {
+                        p["name"].as_str().unwrap_or_default().contains(&q)
+                            || p["title"]
+                                .as_str()
+                                .unwrap_or_default()
+                                .to_lowercase()
+                                .contains(&q)
+                    })
+                    .collect();
+                Ok(json!({"count": results.len(), "results": &results[..results.len().min(rows)]}))
+            }
+
+            "nswflood_submission_project_set" => match data["id"].as_str() {
+                Some(id) if !self.projects.iter().any(|p| p["id"] == id) => {
+                    Err(Fail::not_found("Project not found"))
+                }
+                id => {
+                    self.project = id.map(String::from);
+                    Ok(json!({ "id": id }))
+                }
+            },
+
+            "nswflood_submission_details" => {
+                let uploads = self.uploads.keys().map(|k| self.flake(k));
+                Ok(Value::from(
+                    self.validated
+                        .values()
+                        .cloned()
+                        .chain(uploads)
+                        .collect::<Vec<_>>(),
+                ))
+            }
+
+            "nswflood_submission_validate_dataset" => {
+                let name = data["name"].as_str().unwrap_or_default().to_string();
+                let errors = validate(&data["data"]);
+                self.validated.insert(
+                    (name.clone(), String::new()),
+                    json!({"extras": {"type": "validated-dataset", "dataset": name, "errors": errors}}),
+                );
+                Ok(json!({"data": data["data"], "errors": errors}))
+            }
+
+            "nswflood_submission_validate_resource" => {
+                let (dataset, name) = key(&data);
+                let errors = validate(&data["data"]);
+                self.validated.insert(
+                    (dataset.clone(), name.clone()),
+                    json!({"extras": {
+                        "type": "validated-resource",
+                        "dataset": dataset,
+                        "resource": name,
+                        "errors": errors
+                    }}),
+                );
+                Ok(json!({"data": data["data"], "errors": errors}))
+            }
+
+            "nswflood_submission_finalize" => {
+                if self.project.is_none() {
+                    return Err(Fail::validation("project", "Project is not selected"));
+                }
+                self.finalized = true;
+                Ok(Value::Null)
+            }
+
+            "nswflood_upload_show" => Ok(self.flake(&key(&data))),
+
+            "nswflood_upload_register" => {
+                let requested = data["chunk_size"]
+                    .as_u64()
+                    .unwrap_or(chunk::DEFAULT_CHUNK_SIZE);
+                let chunk_size = match self.max_chunk_size {
+                    Some(max) => requested.min(max),
+                    None => requested,
+                };
+                let key = key(&data);
+                self.uploads.insert(
+                    key.clone(),
+                    Upload {
+                        size: data["size"].as_u64().unwrap_or_default(),
+                        chunk_size,
+                        ..Default::default()
+                    },
+                );
+                let mut flake = self.flake(&key);
+                flake["chunk_size"] = json!(chunk_size);
+                Ok(flake)
+            }
+
+            "nswflood_upload_progress" => {
+                let key = (text(&files, "dataset"), text(&files, "name"));
+                let part: u64 = text(&files, "part_number")
+                    .parse()
+                    .map_err(|_| Fail::validation("part_number", "Must be an integer"))?;
+                let content = files
+                    .get("content")
+                    .map(|p| p.content.clone())
+                    .unwrap_or_default();
+                let upload = self
+                    .uploads
+                    .get_mut(&key)
+                    .ok_or_else(|| Fail::not_found("Upload not found"))?;
+
+                let expected = chunk::part_size(part, upload.chunk_size, upload.size);
+                if part == 0 || (expected == 0 && part > 1) {
+                    return Err(Fail::validation("part_number", "Part is out of range"));
+                }
+                if content.len() as u64 != expected
+                    || text(&files, "size") != content.len().to_string()
+                {
+                    return Err(Fail::validation("size", "Part has unexpected size"));
+                }
+
+                let mut checksum = Checksum::of(&content, false);
+                let entry = (key.0.clone(), key.1.clone(), part);
+                if self.corruptions.remove(&entry) {
+                    checksum = Checksum::of(b"corrupted", false);
+                }
+                upload.parts.insert(part, content);
+                upload.sha256 = Some(checksum.sha256);
+                self.received.push(entry);
+                Ok(self.flake(&key))
+            }
+
+            "nswflood_upload_complete" => {
+                let key = key(&data);
+                let upload = self
+                    .uploads
+                    .get_mut(&key)
+                    .ok_or_else(|| Fail::not_found("Upload not found"))?;
+                if upload.bytes_uploaded() != upload.size {
+                    return Err(Fail::validation("parts", "Not all parts are uploaded"));
+                }
+                upload.sha256 = Some(Checksum::of(&upload.content(), false).sha256);
+                upload.completed = true;
+                Ok(self.flake(&key))
+            }
+
+            "user_show" => match data["id"].as_str() {
+                Some("test-user") => Ok(json!({
+                    "id": "test-user",
+                    "name": "test-user",
+                    "display_name": "Test user"
+                })),
+                _ => Err(Fail::not_found("User not found")),
+            },
+
+            "organization_list_for_user" => Ok(Value::from(self.projects.clone())),
+
+            "package_show" => self
+                .package(&data["id"])
+                .map(|idx| self.packages[idx].clone())
+                .ok_or_else(|| Fail::not_found("Not found")),
+
+            "package_create" => {
+                let name = data["name"].as_str().unwrap_or_default();
+                if name.len() < 2 {
+                    return Err(Fail::validation("name", "Missing value"));
+                }
+                if self.package(&data["name"]).is_some() {
+                    return Err(Fail::validation("name", "That URL is already in use."));
+                }
+                if !self
+                    .projects
+                    .iter()
+                    .any(|p| p["id"] == data["owner_org"] || p["name"] == data["owner_org"])
+                {
+                    return Err(Fail::validation("owner_org", "Organization does not exist"));
+                }
+                let mut package = data;
+                package["id"] = json!(format!("package-{}", self.packages.len() + 1));
+                package["resources"] = json!([]);
+                self.packages.push(package.clone());
+                Ok(package)
+            }
+
+            // Like the real portal, drops resources that are not listed.
+            "package_update" => {
+                let idx = self
+                    .package(&data["id"])
+                    .ok_or_else(|| Fail::not_found("Package was not found"))?;
+                let mut package = data;
+                package["id"] = self.packages[idx]["id"].clone();
+                if package.get("resources").is_none() {
+                    package["resources"] = json!([]);
+                }
+                self.packages[idx] = package.clone();
+                Ok(package)
+            }
+
+            "resource_create" | "resource_update" => {
+                let mut resource = serde_json::Map::new();
+                for (field, part) in &files {
+                    if part.filename.is_none() {
+                        resource.insert(field.clone(), json!(text(&files, field)));
+                    }
+                }
+                let upload = files
+                    .get("upload")
+                    .ok_or_else(|| Fail::validation("upload", "Missing value"))?;
+
+                let (idx, pos) = if name == "resource_update" {
+                    let id = text(&files, "id");
+                    self.packages
+                        .iter()
+                        .enumerate()
+                        .find_map(|(idx, p)| {
+                            p["resources"]
+                                .as_array()?
+                                .iter()
+                                .position(|r| r["id"] == id.as_str())
+                                .map(|pos| (idx, Some(pos)))
+                        })
+                        .ok_or_else(|| Fail::not_found("Resource was not found"))?
+                } else {
+                    let idx = self
+                        .package(&json!(text(&files, "package_id")))
+                        .ok_or_else(|| Fail::not_found("Package was not found"))?;
+                    resource.insert(
+                        "id".into(),
+                        json!(format!("resource-{}", self.files.len() + 1)),
+                    );
+                    (idx, None)
+                };
+
+                let id = resource["id"].as_str().unwrap_or_default().to_string();
+                resource.insert(
+                    "url".into(),
+                    json!(format!(
+                        "http://localhost/dataset/{}/resource/{}/download/{}",
+                        self.packages[idx]["id"].as_str().unwrap_or_default(),
+                        id,
+                        upload.filename.as_deref().unwrap_or_default()
+                    )),
+                );
+                resource.insert("size".into(), json!(upload.content.len()));
+                self.files.insert(id, upload.content.clone());
+
+                let resource = Value::Object(resource);
+                let resources = self.packages[idx]["resources"].as_array_mut().unwrap();
+                match pos {
+                    Some(pos) => resources[pos] = resource.clone(),
+                    None => resources.push(resource.clone()),
+                }
+                Ok(resource)
+            }
+
+            _ => Err(Fail(
+                StatusCode::BAD_REQUEST,
+                json!({"__type": "Bad request", "message": format!("Action name not known: {}", name)}),
+            )),
+        }
+    }
+}
+
+fn reply(status: StatusCode, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+async fn handle(state: Arc<Mutex<State>>, req: Request<Body>) -> Response<Body> {
+    let action = req
+        .uri()
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let help = format!("http://localhost/api/3/action/help_show?name={}", action);
+
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    if token != Some(TOKEN) {
+        return reply(
+            StatusCode::FORBIDDEN,
+            json!({"help": help, "success": false, "error": {
+                "__type": "Authorization Error",
+                "message": "Access denied"
+            }}),
+        );
+    }
+
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(err) => return reply(StatusCode::BAD_REQUEST, json!(err.to_string())),
+    };
+
+    let (data, files) = match content_type.split_once("boundary=") {
+        Some((_, boundary)) => (
+            Value::Null,
+            parse_multipart(&body, boundary.trim_matches('"')),
+        ),
+        None => (
+            serde_json::from_slice(&body).unwrap_or(Value::Null),
+            HashMap::new(),
+        ),
+    };
+
+    if action == "nswflood_upload_progress" {
+        let entry = (
+            text(&files, "dataset"),
+            text(&files, "name"),
+            text(&files, "part_number").parse().unwrap_or_default(),
+        );
+        if state.lock().unwrap().failures.remove(&entry) {
+            return Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .body(Body::from("Bad gateway"))
+                .unwrap();
+        }
+    }
+
+    let result = state.lock().unwrap().action(&action, data, files);
+    match result {
+        Ok(result) => reply(
+            StatusCode::OK,
+            json!({"help": help, "success": true, "result": result}),
+        ),
+        Err(Fail(status, error)) => reply(
+            status,
+            json!({"help": help, "success": false, "error": error}),
+        ),
+    }
+}
+
+/// Server running in the background until it's dropped.
+pub struct TestServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl TestServer {
+    /// Start the server on a random port. Must be called inside the tokio
+    /// runtime.
+    pub fn start() -> std::io::Result<Self> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = shared.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(state, req).await) }
+                }))
+            }
+        });
+
+        let (tx, rx) = oneshot::channel();
+        let server = hyper::Server::from_tcp(listener)
+            .map_err(|err| std::io::Error::other(err.to_string()))?
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                rx.await.ok();
+            });
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                log::error!("Test server failed: {}", err);
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(tx),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// CKAN client authorized with [`TOKEN`].
+    pub fn ckan(&self) -> CKAN {
+        let mut ckan = CKAN::from(&self.url());
+        ckan.login(TOKEN);
+        ckan
+    }
+
+    /// Client of the extension authorized with [`TOKEN`].
+    pub fn client(&self) -> ExtensionClient {
+        self.ckan().into()
+    }
+
+    /// Limit chunk size accepted at registration.
+    pub fn max_chunk_size(&self, size: u64) {
+        self.state.lock().unwrap().max_chunk_size = Some(size);
+    }
+
+    /// Respond to the next attempt to send the part with a gateway error.
+    pub fn fail_part(&self, dataset: &str, name: &str, part: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .insert((dataset.into(), name.into(), part));
+    }
+
+    /// Report wrong checksum for the next attempt to send the part.
+    pub fn corrupt_part(&self, dataset: &str, name: &str, part: u64) {
+        self.state
+            .lock()
+            .unwrap()
+            .corruptions
+            .insert((dataset.into(), name.into(), part));
+    }
+
+    /// Parts received for the resource, in order of arrival.
+    pub fn parts(&self, dataset: &str, name: &str) -> Vec<u64> {
+        self.state
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|(d, n, _)| d == dataset && n == name)
+            .map(|(_, _, p)| *p)
+            .collect()
+    }
+
+    /// Content assembled from the received parts.
+    pub fn content(&self, dataset: &str, name: &str) -> Option<Vec<u8>> {
+        self.state
+            .lock()
+            .unwrap()
+            .uploads
+            .get(&(dataset.into(), name.into()))
+            .map(Upload::content)
+    }
+
+    pub fn project(&self) -> Option<String> {
+        self.state.lock().unwrap().project.clone()
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.state.lock().unwrap().finalized
+    }
+
+    /// Package created via core API.
+    pub fn package(&self, name: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        state
+            .package(&json!(name))
+            .map(|idx| state.packages[idx].clone())
+    }
+
+    /// Content of the resource uploaded via core API.
+    pub fn file(&self, resource_id: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().files.get(resource_id).cloned()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::FdpClient;
+    use crate::testing::source;
+    use crate::upload::{RetryPolicy, Uploader};
+    use crate::FdpError;
+    use std::time::Duration;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn test_parse_multipart() {
+        let body = b"--xyz\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nfile\r\n\
+            --xyz\r\nContent-Disposition: form-data; name=\"content\"; filename=\"upload\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n\r\nbin\r\n--xyz--\r\n";
+        let fields = parse_multipart(body, "xyz");
+        assert_eq!(b"file".to_vec(), fields["name"].content);
+        assert_eq!(None, fields["name"].filename);
+        assert_eq!(b"\r\nbin".to_vec(), fields["content"].content);
+        assert_eq!(Some("upload".into()), fields["content"].filename);
+    }
+
+    #[tokio::test]
+    async fn test_user_and_projects() {
+        let server = TestServer::start().unwrap();
+        let client = server.client();
+
+        assert_eq!("test-user", client.user_info().await.unwrap().id);
+        let projects = client.available_projects("river").await.unwrap();
+        assert_eq!(1, projects.len());
+
+        assert!(matches!(
+            client.submission_finalize().await,
+            Err(FdpError::Validation(_))
+        ));
+        client.project_set(Some("1")).await.unwrap();
+        assert_eq!(Some("1".into()), server.project());
+        client.submission_finalize().await.unwrap();
+        assert!(server.is_finalized());
+
+        assert!(matches!(
+            client.project_set(Some("404")).await,
+            Err(FdpError::NotFound(_))
+        ));
+
+        let mut anonymous = CKAN::from(&server.url());
+        anonymous.login("wrong");
+        assert!(matches!(
+            ExtensionClient::from(anonymous).user_info().await,
+            Err(FdpError::Auth(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_upload_end_to_end() {
+        let content: Vec<u8> = (0..100u8).collect();
+        let dir = source(&[]);
+        std::fs::write(dir.path().join("dataset").join("file.bin"), &content).unwrap();
+        let path = dir.path().to_str().unwrap();
+        let server = TestServer::start().unwrap();
+        server.max_chunk_size(30);
+        server.fail_part("dataset", "file.bin", 2);
+        server.corrupt_part("dataset", "file.bin", 3);
+        let client = server.client();
+
+        let upload = Uploader::new(&client, path)
+            .chunk_size(64)
+            .retry(fast())
+            .upload("dataset", "file.bin")
+            .await
+            .unwrap();
+
+        assert!(upload.data.completed);
+        assert_eq!(Some(30), upload.data.chunk_size);
+        let mut parts = server.parts("dataset", "file.bin");
+        parts.sort();
+        assert_eq!(vec![1, 2, 3, 3, 4], parts);
+        assert_eq!(Some(content), server.content("dataset", "file.bin"));
+
+        let submission = client.show_submission().await.unwrap();
+        assert_eq!(json!("client-upload"), submission[0]["extras"]["type"]);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_upload_is_not_retried() {
+        let dir = source(&[("file.bin", 10)]);
+        let path = dir.path().to_str().unwrap();
+        let server = TestServer::start().unwrap();
+        let client = server.client();
+
+        client
+            .register_upload(path, "dataset", "file.bin", 10)
+            .await
+            .unwrap();
+        client
+            .upload_part(path, "dataset", "file.bin", 1, 10)
+            .await
+            .unwrap();
+        std::fs::write(dir.path().join("dataset").join("file.bin"), [2; 10]).unwrap();
+
+        let err = Uploader::new(&client, path)
+            .retry(fast())
+            .upload("dataset", "file.bin")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, FdpError::CorruptedUpload(_)));
+        assert!(!err.is_transient());
+    }
+}
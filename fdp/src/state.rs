@@ -1,31 +1,173 @@
-use crate::{action::FdpClient, types::Portal};
+use crate::action::{ExtensionClient, FdpClient};
+use crate::profile::{Backend, Profile};
+use crate::schema::{self, Schema};
+use crate::stock::StockClient;
+use crate::{
+    types::{Portal, User},
+    upload::UploadControl,
+};
 
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "mock")]
+use crate::mock::MockFdpClient;
+
+/// URL of the portal that is served by [`MockFdpClient`] instead of the
+/// real server.
+#[cfg(feature = "mock")]
+pub const MOCK_URL: &str = "mock://portal";
 
 #[derive(Default)]
-pub struct PortalState(Mutex<Portal>);
+pub struct PortalState {
+    portal: Mutex<Portal>,
+    profile: Mutex<Arc<Profile>>,
+    /// Client of the stock portal, which keeps the state of the submission.
+    stock: Mutex<Option<Arc<StockClient>>>,
+    #[cfg(feature = "mock")]
+    mock: Arc<MockFdpClient>,
+}
 
 impl PortalState {
-    pub fn replace(&self, portal: Portal) {
-        *self.0.lock().unwrap() = portal;
+    /// Switch to another portal and check the token. Metadata schema of the
+    /// profile is used only after the successful login.
+    pub async fn login(&self, portal: Portal) -> crate::Result<User> {
+        let schema = self.replace(portal)?;
+        let user = self.client()?.user_info().await?;
+        schema::install(schema);
+        Ok(user)
+    }
+
+    /// Switch to another portal, loading its profile. Metadata schema of the
+    /// profile is returned rather than installed.
+    fn replace(&self, portal: Portal) -> crate::Result<Option<Schema>> {
+        let profile = match portal.profile {
+            Some(ref path) => Profile::load(path)?,
+            None => Profile::default(),
+        };
+        let schema = match profile.schema {
+            Some(ref path) => Some(Schema::load(path)?),
+            None => None,
+        };
+        *self.profile.lock().unwrap() = Arc::new(profile);
+        *self.stock.lock().unwrap() = None;
+        *self.portal.lock().unwrap() = portal;
+        Ok(schema)
     }
 
     fn clone(&self) -> Portal {
-        let portal = self.0.lock().unwrap();
+        let portal = self.portal.lock().unwrap();
         portal.clone()
     }
 
-    pub fn client(&self) -> crate::Result<impl FdpClient> {
+    pub fn client(&self) -> crate::Result<Arc<dyn FdpClient>> {
         match self.clone() {
+            #[cfg(feature = "mock")]
+            Portal {
+                url: Some(ref url), ..
+            } if url == MOCK_URL => Ok(self.mock.clone()),
             Portal {
                 token: Some(ref token),
                 url: Some(ref url),
+                ref user,
+                ..
             } => {
                 let mut client = ckanapi::CKAN::from(url);
                 client.login(token);
-                Ok(client)
+                let profile = self.profile.lock().unwrap().clone();
+                Ok(match profile.backend {
+                    Backend::Extension => Arc::new(ExtensionClient::new(client, profile)),
+                    Backend::Stock => self
+                        .stock
+                        .lock()
+                        .unwrap()
+                        .get_or_insert_with(|| {
+                            let stock = StockClient::new(client);
+                            Arc::new(match user {
+                                Some(user) => stock.user(user),
+                                None => stock,
+                            })
+                        })
+                        .clone(),
+                })
             }
             _ => Err("URL and token must be defined".into()),
         }
     }
+
+    /// Chunk size configured for the portal.
+    pub fn chunk_size(&self) -> u64 {
+        self.portal
+            .lock()
+            .unwrap()
+            .chunk_size
+            .unwrap_or(crate::chunk::DEFAULT_CHUNK_SIZE)
+    }
+}
+
+/// Controls of running uploads, keyed by `dataset/name`.
+#[derive(Default)]
+pub struct UploadControls(Mutex<HashMap<String, UploadControl>>);
+
+impl UploadControls {
+    fn key(dataset: &str, name: &str) -> String {
+        format!("{}/{}", dataset, name)
+    }
+
+    /// Register the new upload, replacing the control of the previous one.
+    pub fn start(&self, dataset: &str, name: &str) -> UploadControl {
+        let control = UploadControl::default();
+        self.0
+            .lock()
+            .unwrap()
+            .insert(Self::key(dataset, name), control.clone());
+        control
+    }
+
+    pub fn finish(&self, dataset: &str, name: &str) {
+        self.0.lock().unwrap().remove(&Self::key(dataset, name));
+    }
+
+    pub fn get(&self, dataset: &str, name: &str) -> crate::Result<UploadControl> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&Self::key(dataset, name))
+            .cloned()
+            .ok_or_else(|| crate::FdpError::NotFound(format!("Upload of {}/{}", dataset, name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::TestServer;
+
+    #[tokio::test]
+    async fn test_schema_is_installed_after_login() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("profile.toml"),
+            "schema = \"schema.toml\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("schema.toml"),
+            "[[dataset]]\nname = \"title\"\n[[resource]]\nname = \"name\"\n",
+        )
+        .unwrap();
+        let server = TestServer::start().unwrap();
+        let state = PortalState::default();
+
+        let result = state
+            .login(Portal {
+                url: Some(server.url()),
+                token: Some("wrong".into()),
+                profile: Some(dir.path().join("profile.toml").to_string_lossy().into()),
+                ..Default::default()
+            })
+            .await;
+        assert!(matches!(result, Err(crate::FdpError::Auth(_))));
+        assert_eq!(Schema::embedded(), schema::current());
+    }
 }
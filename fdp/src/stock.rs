@@ -0,0 +1,502 @@
+//! Backend for CKAN portals without the submission extension.
+//!
+//! [`StockClient`] publishes the source with the standard actions. Every
+//! dataset becomes a package of the selected organization, created or updated
+//! from its metadata merged with the root metadata. Every resource is sent
+//! with `resource_create`, or `resource_update` if the package already has
+//! the resource with the same name. Stock portal cannot receive the file in
+//! parts, so the upload is registered with a single chunk covering the whole
+//! file. Validations and uploads of the submission are tracked by the client.
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use ckanapi::{Params, CKAN};
+use serde_json::{json, Map, Value};
+
+use crate::action::{find_resource, FdpClient};
+use crate::types::{
+    Metadata, ProgressedUpload, Project, RegisteredUpload, UploadData, User, ValidationResult,
+};
+use crate::{read_source_path, FdpError};
+
+type Key = (String, String);
+
+#[derive(Debug, Default)]
+struct State {
+    organization: Option<String>,
+    validated: BTreeMap<Key, Value>,
+    uploads: BTreeMap<Key, UploadData>,
+    /// IDs of the packages created for datasets.
+    packages: BTreeMap<String, String>,
+}
+
+/// Client of the portal that provides only the core CKAN API.
+pub struct StockClient {
+    ckan: CKAN,
+    user: Option<String>,
+    state: Mutex<State>,
+}
+
+/// Name of the package. CKAN accepts only lowercase alphanumeric characters,
+/// `-` and `_`.
+pub fn package_name(dataset: &str) -> String {
+    let name: String = dataset
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' | '_' => c,
+            _ => '-',
+        })
+        .collect();
+    name.trim_matches('-').to_string()
+}
+
+/// Non-empty string fields of the metadata.
+fn fields(metadata: &Metadata) -> Map<String, Value> {
+    match metadata {
+        Metadata::Object(Value::Object(fields)) => fields
+            .iter()
+            .filter(|(_, v)| !matches!(v, Value::String(s) if s.is_empty()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        _ => Map::new(),
+    }
+}
+
+impl StockClient {
+    pub fn new(ckan: CKAN) -> Self {
+        Self {
+            ckan,
+            user: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Name of the account. Stock portal cannot tell the user by the token.
+    pub fn user<T: Into<String>>(mut self, user: T) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Package built from the metadata of the dataset, merged with the root
+    /// metadata.
+    fn package(&self, path: &OsStr, dataset: &str) -> crate::Result<Value> {
+        let source = read_source_path(path)?;
+        let dataset = source
+            .get_dataset(dataset)
+            .ok_or_else(|| FdpError::NotFound("Dataset not found".into()))?;
+
+        let mut package = fields(&source.metadata);
+        package.append(&mut fields(&dataset.metadata));
+        package
+            .entry("name")
+            .or_insert_with(|| package_name(&dataset.name).into());
+        package
+            .entry("title")
+            .or_insert_with(|| dataset.name.clone().into());
+        if let Some(org) = &self.state.lock().unwrap().organization {
+            package.insert("owner_org".into(), org.clone().into());
+        }
+        Ok(Value::Object(package))
+    }
+
+    /// Create the package or update the existing one. Returns the package
+    /// with its resources.
+    async fn publish(&self, path: &OsStr, dataset: &str) -> crate::Result<Value> {
+        let package = self.package(path, dataset)?;
+        let existing = self
+            .ckan
+            .build("package_show")
+            .params(Params::Json(json!({"id": &package["name"]})))
+            .send::<Value>()
+            .await?
+            .extract();
+
+        let published: Value = match existing {
+            Ok(mut existing) => {
+                if let (Value::Object(existing), Value::Object(mut package)) =
+                    (&mut existing, package)
+                {
+                    existing.append(&mut package);
+                }
+                self.ckan
+                    .build("package_update")
+                    .params(Params::Json(existing))
+                    .send()
+                    .await?
+                    .extract()?
+            }
+            Err(ckanapi::CKANError::NotFound(_)) => self
+                .ckan
+                .build("package_create")
+                .params(Params::Json(package))
+                .send()
+                .await?
+                .extract()?,
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(id) = published["id"].as_str() {
+            self.state
+                .lock()
+                .unwrap()
+                .packages
+                .insert(dataset.into(), id.into());
+        }
+        Ok(published)
+    }
+
+    fn validated(&self, key: Key, kind: &str, result: ValidationResult) -> ValidationResult {
+        let mut extras = json!({"type": kind, "dataset": &key.0, "errors": &result.errors});
+        if kind == "validated-resource" {
+            extras["resource"] = json!(&key.1);
+        }
+        self.state
+            .lock()
+            .unwrap()
+            .validated
+            .insert(key, json!({ "extras": extras }));
+        result
+    }
+
+    fn flake(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        let state = self.state.lock().unwrap();
+        match state.uploads.get(&(dataset.into(), name.into())) {
+            Some(data) => Ok(ProgressedUpload {
+                id: format!("upload:{}:{}", dataset, name),
+                data: data.clone(),
+            }),
+            None => Err(FdpError::NotFound("Upload has not started yet".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl FdpClient for StockClient {
+    /// Packages are published during the upload, so there is nothing left to
+    /// do.
+    async fn submission_finalize(&self) -> crate::Result<()> {
+        match self.state.lock().unwrap().organization {
+            Some(_) => Ok(()),
+            None => Err(FdpError::Validation(
+                json!({"owner_org": ["Organization is not selected"]}),
+            )),
+        }
+    }
+
+    async fn user_info(&self) -> crate::Result<User> {
+        let user = self.user.as_ref().ok_or_else(|| {
+            FdpError::Auth("User name is required by the portal without extension".into())
+        })?;
+        Ok(self
+            .ckan
+            .build("user_show")
+            .params(Params::Json(json!({ "id": user })))
+            .send()
+            .await?
+            .extract()?)
+    }
+
+    /// Organizations where the user can create datasets.
+    async fn available_projects(&self, name: &str) -> crate::Result<Vec<Project>> {
+        let organizations: Vec<Project> = self
+            .ckan
+            .build("organization_list_for_user")
+            .params(Params::Json(json!({"permission": "create_dataset"})))
+            .send()
+            .await?
+            .extract()?;
+
+        let name = name.to_lowercase();
+        Ok(organizations
+            .into_iter()
+            .filter(|p| {
+                p.name.to_lowercase().contains(&name) || p.title.to_lowercase().contains(&name)
+            })
+            .take(10)
+            .collect())
+    }
+
+    async fn project_set(&self, id: Option<&str>) -> crate::Result<Value> {
+        self.state.lock().unwrap().organization = id.map(String::from);
+        Ok(json!({ "id": id }))
+    }
+
+    async fn show_submission(&self) -> crate::Result<Vec<Value>> {
+        let state = self.state.lock().unwrap();
+        let uploads = state.uploads.iter().map(|((dataset, name), data)| {
+            json!({
+                "id": format!("upload:{}:{}", dataset, name),
+                "data": data,
+                "extras": {"type": "client-upload", "dataset": dataset, "resource": name},
+            })
+        });
+        Ok(state.validated.values().cloned().chain(uploads).collect())
+    }
+
+    /// Portal has no validation of its own, so only the fields required by
+    /// CKAN are checked.
+    async fn validate_dataset(&self, path: &OsStr, name: &str) -> crate::Result<ValidationResult> {
+        let package = self.package(path, name)?;
+
+        let mut errors = Map::new();
+        let slug = package["name"].as_str().unwrap_or_default();
+        if slug.len() < 2 || slug != package_name(slug) {
+            errors.insert(
+                "name".into(),
+                json!(["Must be at least 2 characters long and contain only lowercase alphanumeric characters, - and _"]),
+            );
+        }
+        if package.get("owner_org").is_none() {
+            errors.insert("owner_org".into(), json!(["Organization is not selected"]));
+        }
+
+        let result = ValidationResult {
+            data: package,
+            errors: Value::Object(errors),
+        };
+        Ok(self.validated((name.into(), String::new()), "validated-dataset", result))
+    }
+
+    async fn validate_resource(
+        &self,
+        path: &OsStr,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ValidationResult> {
+        let res = find_resource(&path.to_string_lossy(), dataset, name)?;
+        let mut data = fields(&res.metadata_with_format());
+        data.entry("name").or_insert_with(|| name.into());
+
+        let result = ValidationResult {
+            data: Value::Object(data),
+            errors: json!({}),
+        };
+        Ok(self.validated((dataset.into(), name.into()), "validated-resource", result))
+    }
+
+    async fn show_upload(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        self.flake(dataset, name)
+    }
+
+    /// Publish the package of the dataset. The whole file is sent as a
+    /// single part.
+    async fn register_upload(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+        _chunk_size: u64,
+    ) -> crate::Result<RegisteredUpload> {
+        let size = find_resource(path, dataset, name)?.size;
+        let package = self.publish(OsStr::new(path), dataset).await?;
+        let chunk_size = size.max(1);
+
+        self.state.lock().unwrap().uploads.insert(
+            (dataset.into(), name.into()),
+            UploadData {
+                key: format!("{}/{}", dataset, name),
+                size,
+                dataset: dataset.into(),
+                name: name.into(),
+                completed: false,
+                bytes_uploaded: 0,
+                chunk_size: Some(chunk_size),
+                sha256: None,
+                md5: None,
+            },
+        );
+        Ok(RegisteredUpload {
+            id: package["id"].as_str().unwrap_or_default().into(),
+            chunk_size: Some(chunk_size),
+        })
+    }
+
+    async fn upload_part(
+        &self,
+        path: &str,
+        dataset: &str,
+        name: &str,
+        _part: u64,
+        _chunk_size: u64,
+    ) -> crate::Result<ProgressedUpload> {
+        let res = find_resource(path, dataset, name)?;
+        let package_id = self
+            .state
+            .lock()
+            .unwrap()
+            .packages
+            .get(dataset)
+            .cloned()
+            .ok_or_else(|| FdpError::NotFound("Upload has not started yet".into()))?;
+
+        let package: Value = self
+            .ckan
+            .build("package_show")
+            .params(Params::Json(json!({ "id": &package_id })))
+            .send()
+            .await?
+            .extract()?;
+        let mut metadata = fields(&res.metadata_with_format());
+        let title = metadata
+            .remove("name")
+            .unwrap_or_else(|| name.into())
+            .as_str()
+            .unwrap_or(name)
+            .to_string();
+        let existing = package["resources"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|r| r["name"] == title.as_str())
+            .and_then(|r| r["id"].as_str());
+
+        let mut payload = Params::multipart();
+        payload
+            .add_field("package_id", package_id.clone())
+            .add_field("name", title.clone());
+        for (field, value) in &metadata {
+            if let Value::String(value) = value {
+                payload.add_field(field.clone(), value.clone());
+            }
+        }
+        let action = match existing {
+            Some(id) => {
+                payload.add_field("id", id);
+                "resource_update"
+            }
+            None => "resource_create",
+        };
+        payload.add_named_file("upload", name, res.path.join(&res.name));
+
+        let _resource: Value = self
+            .ckan
+            .build(action)
+            .params(payload)
+            .send()
+            .await?
+            .extract()?;
+
+        if let Some(data) = self
+            .state
+            .lock()
+            .unwrap()
+            .uploads
+            .get_mut(&(dataset.into(), name.into()))
+        {
+            data.bytes_uploaded = data.size;
+        }
+        self.flake(dataset, name)
+    }
+
+    async fn complete_upload(
+        &self,
+        _path: &str,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ProgressedUpload> {
+        if let Some(data) = self
+            .state
+            .lock()
+            .unwrap()
+            .uploads
+            .get_mut(&(dataset.into(), name.into()))
+        {
+            data.completed = true;
+        }
+        self.flake(dataset, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::TestServer;
+    use crate::testing::source;
+    use crate::upload::Uploader;
+
+    const METADATA: &str = "Dataset Field,Description,Master metadata,dataset\n\
+        title,,Root title,\n\
+        notes,,Shared notes,Dataset notes\n\
+        Resource Field,,,file.csv\n\
+        name,,,Readings\n\
+        description,,,Water levels\n";
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!("flood-study-2022", package_name("Flood study 2022"));
+        assert_eq!("data_v1", package_name(" data_v1."));
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let dir = source(&[("file.csv", 10)]);
+        std::fs::write(dir.path().join("metadata.csv"), METADATA).unwrap();
+        let path = dir.path().to_str().unwrap();
+        let server = TestServer::start().unwrap();
+        let client = StockClient::new(server.ckan()).user("test-user");
+
+        assert_eq!("Test user", client.user_info().await.unwrap().display_name);
+        assert_eq!(1, client.available_projects("coast").await.unwrap().len());
+
+        let result = client
+            .validate_dataset(OsStr::new(path), "dataset")
+            .await
+            .unwrap();
+        assert!(result.errors.get("owner_org").is_some());
+        client.project_set(Some("1")).await.unwrap();
+        let result = client
+            .validate_dataset(OsStr::new(path), "dataset")
+            .await
+            .unwrap();
+        assert_eq!(json!({}), result.errors);
+        assert_eq!(json!("Root title"), result.data["title"]);
+        assert_eq!(json!("Dataset notes"), result.data["notes"]);
+
+        let upload = Uploader::new(&client, path)
+            .upload("dataset", "file.csv")
+            .await
+            .unwrap();
+        assert!(upload.data.completed);
+        assert_eq!(10, upload.data.bytes_uploaded);
+
+        let package = server.package("dataset").unwrap();
+        assert_eq!(json!("1"), package["owner_org"]);
+        assert_eq!(json!("Dataset notes"), package["notes"]);
+        let resource = &package["resources"][0];
+        assert_eq!(json!("Readings"), resource["name"]);
+        assert_eq!(json!("Water levels"), resource["description"]);
+        assert!(resource["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/download/file.csv"));
+        assert_eq!(
+            Some(vec![1; 10]),
+            server.file(resource["id"].as_str().unwrap())
+        );
+
+        // Published again, the package and its resource are updated.
+        std::fs::write(dir.path().join("dataset").join("file.csv"), [2; 4]).unwrap();
+        client
+            .register_upload(path, "dataset", "file.csv", 1)
+            .await
+            .unwrap();
+        client
+            .progress_upload(path, "dataset", "file.csv", 1, 4)
+            .await
+            .unwrap();
+        let package = server.package("dataset").unwrap();
+        assert_eq!(1, package["resources"].as_array().unwrap().len());
+        let resource = &package["resources"][0];
+        assert_eq!(
+            Some(vec![2; 4]),
+            server.file(resource["id"].as_str().unwrap())
+        );
+
+        client.submission_finalize().await.unwrap();
+        let submission = client.show_submission().await.unwrap();
+        assert_eq!(2, submission.len());
+    }
+}
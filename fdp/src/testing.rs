@@ -0,0 +1,11 @@
+//! Helpers shared by tests.
+
+/// Source directory with the given files of the dataset `dataset`.
+pub(crate) fn source(files: &[(&str, usize)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("dataset")).unwrap();
+    for (name, size) in files {
+        std::fs::write(dir.path().join("dataset").join(name), vec![1; *size]).unwrap();
+    }
+    dir
+}
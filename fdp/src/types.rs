@@ -1,9 +1,16 @@
+pub mod format;
+pub mod sheet;
+pub mod sidecar;
 mod source;
+pub mod xlsx;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-pub use self::source::{Dataset, Metadata, MetadataContent, Resource, Source, dataset_comments, resource_comments};
+pub use self::source::{
+    dataset_choices, dataset_comments, resource_comments, Dataset, Metadata, MetadataContent,
+    Resource, Source,
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AvailableProjects {
@@ -11,14 +18,14 @@ pub struct AvailableProjects {
     pub results: Vec<Project>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Project {
     pub id: String,
     pub name: String,
     pub title: String,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct User {
     pub display_name: String,
     pub id: String,
@@ -28,21 +35,34 @@ pub struct User {
 pub struct Portal {
     pub url: Option<String>,
     pub token: Option<String>,
+    /// Size of the upload parts requested from this portal.
+    #[serde(default)]
+    pub chunk_size: Option<u64>,
+    /// Path to the TOML file with the [`Profile`](crate::profile::Profile)
+    /// of the portal.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Name of the account, required by the portal without the extension.
+    #[serde(default)]
+    pub user: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 
 pub struct RegisteredUpload {
     pub id: String,
+    /// Chunk size accepted by the portal, if it differs from the requested.
+    #[serde(default)]
+    pub chunk_size: Option<u64>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProgressedUpload {
     pub id: String,
     pub data: UploadData,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct UploadData {
     pub key: String,
     pub size: u64,
@@ -50,9 +70,17 @@ pub struct UploadData {
     pub name: String,
     pub completed: bool,
     pub bytes_uploaded: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_size: Option<u64>,
+    /// Digest of the last received part, or of the whole file once the
+    /// upload is completed. Reported only by portals that verify uploads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ValidationResult {
     pub data: Value,
     pub errors: Value,
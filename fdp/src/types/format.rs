@@ -0,0 +1,429 @@
+//! Detection of `format` and `format_label` of the resource.
+//!
+//! Content of the file is checked first: signatures of PDF, archives,
+//! images, shapefiles, GeoTIFF, NetCDF and HDF5 are recognized regardless
+//! of the extension. Archives and other containers are refined by the
+//! extension, e.g. DOCX is a ZIP archive labeled as `Word`. Files without a
+//! known signature are labeled by the extension only.
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Tags that turn TIFF into GeoTIFF: ModelPixelScale, ModelTiepoint,
+/// ModelTransformation and GeoKeyDirectory.
+const GEOTIFF_TAGS: [u16; 4] = [33550, 33922, 34264, 34735];
+/// Limit of the TIFF directory entries, to stop on broken files.
+const MAX_TIFF_ENTRIES: u16 = 4096;
+
+/// Labels and formats by the lowercased extension.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("doc", "Word"),
+    ("docx", "Word"),
+    ("odt", "Word"),
+    ("rtf", "Word"),
+    ("xls", "Excel"),
+    ("xlsx", "Excel"),
+    ("xlsm", "Excel"),
+    ("ods", "Excel"),
+    ("las", "Point"),
+    ("laz", "Point"),
+    ("xyz", "Point"),
+    ("pts", "Point"),
+    ("e57", "Point"),
+    ("txt", "Text"),
+    ("md", "Text"),
+    ("log", "Text"),
+    ("json", "Text"),
+    ("xml", "Text"),
+    ("csv", "CSV"),
+    ("tsv", "CSV"),
+    ("pdf", "PDF"),
+    ("zip", "Zipped"),
+    ("7z", "Zipped"),
+    ("gz", "Zipped"),
+    ("tar", "Zipped"),
+    ("rar", "Zipped"),
+    ("png", "Image"),
+    ("jpg", "Image"),
+    ("jpeg", "Image"),
+    ("gif", "Image"),
+    ("bmp", "Image"),
+    ("tif", "Image"),
+    ("tiff", "Image"),
+    ("webp", "Image"),
+    ("shp", "GIS"),
+    ("shx", "GIS"),
+    ("dbf", "GIS"),
+    ("prj", "GIS"),
+    ("cpg", "GIS"),
+    ("kml", "GIS"),
+    ("kmz", "GIS"),
+    ("geojson", "GIS"),
+    ("gpkg", "GIS"),
+    ("tab", "GIS"),
+    ("mif", "GIS"),
+    ("mid", "GIS"),
+    ("asc", "GIS"),
+    ("flt", "GIS"),
+    ("grd", "GIS"),
+    ("ecw", "GIS"),
+    ("nc", "GIS"),
+    ("qgs", "GIS"),
+    ("qgz", "GIS"),
+    ("mxd", "GIS"),
+    ("lyr", "GIS"),
+    // TUFLOW
+    ("tcf", "Model"),
+    ("tgc", "Model"),
+    ("tbc", "Model"),
+    ("ecf", "Model"),
+    ("tef", "Model"),
+    ("tmf", "Model"),
+    ("trd", "Model"),
+    ("toc", "Model"),
+    ("2dm", "Model"),
+    ("xmdf", "Model"),
+    // HEC-RAS and HEC-HMS
+    ("rasmap", "Model"),
+    ("hdf", "Model"),
+    ("hms", "Model"),
+    ("basin", "Model"),
+    // MIKE
+    ("m11", "Model"),
+    ("m21", "Model"),
+    ("m21fm", "Model"),
+    ("mdf", "Model"),
+    ("sim11", "Model"),
+    ("nwk11", "Model"),
+    ("mhydro", "Model"),
+    ("dfs0", "Model"),
+    ("dfs1", "Model"),
+    ("dfs2", "Model"),
+    ("dfsu", "Model"),
+    // RORB, WBNM, DRAINS, 12d, SWMM and URBS
+    ("catg", "Model"),
+    ("par", "Model"),
+    ("wbn", "Model"),
+    ("drn", "Model"),
+    ("12da", "Model"),
+    ("xp", "Model"),
+    ("inp", "Model"),
+    ("vec", "Model"),
+];
+
+/// Suggested values of the resource fields.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Suggestion {
+    pub format: String,
+    pub format_label: String,
+}
+
+impl Suggestion {
+    fn new(format: &str, format_label: &str) -> Self {
+        Self {
+            format: format.into(),
+            format_label: format_label.into(),
+        }
+    }
+}
+
+/// Type of the file recognized by its content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Signature {
+    Pdf,
+    Zip,
+    /// Compound document of the old Office formats.
+    Ole,
+    Gzip,
+    SevenZip,
+    Png,
+    Jpeg,
+    Gif,
+    Tiff,
+    GeoTiff,
+    Shapefile,
+    NetCdf,
+    /// HDF5, used by NetCDF-4 and by the model outputs.
+    Hdf5,
+    Sqlite,
+    Las,
+}
+
+impl Signature {
+    fn suggestion(&self) -> Suggestion {
+        match self {
+            Self::Pdf => Suggestion::new("PDF", "PDF"),
+            Self::Zip => Suggestion::new("ZIP", "Zipped"),
+            Self::Ole => Suggestion::new("OLE", "MISC"),
+            Self::Gzip => Suggestion::new("GZ", "Zipped"),
+            Self::SevenZip => Suggestion::new("7Z", "Zipped"),
+            Self::Png => Suggestion::new("PNG", "Image"),
+            Self::Jpeg => Suggestion::new("JPEG", "Image"),
+            Self::Gif => Suggestion::new("GIF", "Image"),
+            Self::Tiff => Suggestion::new("TIFF", "Image"),
+            Self::GeoTiff => Suggestion::new("GeoTIFF", "GIS"),
+            Self::Shapefile => Suggestion::new("SHP", "GIS"),
+            Self::NetCdf => Suggestion::new("NetCDF", "GIS"),
+            Self::Hdf5 => Suggestion::new("HDF5", "Model"),
+            Self::Sqlite => Suggestion::new("SQLite", "MISC"),
+            Self::Las => Suggestion::new("LAS", "Point"),
+        }
+    }
+
+    /// Whether the extension tells more about the file than the signature,
+    /// e.g. DOCX inside ZIP or NetCDF-4 inside HDF5.
+    fn is_container(&self) -> bool {
+        matches!(self, Self::Zip | Self::Ole | Self::Hdf5 | Self::Sqlite)
+    }
+}
+
+/// Signature of the first bytes of the file. TIFF is checked further to
+/// tell GeoTIFF apart.
+fn sniff(file: &mut File) -> io::Result<Option<Signature>> {
+    let mut header = [0; 16];
+    let size = read_up_to(file, &mut header)?;
+    let header = &header[..size];
+
+    let signature = if header.starts_with(b"%PDF") {
+        Signature::Pdf
+    } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        Signature::Zip
+    } else if header.starts_with(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+        Signature::Ole
+    } else if header.starts_with(b"\x1F\x8B") {
+        Signature::Gzip
+    } else if header.starts_with(b"7z\xBC\xAF\x27\x1C") {
+        Signature::SevenZip
+    } else if header.starts_with(b"\x89PNG\r\n\x1A\n") {
+        Signature::Png
+    } else if header.starts_with(b"\xFF\xD8\xFF") {
+        Signature::Jpeg
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Signature::Gif
+    } else if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+        match is_geotiff(file, header[0] == b'I') {
+            Ok(true) => Signature::GeoTiff,
+            _ => Signature::Tiff,
+        }
+    } else if header.starts_with(b"\0\0\x27\x0A") {
+        Signature::Shapefile
+    } else if header.starts_with(b"CDF\x01")
+        || header.starts_with(b"CDF\x02")
+        || header.starts_with(b"CDF\x05")
+    {
+        Signature::NetCdf
+    } else if header.starts_with(b"\x89HDF\r\n\x1A\n") {
+        Signature::Hdf5
+    } else if header.starts_with(b"SQLite format 3\0") {
+        Signature::Sqlite
+    } else if header.starts_with(b"LASF") {
+        Signature::Las
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(signature))
+}
+
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buf.len() {
+        match file.read(&mut buf[total..])? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+/// Whether the first directory of TIFF has any of the GeoTIFF tags.
+fn is_geotiff(file: &mut File, little_endian: bool) -> io::Result<bool> {
+    let u16_from = |bytes: [u8; 2]| match little_endian {
+        true => u16::from_le_bytes(bytes),
+        false => u16::from_be_bytes(bytes),
+    };
+    let mut offset = [0; 4];
+    file.seek(SeekFrom::Start(4))?;
+    file.read_exact(&mut offset)?;
+    let offset = match little_endian {
+        true => u32::from_le_bytes(offset),
+        false => u32::from_be_bytes(offset),
+    };
+
+    let mut count = [0; 2];
+    file.seek(SeekFrom::Start(offset.into()))?;
+    file.read_exact(&mut count)?;
+    let count = u16_from(count).min(MAX_TIFF_ENTRIES);
+
+    let mut entries = vec![0; count as usize * 12];
+    let size = read_up_to(file, &mut entries)?;
+    Ok(entries[..size - size % 12]
+        .chunks(12)
+        .map(|entry| u16_from([entry[0], entry[1]]))
+        .any(|tag| GEOTIFF_TAGS.contains(&tag)))
+}
+
+/// Suggestion by the extension only.
+pub fn by_extension(name: &str) -> Option<Suggestion> {
+    let extension = Path::new(name).extension()?.to_string_lossy();
+    let lowercase = extension.to_lowercase();
+    let label = EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == lowercase)
+        .map(|(_, label)| *label)
+        .or_else(|| is_hec_ras(&lowercase).then_some("Model"))
+        .unwrap_or("MISC");
+    Some(Suggestion::new(&extension.to_uppercase(), label))
+}
+
+/// Numbered files of HEC-RAS project: geometry, plan, flow and so on,
+/// e.g. `.g01` or `.p12`.
+fn is_hec_ras(extension: &str) -> bool {
+    let mut chars = extension.chars();
+    matches!(chars.next(), Some('g' | 'p' | 'u' | 'f' | 'q' | 'b'))
+        && extension.len() == 3
+        && chars.all(|c| c.is_ascii_digit())
+}
+
+/// Format and label of the file. Unreadable files are labeled by the
+/// extension.
+pub fn detect(path: &Path) -> Option<Suggestion> {
+    let name = path.file_name()?.to_string_lossy();
+    let signature = match File::open(path).and_then(|mut file| sniff(&mut file)) {
+        Ok(signature) => signature,
+        Err(err) => {
+            log::debug!("Cannot sniff {}: {}", path.display(), err);
+            None
+        }
+    };
+    let extension = by_extension(&name).filter(|s| s.format_label != "MISC");
+
+    match (signature, extension) {
+        (Some(signature), Some(extension))
+            if signature.is_container()
+                || extension.format_label == signature.suggestion().format_label =>
+        {
+            Some(extension)
+        }
+        (Some(signature), _) => Some(signature.suggestion()),
+        (None, Some(extension)) => Some(extension),
+        (None, None) => by_extension(&name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn detect_content(name: &str, content: &[u8]) -> Option<Suggestion> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        detect(&path)
+    }
+
+    /// Little-endian TIFF with a single directory entry.
+    fn tiff(tag: u16) -> Vec<u8> {
+        let mut content = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        content.extend(tag.to_le_bytes());
+        content.extend([3, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        content
+    }
+
+    #[test]
+    fn test_by_extension() {
+        assert_eq!(
+            Some(Suggestion::new("CSV", "CSV")),
+            by_extension("flow.csv")
+        );
+        assert_eq!(
+            Some(Suggestion::new("DOCX", "Word")),
+            by_extension("Report.DOCX")
+        );
+        assert_eq!(Some(Suggestion::new("TCF", "Model")), by_extension("m.tcf"));
+        assert_eq!(Some(Suggestion::new("G01", "Model")), by_extension("r.g01"));
+        assert_eq!(Some(Suggestion::new("ABC", "MISC")), by_extension("x.abc"));
+        assert_eq!(None, by_extension("README"));
+    }
+
+    #[test]
+    fn test_detect_signature() {
+        let pdf = Some(Suggestion::new("PDF", "PDF"));
+        assert_eq!(pdf, detect_content("report.pdf", b"%PDF-1.7\n"));
+        assert_eq!(pdf, detect_content("report", b"%PDF-1.7\n"));
+        assert_eq!(pdf, detect_content("report.dat", b"%PDF-1.7\n"));
+
+        let zip = b"PK\x03\x04\x14\0\0\0";
+        assert_eq!(
+            Some(Suggestion::new("ZIP", "Zipped")),
+            detect_content("archive.bin", zip)
+        );
+        assert_eq!(
+            Some(Suggestion::new("DOCX", "Word")),
+            detect_content("report.docx", zip)
+        );
+        assert_eq!(
+            Some(Suggestion::new("KMZ", "GIS")),
+            detect_content("areas.kmz", zip)
+        );
+        assert_eq!(
+            Some(Suggestion::new("PNG", "Image")),
+            detect_content("photo.csv", b"\x89PNG\r\n\x1A\n\0\0")
+        );
+        assert_eq!(
+            Some(Suggestion::new("SHP", "GIS")),
+            detect_content("rivers.shp", b"\0\0\x27\x0A\0\0\0\0")
+        );
+        assert_eq!(
+            Some(Suggestion::new("NetCDF", "GIS")),
+            detect_content("depth", b"CDF\x01\0\0\0\0")
+        );
+        assert_eq!(
+            Some(Suggestion::new("NC", "GIS")),
+            detect_content("depth.nc", b"\x89HDF\r\n\x1A\n")
+        );
+        assert_eq!(
+            Some(Suggestion::new("HDF5", "Model")),
+            detect_content("results", b"\x89HDF\r\n\x1A\n")
+        );
+        assert_eq!(
+            Some(Suggestion::new("LAZ", "Point")),
+            detect_content("cloud.laz", b"LASF\0\0")
+        );
+    }
+
+    #[test]
+    fn test_detect_geotiff() {
+        assert_eq!(
+            Some(Suggestion::new("GeoTIFF", "GIS")),
+            detect_content("dem.tif", &tiff(34735))
+        );
+        assert_eq!(
+            Some(Suggestion::new("TIF", "Image")),
+            detect_content("photo.tif", &tiff(256))
+        );
+        assert_eq!(
+            Some(Suggestion::new("TIFF", "Image")),
+            detect_content("photo", b"II*\0\xFF\xFF\0\0")
+        );
+    }
+
+    #[test]
+    fn test_detect_without_signature() {
+        assert_eq!(
+            Some(Suggestion::new("TXT", "Text")),
+            detect_content("notes.txt", b"Roses are red")
+        );
+        assert_eq!(
+            Some(Suggestion::new("DAT", "MISC")),
+            detect_content("flow.dat", b"1 2 3")
+        );
+        assert_eq!(None, detect_content("README", b"Roses are red"));
+        assert_eq!(
+            Some(Suggestion::new("PDF", "PDF")),
+            detect(Path::new("/missing/report.pdf"))
+        );
+    }
+}
@@ -0,0 +1,899 @@
+//! Complete content of metadata.csv or metadata.xlsx.
+//!
+//! The first row holds the headers: field name, description, master metadata
+//! and a column for every dataset. Dataset fields follow it until the first
+//! `Resource Field` row. Every `Resource Field` row starts the group of
+//! resource fields and names the resource of every dataset in the
+//! corresponding column. [`Sheet`] keeps all the cells, so the file can be
+//! updated cell by cell without losing the columns, rows and descriptions
+//! that are not changed. [`MetadataSheet`] indexes the sheet, so metadata of
+//! the source, its datasets and resources is read from a single parsed file
+//! and every value can be traced back to its cell.
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+
+use csv::{ReaderBuilder, WriterBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{xlsx, Metadata};
+
+pub const RESOURCE_FIELD: &str = "Resource Field";
+/// Column of the field names.
+pub const FIELD_COLUMN: usize = 0;
+/// Column of the field descriptions.
+pub const DESCRIPTION_COLUMN: usize = 1;
+/// Column of the root metadata.
+pub const MASTER_COLUMN: usize = 2;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Characters of Windows-1252 in the range 0x80..0xA0. Other bytes match
+/// the Unicode code points.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Problem found while reading the sheet, which doesn't prevent reading
+/// the rest of it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Diagnostic {
+    /// Line of the file, starting from 1, if the problem is not in the
+    /// whole file.
+    pub line: Option<u64>,
+    pub message: String,
+    /// File with the problem, relative to the source, if it's not the
+    /// metadata sheet.
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new<M: Into<String>>(line: Option<u64>, message: M) -> Self {
+        Self {
+            line,
+            message: message.into(),
+            file: None,
+        }
+    }
+
+    /// Problem of the whole `file` other than the metadata sheet.
+    pub fn in_file<F: Into<String>, M: Into<String>>(file: F, message: M) -> Self {
+        Self {
+            line: None,
+            message: message.into(),
+            file: Some(file.into()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Encoding of metadata.csv.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Windows1252,
+}
+
+/// Text of the file. Content that is not valid UTF-8 is read as
+/// Windows-1252, which Excel uses for CSV in the western locales.
+fn decode(bytes: &[u8], diagnostics: &mut Vec<Diagnostic>) -> (String, Encoding) {
+    let bytes = bytes.strip_prefix(BOM).unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.into(), Encoding::Utf8),
+        Err(err) => {
+            let valid = &bytes[..err.valid_up_to()];
+            let line = valid.iter().filter(|&&b| b == b'\n').count() as u64 + 1;
+            diagnostics.push(Diagnostic::new(
+                Some(line),
+                "File is not UTF-8 encoded, it is read as Windows-1252",
+            ));
+            let text = bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect();
+            (text, Encoding::Windows1252)
+        }
+    }
+}
+
+/// Bytes of the text in the given encoding, or `None` if some characters
+/// cannot be represented in it.
+fn encode(text: &str, encoding: Encoding) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::Utf8 => Some(text.as_bytes().to_vec()),
+        Encoding::Windows1252 => text
+            .chars()
+            .map(|c| match c as u32 {
+                0..=0x7F | 0xA0..=0xFF => Some(c as u8),
+                _ => WINDOWS_1252
+                    .iter()
+                    .position(|&w| w == c)
+                    .map(|i| 0x80 + i as u8),
+            })
+            .collect(),
+    }
+}
+
+/// `;` if the header has more of them than commas, as in CSV exported by
+/// Excel in the locales with decimal comma.
+fn detect_delimiter(text: &str) -> u8 {
+    let header = text.lines().next().unwrap_or_default();
+    let count = |delimiter| header.chars().filter(|&c| c == delimiter).count();
+    if count(';') > count(',') {
+        b';'
+    } else {
+        b','
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sheet {
+    rows: Vec<Vec<String>>,
+    /// Delimiter of metadata.csv, kept when the file is written back.
+    delimiter: u8,
+    /// Encoding of metadata.csv and whether it starts with BOM, kept when
+    /// the file is written back as well.
+    encoding: Encoding,
+    bom: bool,
+}
+
+impl Default for Sheet {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl Sheet {
+    pub fn new(rows: Vec<Vec<String>>) -> Self {
+        Self {
+            rows,
+            delimiter: b',',
+            encoding: Encoding::Utf8,
+            bom: false,
+        }
+    }
+
+    /// Sheet with the header row only.
+    pub fn with_header() -> Self {
+        Self::new(vec![vec![
+            "Dataset Field".into(),
+            "Description".into(),
+            "Master metadata".into(),
+        ]])
+    }
+
+    /// Read metadata.csv or metadata.xlsx, depending on the extension.
+    pub fn read<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        Ok(Self::read_with_diagnostics(path)?.0)
+    }
+
+    /// Read the sheet along with the problems found in its content. Only the
+    /// file that cannot be read at all is an error.
+    pub fn read_with_diagnostics<P: AsRef<Path>>(
+        path: P,
+    ) -> crate::Result<(Self, Vec<Diagnostic>)> {
+        let path = path.as_ref();
+        if xlsx::is_xlsx(path) {
+            let sheet = Self::new(xlsx::read(path)?);
+            let diagnostics = sheet.check_width(|row| Some(row as u64 + 1));
+            return Ok((sheet, diagnostics));
+        }
+        Ok(Self::parse(&std::fs::read(path)?))
+    }
+
+    /// Parse content of metadata.csv, tolerating BOM, Windows-1252
+    /// encoding, `;` delimiter and rows of different length.
+    pub fn parse(bytes: &[u8]) -> (Self, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let (text, encoding) = decode(bytes, &mut diagnostics);
+        let delimiter = detect_delimiter(&text);
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(text.as_bytes());
+
+        let mut rows = Vec::new();
+        let mut lines = Vec::new();
+        for record in reader.records() {
+            match record {
+                Ok(record) => {
+                    lines.push(record.position().map(|p| p.line()));
+                    rows.push(record.iter().map(String::from).collect());
+                }
+                Err(err) => {
+                    let line = err.position().map(|p| p.line());
+                    diagnostics.push(Diagnostic::new(line, err.to_string()));
+                    break;
+                }
+            }
+        }
+
+        let sheet = Self {
+            rows,
+            delimiter,
+            encoding,
+            bom: bytes.starts_with(BOM),
+        };
+        diagnostics.extend(sheet.check_width(|row| lines[row]));
+        (sheet, diagnostics)
+    }
+
+    /// Rows with values outside of the columns named in the header.
+    fn check_width<F: Fn(usize) -> Option<u64>>(&self, line: F) -> Vec<Diagnostic> {
+        let width = self.rows.first().map(Vec::len).unwrap_or_default();
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, cells)| cells.iter().skip(width).any(|c| !c.is_empty()))
+            .map(|(row, cells)| {
+                Diagnostic::new(
+                    line(row),
+                    format!(
+                        "Row has {} cells, but the header has only {} columns",
+                        cells.len(),
+                        width
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// Write all the rows, padded to the same width, with the delimiter and
+    /// encoding of the original file. Text that cannot be represented in
+    /// Windows-1252 is written as UTF-8 with BOM instead. The file is
+    /// replaced only after the content is written.
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        if xlsx::is_xlsx(path) {
+            return xlsx::write(path, &self.rows);
+        }
+        let width = self.rows.iter().map(Vec::len).max().unwrap_or_default();
+        let mut writer = WriterBuilder::new()
+            .flexible(true)
+            .delimiter(self.delimiter)
+            .from_writer(Vec::new());
+        for row in &self.rows {
+            let padding = std::iter::repeat_n("", width - row.len());
+            writer.write_record(row.iter().map(String::as_str).chain(padding))?;
+        }
+        let text = String::from_utf8(
+            writer
+                .into_inner()
+                .map_err(|err| crate::FdpError::Csv(err.to_string()))?,
+        )
+        .expect("CSV of strings is UTF-8");
+
+        // BOM is only meaningful for UTF-8, Windows-1252 has none
+        let (content, bom) = match encode(&text, self.encoding) {
+            Some(content) => (content, self.bom && matches!(self.encoding, Encoding::Utf8)),
+            None => (text.into_bytes(), true),
+        };
+        let tmp = path.with_extension("csv.tmp");
+        std::fs::write(&tmp, [if bom { BOM } else { &[] }, &content].concat())?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn rows(&self) -> &[Vec<String>] {
+        &self.rows
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> &str {
+        self.rows
+            .get(row)
+            .and_then(|r| r.get(column))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    pub fn set_cell<V: Into<String>>(&mut self, row: usize, column: usize, value: V) {
+        if self.rows.len() <= row {
+            self.rows.resize(row + 1, Vec::new());
+        }
+        let cells = &mut self.rows[row];
+        if cells.len() <= column {
+            cells.resize(column + 1, String::new());
+        }
+        cells[column] = value.into();
+    }
+
+    /// Set the cell unless it already has a value.
+    pub fn fill<V: Into<String>>(&mut self, row: usize, column: usize, value: V) {
+        if self.cell(row, column).is_empty() {
+            self.set_cell(row, column, value);
+        }
+    }
+
+    /// Column of the dataset.
+    pub fn column(&self, dataset: &str) -> Option<usize> {
+        self.rows
+            .first()?
+            .iter()
+            .skip(MASTER_COLUMN + 1)
+            .position(|h| h == dataset)
+            .map(|idx| idx + MASTER_COLUMN + 1)
+    }
+
+    /// Remove the column of the dataset with its values and resource names.
+    pub fn remove_column(&mut self, dataset: &str) -> bool {
+        match self.column(dataset) {
+            Some(column) => {
+                for row in &mut self.rows {
+                    if row.len() > column {
+                        row.remove(column);
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Column of the dataset, added after the existing ones if missing.
+    pub fn column_or_insert(&mut self, dataset: &str) -> usize {
+        match self.column(dataset) {
+            Some(column) => column,
+            None => {
+                let column = self
+                    .rows
+                    .first()
+                    .map(Vec::len)
+                    .unwrap_or_default()
+                    .max(MASTER_COLUMN + 1);
+                self.set_cell(0, column, dataset);
+                column
+            }
+        }
+    }
+
+    fn is_group(&self, row: usize) -> bool {
+        self.cell(row, FIELD_COLUMN) == RESOURCE_FIELD
+    }
+
+    /// Rows of the dataset fields.
+    fn dataset_rows(&self) -> Range<usize> {
+        let end = (1..self.rows.len())
+            .find(|&row| self.is_group(row))
+            .unwrap_or(self.rows.len());
+        1.min(end)..end
+    }
+
+    /// Rows of the resource group started at `start`, without the
+    /// `Resource Field` row itself.
+    fn group_rows(&self, start: usize) -> Range<usize> {
+        let end = (start + 1..self.rows.len())
+            .find(|&row| self.is_group(row))
+            .unwrap_or(self.rows.len());
+        start + 1..end
+    }
+
+    /// Start of the resource group, that holds `resource` in the `column`.
+    pub fn group(&self, column: usize, resource: &str) -> Option<usize> {
+        (0..self.rows.len()).find(|&row| self.is_group(row) && self.cell(row, column) == resource)
+    }
+
+    /// Start of the resource group, taking over the group that has no
+    /// resource in the `column` or adding the new one at the end.
+    pub fn group_or_insert(&mut self, column: usize, resource: &str) -> usize {
+        if let Some(row) = self.group(column, resource) {
+            return row;
+        }
+        let row = (0..self.rows.len())
+            .find(|&row| self.is_group(row) && self.cell(row, column).is_empty())
+            .unwrap_or_else(|| {
+                let row = self.rows.len().max(1);
+                self.set_cell(row, FIELD_COLUMN, RESOURCE_FIELD);
+                row
+            });
+        self.set_cell(row, column, resource);
+        row
+    }
+
+    fn find_field(&self, mut rows: Range<usize>, field: &str) -> Option<usize> {
+        rows.find(|&row| self.cell(row, FIELD_COLUMN) == field)
+    }
+
+    fn insert_field(&mut self, at: usize, field: &str) -> usize {
+        self.rows.insert(at, vec![field.to_string()]);
+        at
+    }
+
+    /// Row of the dataset field.
+    pub fn dataset_field(&self, field: &str) -> Option<usize> {
+        self.find_field(self.dataset_rows(), field)
+    }
+
+    /// Row of the dataset field, added at the end of the dataset fields if
+    /// missing.
+    pub fn dataset_field_or_insert(&mut self, field: &str) -> usize {
+        match self.dataset_field(field) {
+            Some(row) => row,
+            None => {
+                if self.rows.is_empty() {
+                    self.rows.push(Vec::new());
+                }
+                let at = self.dataset_rows().end;
+                self.insert_field(at, field)
+            }
+        }
+    }
+
+    /// Row of the field in the resource group started at `group`.
+    pub fn resource_field(&self, group: usize, field: &str) -> Option<usize> {
+        self.find_field(self.group_rows(group), field)
+    }
+
+    /// Row of the field in the resource group, added at the end of the group
+    /// if missing.
+    pub fn resource_field_or_insert(&mut self, group: usize, field: &str) -> usize {
+        match self.resource_field(group, field) {
+            Some(row) => row,
+            None => {
+                let at = self.group_rows(group).end;
+                self.insert_field(at, field)
+            }
+        }
+    }
+
+    /// Set the value of the dataset field in the `column`.
+    pub fn set_dataset_value<V: Into<String>>(&mut self, column: usize, field: &str, value: V) {
+        let row = self.dataset_field_or_insert(field);
+        self.set_cell(row, column, value);
+    }
+
+    /// Set the value of the resource field in the `column`.
+    pub fn set_resource_value<V: Into<String>>(
+        &mut self,
+        column: usize,
+        resource: &str,
+        field: &str,
+        value: V,
+    ) {
+        let group = self.group_or_insert(column, resource);
+        let row = self.resource_field_or_insert(group, field);
+        self.set_cell(row, column, value);
+    }
+
+    /// Set the description of the dataset field, unless it's already
+    /// described.
+    pub fn describe(&mut self, field: &str, description: &str) {
+        if let Some(row) = self.dataset_field(field) {
+            if self.cell(row, DESCRIPTION_COLUMN).is_empty() {
+                self.set_cell(row, DESCRIPTION_COLUMN, description);
+            }
+        }
+    }
+}
+
+/// Position of the cell, counted from zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Cell {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl Cell {
+    pub fn new(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+
+    /// Reference in the spreadsheet notation, e.g. `C5`.
+    pub fn a1(&self) -> String {
+        let mut letters = Vec::new();
+        let mut column = self.column + 1;
+        while column > 0 {
+            letters.push(b'A' + ((column - 1) % 26) as u8);
+            column = (column - 1) / 26;
+        }
+        letters.reverse();
+        format!("{}{}", String::from_utf8_lossy(&letters), self.row + 1)
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.a1())
+    }
+}
+
+/// Start of the dataset fields in the section index. Resource groups are
+/// identified by the row of their `Resource Field`.
+const DATASET_SECTION: usize = 0;
+
+/// Parsed metadata.csv with the index of dataset columns, resource groups
+/// and fields.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataSheet {
+    sheet: Sheet,
+    columns: HashMap<String, usize>,
+    groups: HashMap<(usize, String), usize>,
+    /// Row of the field by the section and the field name.
+    fields: HashMap<(usize, String), usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl MetadataSheet {
+    pub fn new(sheet: Sheet) -> Self {
+        let mut index = Self {
+            sheet,
+            ..Default::default()
+        };
+        let sheet = &index.sheet;
+
+        if let Some(header) = sheet.rows.first() {
+            for (column, name) in header.iter().enumerate().skip(MASTER_COLUMN + 1) {
+                index.columns.entry(name.clone()).or_insert(column);
+            }
+        }
+
+        let mut section = DATASET_SECTION;
+        for (row, cells) in sheet.rows.iter().enumerate().skip(1) {
+            if sheet.is_group(row) {
+                section = row;
+                for (column, resource) in cells.iter().enumerate().skip(MASTER_COLUMN + 1) {
+                    index
+                        .groups
+                        .entry((column, resource.clone()))
+                        .or_insert(row);
+                }
+            } else {
+                let field = sheet.cell(row, FIELD_COLUMN).to_string();
+                index.fields.insert((section, field), row);
+            }
+        }
+        index
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let (sheet, diagnostics) = Sheet::read_with_diagnostics(path)?;
+        Ok(Self {
+            diagnostics,
+            ..Self::new(sheet)
+        })
+    }
+
+    /// Sheet of the file, or the empty one if the file is missing or cannot
+    /// be read. The reason is kept in the diagnostics.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Self::default();
+        }
+        Self::read(path).unwrap_or_else(|err| {
+            log::error!("Cannot read {}: {}", path.display(), err);
+            Self {
+                diagnostics: vec![Diagnostic::new(None, err.to_string())],
+                ..Self::default()
+            }
+        })
+    }
+
+    /// Problems found while reading the file.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn sheet(&self) -> &Sheet {
+        &self.sheet
+    }
+
+    pub fn column(&self, dataset: &str) -> Option<usize> {
+        self.columns.get(dataset).copied()
+    }
+
+    /// Start of the resource group of the dataset.
+    pub fn group(&self, dataset: &str, resource: &str) -> Option<usize> {
+        let column = self.column(dataset)?;
+        self.groups.get(&(column, resource.to_string())).copied()
+    }
+
+    /// Values of the `column` in the `rows`. Sheet without values in the
+    /// column has no metadata.
+    fn metadata(&self, rows: Range<usize>, column: usize, include_empty: bool) -> Metadata {
+        let mut present = false;
+        let mut data = Map::new();
+        for row in rows {
+            if self.sheet.rows[row].len() <= column {
+                continue;
+            }
+            present = true;
+            let value = self.sheet.cell(row, column);
+            if include_empty || !value.is_empty() {
+                let field = self.sheet.cell(row, FIELD_COLUMN);
+                data.insert(field.into(), Value::from(value));
+            }
+        }
+        match present {
+            true => Metadata::Object(Value::Object(data)),
+            false => Metadata::Empty,
+        }
+    }
+
+    /// Root metadata, including empty fields.
+    pub fn master(&self) -> Metadata {
+        self.metadata(self.sheet.dataset_rows(), MASTER_COLUMN, true)
+    }
+
+    pub fn dataset(&self, name: &str) -> Metadata {
+        match self.column(name) {
+            Some(column) => self.metadata(self.sheet.dataset_rows(), column, false),
+            None => Metadata::Empty,
+        }
+    }
+
+    pub fn resource(&self, dataset: &str, name: &str) -> Metadata {
+        match (self.column(dataset), self.group(dataset, name)) {
+            (Some(column), Some(group)) => {
+                self.metadata(self.sheet.group_rows(group), column, false)
+            }
+            _ => Metadata::Empty,
+        }
+    }
+
+    fn field(&self, section: usize, field: &str) -> Option<usize> {
+        self.fields.get(&(section, field.to_string())).copied()
+    }
+
+    /// Cell of the root metadata field.
+    pub fn master_cell(&self, field: &str) -> Option<Cell> {
+        let row = self.field(DATASET_SECTION, field)?;
+        Some(Cell::new(row, MASTER_COLUMN))
+    }
+
+    pub fn dataset_cell(&self, dataset: &str, field: &str) -> Option<Cell> {
+        let row = self.field(DATASET_SECTION, field)?;
+        Some(Cell::new(row, self.column(dataset)?))
+    }
+
+    pub fn resource_cell(&self, dataset: &str, resource: &str, field: &str) -> Option<Cell> {
+        let row = self.field(self.group(dataset, resource)?, field)?;
+        Some(Cell::new(row, self.column(dataset)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SHEET: &str = "\
+Dataset Field,Description,Master metadata,rivers,lakes,notes
+title,Title of the dataset,Root,Rivers,Lakes,
+custom,\"Multiline
+comment\",,a,b,keep me
+Resource Field,Description,,flow.csv,depth.csv,
+name,Name,,Flow,Depth,
+extra,,,,,unknown
+Resource Field,Description,,,level.csv,
+name,Name,,,Level,
+repeat 'Resource Field' rows for each resource,,,,,
+";
+
+    fn sheet() -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.csv");
+        std::fs::write(&path, SHEET).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (_dir, path) = sheet();
+        let sheet = Sheet::read(&path).unwrap();
+        sheet.write(&path).unwrap();
+        assert_eq!(SHEET, std::fs::read_to_string(&path).unwrap());
+        assert_eq!(sheet, Sheet::read(&path).unwrap());
+    }
+
+    #[test]
+    fn test_update_cells() {
+        let (_dir, path) = sheet();
+        let mut sheet = Sheet::read(&path).unwrap();
+        let mut expected = sheet.clone();
+
+        sheet.set_dataset_value(MASTER_COLUMN, "title", "New root");
+        let lakes = sheet.column("lakes").unwrap();
+        sheet.set_resource_value(lakes, "level.csv", "name", "Water level");
+        sheet.write(&path).unwrap();
+
+        expected.rows[1][2] = "New root".into();
+        expected.rows[7][4] = "Water level".into();
+        assert_eq!(expected, Sheet::read(&path).unwrap());
+    }
+
+    #[test]
+    fn test_insert_cells() {
+        let (_dir, path) = sheet();
+        let mut sheet = Sheet::read(&path).unwrap();
+
+        sheet.set_dataset_value(MASTER_COLUMN, "licence", "CC-BY");
+        sheet.describe("licence", "Licence of the data");
+        assert_eq!(3, sheet.dataset_field("licence").unwrap());
+        assert_eq!("Licence of the data", sheet.cell(3, DESCRIPTION_COLUMN));
+
+        let coast = sheet.column_or_insert("coast");
+        assert_eq!(6, coast);
+        sheet.set_resource_value(coast, "waves.csv", "name", "Waves");
+        let rivers = sheet.column("rivers").unwrap();
+        sheet.set_resource_value(rivers, "rain.csv", "name", "Rain");
+
+        sheet.write(&path).unwrap();
+        let sheet = Sheet::read(&path).unwrap();
+
+        // the group without resource of the dataset is taken over
+        let group = sheet.group(coast, "waves.csv").unwrap();
+        assert_eq!(sheet.group(rivers, "flow.csv"), Some(group));
+        assert_eq!("Waves", sheet.cell(group + 1, coast));
+
+        let group = sheet.group(rivers, "rain.csv").unwrap();
+        assert_eq!(
+            sheet.group(sheet.column("lakes").unwrap(), "level.csv"),
+            Some(group)
+        );
+        assert_eq!("Rain", sheet.cell(group + 1, rivers));
+
+        assert_eq!("keep me", sheet.cell(2, 5));
+        assert_eq!("unknown", sheet.cell(6, 5));
+        assert_eq!("Multiline\ncomment", sheet.cell(2, DESCRIPTION_COLUMN));
+        assert!(sheet.rows().iter().all(|r| r.len() == 7));
+    }
+
+    #[test]
+    fn test_empty_sheet() {
+        let mut sheet = Sheet::default();
+        let column = sheet.column_or_insert("rivers");
+        assert_eq!(3, column);
+        sheet.set_dataset_value(column, "title", "Rivers");
+        sheet.set_resource_value(column, "flow.csv", "name", "Flow");
+
+        assert_eq!(Some(1), sheet.dataset_field("title"));
+        assert_eq!(Some(2), sheet.group(column, "flow.csv"));
+        assert_eq!(Some(3), sheet.resource_field(2, "name"));
+    }
+
+    #[test]
+    fn test_a1() {
+        assert_eq!("A1", Cell::new(0, 0).a1());
+        assert_eq!("C5", Cell::new(4, 2).to_string());
+        assert_eq!("Z2", Cell::new(1, 25).a1());
+        assert_eq!("AA3", Cell::new(2, 26).a1());
+        assert_eq!("BA1", Cell::new(0, 52).a1());
+    }
+
+    #[test]
+    fn test_metadata_sheet() {
+        let (_dir, path) = sheet();
+        let sheet = MetadataSheet::read(&path).unwrap();
+
+        assert_eq!(
+            Metadata::Object(serde_json::json!({"title": "Root", "custom": ""})),
+            sheet.master()
+        );
+        assert_eq!(
+            Metadata::Object(serde_json::json!({"title": "Lakes", "custom": "b"})),
+            sheet.dataset("lakes")
+        );
+        assert_eq!(
+            Metadata::Object(serde_json::json!({"name": "Level"})),
+            sheet.resource("lakes", "level.csv")
+        );
+        assert_eq!(Metadata::Empty, sheet.dataset("missing"));
+        assert_eq!(Metadata::Empty, sheet.resource("rivers", "level.csv"));
+
+        assert_eq!(Some(Cell::new(1, 2)), sheet.master_cell("title"));
+        assert_eq!("D3", sheet.dataset_cell("rivers", "custom").unwrap().a1());
+        assert_eq!(
+            "E8",
+            sheet
+                .resource_cell("lakes", "level.csv", "name")
+                .unwrap()
+                .a1()
+        );
+        assert_eq!(None, sheet.resource_cell("lakes", "level.csv", "extra"));
+        assert_eq!(
+            Some(Cell::new(5, 3)),
+            sheet.resource_cell("rivers", "flow.csv", "extra")
+        );
+    }
+
+    #[test]
+    fn test_parse_excel_csv() {
+        let mut content = b"\xEF\xBB\xBFDataset Field;Description;Master metadata;rivers\n\
+            title;\"Title; short\";Root;Caf\xE9 \x96 rivers\n\
+            notes;;\n\
+            name;;;a;b\n"
+            .to_vec();
+        content.extend_from_slice(b"tag_string;;;\"x,y\"\n");
+        let (sheet, diagnostics) = Sheet::parse(&content);
+
+        assert_eq!(b';', sheet.delimiter);
+        assert_eq!("Dataset Field", sheet.cell(0, FIELD_COLUMN));
+        assert_eq!("Title; short", sheet.cell(1, DESCRIPTION_COLUMN));
+        assert_eq!("Caf\u{E9} \u{2013} rivers", sheet.cell(1, 3));
+        assert_eq!(3, sheet.rows()[2].len());
+        assert_eq!("x,y", sheet.cell(4, 3));
+        assert_eq!(
+            vec![
+                Diagnostic::new(
+                    Some(2),
+                    "File is not UTF-8 encoded, it is read as Windows-1252"
+                ),
+                Diagnostic::new(
+                    Some(4),
+                    "Row has 5 cells, but the header has only 4 columns"
+                ),
+            ],
+            diagnostics
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.csv");
+        sheet.write(&path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.starts_with(b"Dataset Field;Description;Master metadata;rivers;\n"));
+        assert!(written.ends_with(
+            b"title;\"Title; short\";Root;Caf\xE9 \x96 rivers;\n\
+            notes;;;;\n\
+            name;;;a;b\n\
+            tag_string;;;x,y;\n"
+        ));
+        assert_eq!(
+            "Caf\u{E9} \u{2013} rivers",
+            Sheet::read(&path).unwrap().cell(1, 3)
+        );
+
+        // Text that Windows-1252 cannot represent is written as UTF-8.
+        let mut sheet = Sheet::read(&path).unwrap();
+        sheet.set_cell(1, 3, "\u{2248} rivers");
+        sheet.write(&path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        assert!(written.starts_with(BOM));
+        assert_eq!("\u{2248} rivers", Sheet::read(&path).unwrap().cell(1, 3));
+    }
+
+    #[test]
+    fn test_keep_bom() {
+        let (_dir, path) = sheet();
+        std::fs::write(&path, [BOM, SHEET.as_bytes()].concat()).unwrap();
+        Sheet::read(&path).unwrap().write(&path).unwrap();
+        assert_eq!(
+            [BOM, SHEET.as_bytes()].concat(),
+            std::fs::read(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_load_diagnostics() {
+        let (dir, path) = sheet();
+        assert!(MetadataSheet::load(&path).diagnostics().is_empty());
+
+        let path = dir.path().join("metadata.xlsx");
+        std::fs::write(&path, SHEET).unwrap();
+        let sheet = MetadataSheet::load(&path);
+        assert_eq!(Metadata::Empty, sheet.dataset("rivers"));
+        assert_eq!(1, sheet.diagnostics().len());
+        assert_eq!(None, sheet.diagnostics()[0].line);
+    }
+}
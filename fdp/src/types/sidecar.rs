@@ -0,0 +1,228 @@
+//! TOML sidecars with metadata of the single dataset or resource.
+//!
+//! Dataset folder may contain `metadata.toml` with the dataset metadata and
+//! `<file>.toml` next to every resource `<file>`. Fields of the sidecar take
+//! precedence over the same fields in metadata.csv, so the sheet can keep
+//! the shared values while sidecars override them for specific items.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use super::Metadata;
+use crate::FdpError;
+
+pub const EXTENSION: &str = "toml";
+/// Sidecar of the dataset inside its folder.
+pub const DATASET_SIDECAR: &str = "metadata.toml";
+
+pub fn dataset_path(folder: &Path) -> PathBuf {
+    folder.join(DATASET_SIDECAR)
+}
+
+pub fn resource_path(folder: &Path, name: &str) -> PathBuf {
+    folder.join(format!("{}.{}", name, EXTENSION))
+}
+
+impl From<toml::de::Error> for FdpError {
+    fn from(source: toml::de::Error) -> Self {
+        Self::Csv(source.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for FdpError {
+    fn from(source: toml::ser::Error) -> Self {
+        Self::Csv(source.to_string())
+    }
+}
+
+fn to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn to_toml(value: &Value) -> crate::Result<toml::Value> {
+    Ok(match value {
+        Value::Null => toml::Value::String(String::new()),
+        other => toml::Value::try_from(other)?,
+    })
+}
+
+/// Fields of the sidecar, or `None` if it does not exist.
+pub fn read(path: &Path) -> crate::Result<Option<Map<String, Value>>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let table: toml::value::Table = toml::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(Some(
+        table
+            .into_iter()
+            .map(|(key, value)| (key, to_json(value)))
+            .collect(),
+    ))
+}
+
+/// Metadata of the sidecar. Broken sidecar is reported and ignored. Empty
+/// strings are dropped, so they don't override values of the sheet.
+pub fn load(path: &Path) -> Metadata {
+    match read(path) {
+        Ok(Some(fields)) => Metadata::Object(Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, value)| !matches!(value, Value::String(s) if s.is_empty()))
+                .collect(),
+        )),
+        Ok(None) => Metadata::Empty,
+        Err(err) => {
+            log::error!("Cannot read {}: {}", path.display(), err);
+            Metadata::Empty
+        }
+    }
+}
+
+/// Metadata of the sheet with fields of the sidecar on top.
+pub fn merge(sheet: Metadata, sidecar: Metadata) -> Metadata {
+    match (sheet, sidecar) {
+        (sheet, Metadata::Empty) => sheet,
+        (Metadata::Object(Value::Object(mut fields)), Metadata::Object(Value::Object(patch))) => {
+            fields.extend(patch);
+            Metadata::Object(Value::Object(fields))
+        }
+        (_, sidecar) => sidecar,
+    }
+}
+
+/// Content of the sidecar. Known fields go first, in the given order, and
+/// are preceded by their descriptions. Missing known fields are commented
+/// out, so they don't override values of metadata.csv.
+pub fn template(
+    fields: &Map<String, Value>,
+    order: &[&str],
+    comments: &HashMap<String, Vec<String>>,
+) -> crate::Result<String> {
+    let extra = fields.keys().filter(|key| !order.contains(&key.as_str()));
+    let mut content = String::new();
+    for key in order.iter().copied().chain(extra.map(String::as_str)) {
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        for line in comments.get(key).into_iter().flatten() {
+            content.push_str(format!("# {}", line).trim_end());
+            content.push('\n');
+        }
+        let mut entry = toml::value::Table::new();
+        match fields.get(key) {
+            Some(value) => {
+                entry.insert(key.into(), to_toml(value)?);
+            }
+            None => {
+                entry.insert(key.into(), toml::Value::String(String::new()));
+                content.push_str("# ");
+            }
+        }
+        content.push_str(&toml::to_string_pretty(&entry)?);
+    }
+    Ok(content)
+}
+
+/// Write the sidecar from the template, replacing the file only after the
+/// content is ready.
+pub fn write(
+    path: &Path,
+    fields: &Map<String, Value>,
+    order: &[&str],
+    comments: &HashMap<String, Vec<String>>,
+) -> crate::Result<()> {
+    let content = template(fields, order, comments)?;
+    let tmp = path.with_extension("toml.tmp");
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn test_template_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = resource_path(dir.path(), "flow.csv");
+        let comments = HashMap::from([("name".into(), vec!["Name of the resource".into()])]);
+        let fields = json!({
+            "description": "Roses are red\nViolets are blue",
+            "size": 10,
+            "name": "Flow",
+        });
+        let fields = fields.as_object().unwrap();
+
+        write(&path, fields, &["name", "description", "format"], &comments).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Name of the resource\nname = 'Flow'\n"));
+        assert!(content.contains("\n# format = ''\n"));
+        assert_eq!(
+            json!({
+                "name": "Flow",
+                "description": "Roses are red\nViolets are blue",
+                "size": 10,
+            }),
+            Value::Object(read(&path).unwrap().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_read_typed_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dataset_path(dir.path());
+        assert_eq!(None, read(&path).unwrap());
+        assert_eq!(Metadata::Empty, load(&path));
+
+        std::fs::write(
+            &path,
+            "dataset_type = 3\npublication_date = 2022-11-24\ntags = [\"a\", \"b\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            Metadata::Object(json!({
+                "dataset_type": 3,
+                "publication_date": "2022-11-24",
+                "tags": ["a", "b"],
+            })),
+            load(&path)
+        );
+
+        std::fs::write(&path, "title = ''\nnotes = 'Notes'\n").unwrap();
+        assert_eq!(Metadata::Object(json!({"notes": "Notes"})), load(&path));
+
+        std::fs::write(&path, "title = \n").unwrap();
+        assert!(matches!(read(&path), Err(FdpError::Csv(_))));
+        assert_eq!(Metadata::Empty, load(&path));
+    }
+
+    #[test]
+    fn test_merge() {
+        let sheet = Metadata::Object(json!({"title": "Sheet", "notes": "Notes"}));
+        let sidecar = Metadata::Object(json!({"title": "Sidecar"}));
+        assert_eq!(
+            Metadata::Object(json!({"title": "Sidecar", "notes": "Notes"})),
+            merge(sheet.clone(), sidecar.clone())
+        );
+        assert_eq!(sheet.clone(), merge(sheet, Metadata::Empty));
+        assert_eq!(sidecar.clone(), merge(Metadata::Empty, sidecar));
+    }
+}
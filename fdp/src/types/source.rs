@@ -1,11 +1,15 @@
 use csv;
 
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+
+use super::format::{self, Suggestion};
+use super::sheet::{Diagnostic, MetadataSheet, Sheet, DESCRIPTION_COLUMN};
+use super::sidecar;
+use crate::schema;
+use serde_json::Value;
 
 use std::collections::HashMap;
-use std::ops::Index;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{
     ffi::OsStr,
     fs::{self, DirEntry},
@@ -13,6 +17,17 @@ use std::{
 
 const METADATA_EXT: &str = "csv";
 const METADATA_FILENAME: &str = "metadata.csv";
+const METADATA_XLSX: &str = "metadata.xlsx";
+
+/// metadata.xlsx of the directory if it exists, metadata.csv otherwise.
+fn metadata_file(dir: &Path) -> PathBuf {
+    let xlsx = dir.join(METADATA_XLSX);
+    if xlsx.is_file() {
+        xlsx
+    } else {
+        dir.join(METADATA_FILENAME)
+    }
+}
 
 #[derive(Debug)]
 pub enum MetadataContent<'a> {
@@ -20,124 +35,18 @@ pub enum MetadataContent<'a> {
     Dataset(&'a str),
     Resource(&'a OsStr, &'a str),
 }
+/// Help text of the dataset fields from the [`schema`](crate::schema).
 pub fn dataset_comments() -> HashMap<String, Vec<String>> {
-    HashMap::from([
-        ("first".into(), vec!["First field".into(), "---".into()]),
-        (
-            "dataset_type".into(),
-            vec![
-                "A number between 1 and 18(inclusive)".into(),
-                "Allowed values and their corresponding types:".into(),
-                "1: Dataset handover checklist and description".into(),
-                "2: Report PDFs - all volumes".into(),
-                "3: Completed NSW Flood Database Template".into(),
-                "4: Spatial flood layers (post processed layers - not direct model outputs)".into(),
-                "5: Collected Data".into(),
-                "6: Hydrological, Hydraulic and flood damage model input files".into(),
-                "7: Hydrological, Hydraulic and flood damage pre-processed model output files".into(),
-                "8: Hydraulic modelling post processed files for AVIs".into(),
-                "9: Base information on buildings".into(),
-                "10: Survey Information – all required".into(),
-                "11: Lidar".into(),
-                "12: Aerial Imagery".into(),
-                "13: Emergency Response Planning".into(),
-                "14: Land use planning".into(),
-                "15: Management options".into(),
-                "16: Plans for works".into(),
-                "17: All Other Required Data".into(),
-                "18: Community Consultation".into(),
-                ],
-        ),
-        ("title".into(), vec!["Provide a clear and unambiguous title for your Flood Dataset".into()]),
-        (
-            "name".into(),
-            vec!["Unique name(URL) of the dataset".into(),
-            "Only the following characters are allowed:".into(),
-            "  * lowercased letters".into(),
-            "  * digits".into(),
-            "  * hyphens(-) and underscores(_)".into(),
-            "In addition, name must be no longer than 100 characters(and at least 2 characters)".into(),
-            ],
-        ),
-        (
-            "notes".into(),
-            vec![
-                "Desctiption. For long descriptions use three quotation marks:".into(),
-                "notes = \"\"\"Roses are red".into(),
-                "Violets are blue\"\"\"".into(),
-            ],
-        ),
-        (
-            "publication_date".into(),
-            vec![
-                "Date in YYYY-MM-DD format: 2022-11-24".into(),
-                " (note this is not a native excel format so user will need to include a apostrophe (') before the date)".into()
-                ],
-        ),
-        ("tag_string".into(), vec!["Comma-separated tags of key words".into()]),
-        ("spatial_data".into(), vec!["Either 'yes' or 'no'".into()]),
-        ("capture_method".into(), vec![
-            "The method by which the spatial data was created; digitised and/or exported from a model".into(),
-            "This field is required if `spatial_data` is set to `yes`. Otherwise this field must be empty".into(),
-        ]),
-        ("data_status".into(), vec![
-            "Currency status of the data and other artefacts produced by the Flood Project. One of:".into(),
-            "  * completed".into(),
-            "    - Completed - production of the data has been completed".into(),
-            "  * retired".into(),
-            "    - Retired - item is no longer recommended for use. It has not been superseded by another item".into(),
-            "  * superseded".into(),
-            "    - Superseded - data for the whole geographic extent has been replaced by new data".into(),
-            "  * partiallySuperseded".into(),
-            "    - Partially Superseded - data for part of the geographic extent has been replaced by new data".into(),
-            "  * deprecated".into(),
-            "    - Deprecated - resource superseded and will become obsolete, use only for historical purposes".into(),
-            "  * draft".into(),
-            "    - Draft - this flood project is in the draft stage of development. It is NOT final.".into(),
-            ]),
-        ("license_id".into(), vec![
-            "ID of the license. One of:".into(),
-            " cc-by".into(),
-            "    - Creative Commons Attribution 4.0".into(),
-            " oeh".into(),
-            "    - DPIE Licence".into(),
-            " 3rd".into(),
-            "    - 3rd party licence".into(),
-            " internal".into(),
-            "    - Internal use only".into(),
-            ]),
-        (
-            "dataset_status".into(),
-            vec!["One of: final, draft or updated".into()],
-        ),
-        (
-            "update_freq".into(),
-            vec!["One of: daily, weekly, monthly, quarterly, yearly, as_required".into()],
-        ),
-        ("author".into(), vec!["The name of the author".into()]),
-        ("data_comment".into(), vec!["Comment to the dataset".into()]),
-        (
-            "access_level".into(),
-            vec!["One of: open, registered, internal, restricted".into()],
-        ),
-    ])
+    schema::comments(&schema::current().dataset)
+}
+
+/// Allowed values of the enumerated dataset fields.
+pub fn dataset_choices() -> HashMap<String, Vec<String>> {
+    schema::choices(&schema::current().dataset)
 }
 
 pub fn resource_comments() -> HashMap<String, Vec<String>> {
-    HashMap::from([
-        (
-            "name".into(),
-            vec!["Easily understood filename of the resource".into()],
-        ),
-        (
-            "description".into(),
-            vec![
-                "Desctiption. For long descriptions use three quotation marks:".into(),
-                "description = \"\"\"Roses are red".into(),
-                "Violets are blue\"\"\"".into(),
-            ],
-        ),
-    ])
+    schema::comments(&schema::current().resource)
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -145,6 +54,12 @@ pub struct Source {
     pub path: PathBuf,
     pub metadata: Metadata,
     pub datasets: Vec<Dataset>,
+    /// metadata.xlsx or metadata.csv, the one used for the metadata.
+    #[serde(default)]
+    pub metadata_path: PathBuf,
+    /// Problems found in metadata.csv and in the sidecars.
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Source {
@@ -152,12 +67,26 @@ impl Source {
         let path = PathBuf::from(path.as_ref());
         if path.is_dir() {
             let mut source = Source {
+                metadata_path: metadata_file(&path),
                 path,
                 metadata: Metadata::Empty,
                 datasets: Vec::new(),
+                diagnostics: Vec::new(),
             };
-            source.refresh_metadata();
-            source.gather_datasets();
+            let sheet = MetadataSheet::load(source.metadata_path());
+            source.metadata = sheet.master();
+            source.diagnostics = sheet.diagnostics().to_vec();
+            if source.metadata_path.ends_with(METADATA_XLSX)
+                && source.path.join(METADATA_FILENAME).is_file()
+            {
+                source.diagnostics.push(Diagnostic::in_file(
+                    METADATA_FILENAME,
+                    format!("Ignored, because {} is used instead", METADATA_XLSX),
+                ));
+            }
+            source.gather_datasets(&sheet);
+            let broken = source.broken_sidecars();
+            source.diagnostics.extend(broken);
             Some(source)
         } else {
             None
@@ -165,16 +94,26 @@ impl Source {
     }
 
     pub fn metadata_path(&self) -> PathBuf {
-        let mut path = self.path.clone();
-        path.push(METADATA_FILENAME);
-        path
-    }
-
-    fn refresh_metadata(&mut self) {
-        self.metadata = Metadata::for_source(&self.metadata_path());
-    }
-
-    fn gather_datasets(&mut self) {
+        self.metadata_path.clone()
+    }
+
+    /// Sidecars that cannot be read and are ignored.
+    fn broken_sidecars(&self) -> Vec<Diagnostic> {
+        self.datasets
+            .iter()
+            .flat_map(|d| {
+                let resources = d.resources.iter().map(Resource::sidecar_path);
+                std::iter::once(d.sidecar_path()).chain(resources)
+            })
+            .filter_map(|path| {
+                let err = sidecar::read(&path).err()?;
+                let file = path.strip_prefix(&self.path).unwrap_or(&path);
+                Some(Diagnostic::in_file(file.to_string_lossy(), err.to_string()))
+            })
+            .collect()
+    }
+
+    fn gather_datasets(&mut self, sheet: &MetadataSheet) {
         let path = &self.path;
         self.datasets = match fs::read_dir(path) {
             Err(err) => {
@@ -184,7 +123,7 @@ impl Source {
             Ok(entries) => entries
                 .filter_map(|r| r.ok())
                 .filter_map(|e| entry_tuples(e, false))
-                .filter_map(|(path, name)| Dataset::new(&path, &name))
+                .filter_map(|(path, name)| Dataset::from_sheet(&path, &name, sheet))
                 .collect(),
         };
     }
@@ -193,7 +132,7 @@ impl Source {
         let mut path = self.path.clone();
         path.push(name);
         fs::create_dir(path)?;
-        self.gather_datasets();
+        self.gather_datasets(&MetadataSheet::load(self.metadata_path()));
         Ok(())
     }
 
@@ -204,6 +143,64 @@ impl Source {
     pub fn get_dataset(&self, name: &str) -> Option<&Dataset> {
         self.datasets.iter().find(|d| d.name == name)
     }
+
+    /// Set the [`Suggestion`] of every resource. Reading the files takes
+    /// time, so it's not done when the source is read.
+    pub fn detect_formats(&mut self) {
+        for resource in self
+            .datasets
+            .iter_mut()
+            .flat_map(|d| d.resources.iter_mut())
+        {
+            resource.suggestion = resource.detect_format();
+        }
+    }
+
+    /// Add a column for every dataset folder and a resource group for every
+    /// file to the metadata sheet, with `name` taken from the filename and
+    /// the detected `format` and `format_label`. Fields of the schema are
+    /// added where missing. Values that are already in the sheet are kept.
+    pub fn generate_template(&self) -> crate::Result<()> {
+        let path = self.metadata_path();
+        let mut sheet = if path.is_file() {
+            Sheet::read(&path)?
+        } else {
+            Sheet::with_header()
+        };
+        let schema = schema::current();
+        for field in &schema.dataset {
+            let row = sheet.dataset_field_or_insert(&field.name);
+            sheet.fill(row, DESCRIPTION_COLUMN, field.help.join("\n"));
+        }
+
+        let mut datasets: Vec<&Dataset> = self.datasets.iter().collect();
+        datasets.sort_by(|a, b| a.name.cmp(&b.name));
+        for dataset in datasets {
+            let column = sheet.column_or_insert(&dataset.name);
+            let mut resources: Vec<&Resource> = dataset.resources.iter().collect();
+            resources.sort_by(|a, b| a.name.cmp(&b.name));
+            for resource in resources {
+                let group = sheet.group_or_insert(column, &resource.name);
+                sheet.fill(group, DESCRIPTION_COLUMN, "Description");
+                for field in &schema.resource {
+                    let row = sheet.resource_field_or_insert(group, &field.name);
+                    sheet.fill(row, DESCRIPTION_COLUMN, field.help.join("\n"));
+                }
+
+                if let Some(stem) = Path::new(&resource.name).file_stem() {
+                    let row = sheet.resource_field_or_insert(group, "name");
+                    sheet.fill(row, column, stem.to_string_lossy());
+                }
+                if let Some(suggestion) = resource.detect_format() {
+                    let row = sheet.resource_field_or_insert(group, "format");
+                    sheet.fill(row, column, suggestion.format.as_str());
+                    let row = sheet.resource_field_or_insert(group, "format_label");
+                    sheet.fill(row, column, suggestion.format_label.as_str());
+                }
+            }
+        }
+        sheet.write(&path)
+    }
 }
 
 fn entry_tuples(e: DirEntry, resource: bool) -> Option<(String, String)> {
@@ -212,8 +209,9 @@ fn entry_tuples(e: DirEntry, resource: bool) -> Option<(String, String)> {
     let stem = path.file_stem()?;
     let source = path.parent()?;
     let base = source.join(stem);
+    let extension = path.extension().map(|e| e.to_ascii_lowercase());
     let is_metadata =
-        e.path().extension().map(|e| e.to_ascii_lowercase()) == Some(METADATA_EXT.into());
+        extension == Some(METADATA_EXT.into()) || extension == Some(sidecar::EXTENSION.into());
 
     if e.file_type().ok()?.is_dir() {
         if resource {
@@ -224,7 +222,7 @@ fn entry_tuples(e: DirEntry, resource: bool) -> Option<(String, String)> {
                 e.file_name().to_string_lossy().into(),
             ))
         }
-    } else if is_metadata && base.exists() {
+    } else if is_metadata && base.exists() || name == sidecar::DATASET_SIDECAR {
         None
     } else if resource {
         Some((
@@ -246,17 +244,23 @@ pub struct Dataset {
 
 impl Dataset {
     pub fn new(path: &str, name: &str) -> Option<Self> {
+        let sheet = MetadataSheet::load(metadata_file(Path::new(path)));
+        Self::from_sheet(path, name, &sheet)
+    }
+
+    /// Dataset with metadata from the already parsed sheet.
+    pub fn from_sheet(path: &str, name: &str, sheet: &MetadataSheet) -> Option<Self> {
         let path = PathBuf::from(path);
 
         if path.is_dir() {
+            let sidecar = sidecar::load(&sidecar::dataset_path(&path.join(name)));
             let mut dataset = Dataset {
                 name: name.to_owned(),
                 path,
-                metadata: Metadata::Empty,
+                metadata: sidecar::merge(sheet.dataset(name), sidecar),
                 resources: Vec::new(),
             };
-            dataset.refresh_metadata();
-            dataset.gather_resources();
+            dataset.gather_resources(sheet);
             Some(dataset)
         } else {
             None
@@ -264,13 +268,15 @@ impl Dataset {
     }
 
     pub fn metadata_path(&self) -> PathBuf {
-        self.path.join(METADATA_FILENAME)
+        metadata_file(&self.path)
     }
 
-    fn refresh_metadata(&mut self) {
-        self.metadata = Metadata::for_dataset(&self.metadata_path(), &self.name);
+    /// metadata.toml inside the folder of the dataset.
+    pub fn sidecar_path(&self) -> PathBuf {
+        sidecar::dataset_path(&self.path.join(&self.name))
     }
-    fn gather_resources(&mut self) {
+
+    fn gather_resources(&mut self, sheet: &MetadataSheet) {
         let mut path = self.path.clone();
         path.push(&self.name);
 
@@ -282,7 +288,7 @@ impl Dataset {
             Ok(entries) => entries
                 .filter_map(|r| r.ok())
                 .filter_map(|e| entry_tuples(e, true))
-                .filter_map(|(path, name)| Resource::new(&path, &name))
+                .filter_map(|(path, name)| Resource::from_sheet(&path, &name, sheet))
                 .collect(),
         };
     }
@@ -297,7 +303,7 @@ impl Dataset {
             ))
         } else {
             fs::write(path, "")?;
-            self.gather_resources();
+            self.gather_resources(&MetadataSheet::load(self.metadata_path()));
             Ok(())
         }
     }
@@ -309,25 +315,38 @@ impl Dataset {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Resource {
     pub path: PathBuf,
     pub name: String,
     pub metadata: Metadata,
     pub size: u64,
+    /// `format` and `format_label` detected from the file, used as defaults
+    /// when the resource has no metadata. Only set by
+    /// [`Source::detect_formats`].
+    #[serde(default)]
+    pub suggestion: Option<Suggestion>,
 }
 impl Resource {
     pub fn new(path: &str, name: &str) -> Option<Self> {
+        let parent = metadata_file(Path::new(path).parent()?);
+        Self::from_sheet(path, name, &MetadataSheet::load(parent))
+    }
+
+    /// Resource with metadata from the already parsed sheet.
+    pub fn from_sheet(path: &str, name: &str, sheet: &MetadataSheet) -> Option<Self> {
         let path = PathBuf::from(path);
 
         if path.is_dir() {
+            let dataset = path.file_name()?.to_string_lossy();
+            let sidecar = sidecar::load(&sidecar::resource_path(&path, name));
             let mut resource = Resource {
                 name: name.to_owned(),
+                metadata: sidecar::merge(sheet.resource(&dataset, name), sidecar),
                 path,
-                metadata: Metadata::Empty,
                 size: 0,
+                suggestion: None,
             };
-            resource.refresh_metadata();
             resource.size = resource.size();
             Some(resource)
         } else {
@@ -336,16 +355,58 @@ impl Resource {
     }
 
     pub fn metadata_path(&self) -> PathBuf {
-        let path = self.path.parent().unwrap();
-        path.join(METADATA_FILENAME)
+        metadata_file(self.path.parent().unwrap())
     }
 
-    fn refresh_metadata(&mut self) {
-        self.metadata = Metadata::for_resource(
-            &self.metadata_path(),
-            self.path.file_name().unwrap(),
-            &self.name,
-        );
+    /// `<file>.toml` next to the resource.
+    pub fn sidecar_path(&self) -> PathBuf {
+        sidecar::resource_path(&self.path, &self.name)
+    }
+
+    /// Format of the file, with the label limited to the choices of the
+    /// schema.
+    pub fn detect_format(&self) -> Option<Suggestion> {
+        let mut suggestion = format::detect(&self.path.join(&self.name))?;
+        let choices = schema::choices(&schema::current().resource);
+        if let Some(labels) = choices.get("format_label") {
+            if !labels.contains(&suggestion.format_label) {
+                suggestion.format_label = String::new();
+            }
+        }
+        Some(suggestion)
+    }
+
+    /// Metadata with blank `format` and `format_label` taken from the
+    /// content of the file. Resources without metadata get an object with
+    /// just the detected format.
+    pub fn metadata_with_format(&self) -> Metadata {
+        let mut fields = match &self.metadata {
+            Metadata::Object(Value::Object(fields)) => fields.clone(),
+            Metadata::Object(_) => return self.metadata.clone(),
+            Metadata::Empty => Default::default(),
+        };
+        let blank = |value: Option<&Value>| match value {
+            None | Some(Value::Null) => true,
+            Some(Value::String(s)) => s.is_empty(),
+            _ => false,
+        };
+        if !blank(fields.get("format")) && !blank(fields.get("format_label")) {
+            return self.metadata.clone();
+        }
+        if let Some(suggestion) = self.detect_format() {
+            for (field, value) in [
+                ("format", suggestion.format),
+                ("format_label", suggestion.format_label),
+            ] {
+                if blank(fields.get(field)) && !value.is_empty() {
+                    fields.insert(field.into(), value.into());
+                }
+            }
+        }
+        if fields.is_empty() {
+            return self.metadata.clone();
+        }
+        Metadata::Object(Value::Object(fields))
     }
 
     pub fn size(&self) -> u64 {
@@ -355,7 +416,7 @@ impl Resource {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum Metadata {
     Empty,
@@ -363,16 +424,23 @@ pub enum Metadata {
 }
 
 impl Metadata {
-    pub fn write<P: AsRef<OsStr>>(
-        &self,
-        path: &P,
-        _comments: HashMap<String, Vec<String>>,
-    ) -> Result<(), std::io::Error> {
+    /// Write the template of metadata.csv with the fields of the schema
+    /// and the values of the root metadata.
+    pub fn write<P: AsRef<OsStr>>(&self, path: &P) -> Result<(), std::io::Error> {
         match self {
             Metadata::Empty => fs::remove_file(path.as_ref()),
             Metadata::Object(v) => {
+                let schema = schema::current();
+                let empty = serde_json::Map::new();
+                let values = v.as_object().unwrap_or(&empty);
+                let text = |value: Option<&Value>| match value {
+                    Some(Value::String(s)) => s.clone(),
+                    None | Some(Value::Null) => String::new(),
+                    Some(other) => other.to_string(),
+                };
+
                 let mut wtr = csv::Writer::from_path(path.as_ref())?;
-                wtr.write_record(&[
+                wtr.write_record([
                     "Dataset Field",
                     "Description",
                     "Master metadata",
@@ -380,110 +448,29 @@ impl Metadata {
                     "foldername2",
                     "(include a column for every data set folder)",
                 ])?;
-                match v {
-                    Value::Object(v) => {
-                        for (k, v) in v {
-                            wtr.write_record(&[
-                                k,
-                                &_comments.get(k).unwrap_or(&vec!["".to_string()]).join("\n"),
-                                match v {
-                                    Value::String(s) => s,
-                                    _ => "",
-                                },
-                                "",
-                                "",
-                                "",
-                            ])?;
-                        }
+                let known = schema::names(&schema.dataset);
+                let extra = values.keys().filter(|k| !known.contains(&k.as_str()));
+                let comments = schema::comments(&schema.dataset);
+                for name in known.iter().copied().chain(extra.map(String::as_str)) {
+                    let help = comments.get(name).map(|c| c.join("\n")).unwrap_or_default();
+                    wtr.write_record([name, &help, &text(values.get(name)), "", "", ""])?;
+                }
+                // the second dataset has only one resource
+                for second in ["actual filename (incuding extension)", ""] {
+                    wtr.write_record([
+                        "Resource Field",
+                        "Description",
+                        "",
+                        "actual filename (incuding extension)",
+                        second,
+                        "",
+                    ])?;
+                    for field in &schema.resource {
+                        wtr.write_record([&field.name, &field.help.join("\n"), "", "", "", ""])?;
                     }
-                    _ => (),
                 }
-                wtr.write_record(&[
-                    "Resource Field",
-                    "Description",
-                    "",
-                    "actual filename (incuding extension)",
-                    "actual filename (incuding extension)",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "name",
-                    "Easily understood filename of the resource",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "description",
-                    "Desctiption. For long descriptions use three quotation marks:\n\
-                    description = \"\"\"Roses are red\n\
-                    Violets are blue\"\"\"",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "format",
-                    "File extension, e.g XML, CSV, ZIP",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                 wtr.write_record(&[
-                    "format_label",
-                    "Label for Resource format. One of:\n  * Word\n  * Excel\n  * Point\n  * Text\n  * CSV\n  * PDF\n  * Zipped\n  * Image\n  * GIS\n  * Model\n  * MISC",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                 wtr.write_record(&[
-                    "Resource Field",
-                    "Description",
-                    "",
-                    "actual filename (incuding extension)",
-                    "",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "name",
-                    "Easily understood filename of the resource",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "description",
-                    "Desctiption. For long descriptions use three quotation marks:\n\
-                    description = \"\"\"Roses are red\n\
-                    Violets are blue\"\"\"",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                wtr.write_record(&[
-                    "format",
-                    "File extension, e.g XML, CSV, ZIP",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
-                 wtr.write_record(&[
-                    "format_label",
-                    "Label for Resource format. One of:\n  * Word\n  * Excel\n  * Point\n  * Text\n  * CSV\n  * PDF\n  * Zipped\n  * Image\n  * GIS\n  * Model\n  * MISC",
-                    "",
-                    "",
-                    "",
-                    "",
-                ])?;
 
-                wtr.write_record(&[
+                wtr.write_record([
                     "repeat 'Resource Field', 'name', 'description' rows as a group for each resource to be uploaded to a dataset",
                     "",
                     "",
@@ -502,90 +489,25 @@ impl Metadata {
         }
     }
 
-    fn for_source(path: &PathBuf) -> Self {
-        Metadata::extract_key_from_csv(path, MetadataContent::Master, true)
-    }
-
-    fn for_dataset(path: &PathBuf, name: &str) -> Self {
-        Metadata::extract_key_from_csv(path, MetadataContent::Dataset(name), false)
+    /// Root metadata from metadata.csv. Use [`MetadataSheet`] to read
+    /// metadata of multiple items from the same file.
+    pub fn for_source(path: &PathBuf) -> Self {
+        MetadataSheet::load(path).master()
     }
 
-    fn for_resource(path: &PathBuf, dataset: &OsStr, name: &str) -> Self {
-        Metadata::extract_key_from_csv(path, MetadataContent::Resource(dataset, name), false)
+    pub fn for_dataset(path: &PathBuf, name: &str) -> Self {
+        MetadataSheet::load(path).dataset(name)
     }
 
-    fn extract_key_from_csv(path: &PathBuf, key: MetadataContent, include_empty: bool) -> Self {
-        if path.is_file() {
-            let mut reader = csv::Reader::from_path(path).unwrap();
-
-            let header = match reader.headers() {
-                Ok(header) => header.clone(),
-                Err(_) => return Self::Empty,
-            };
-            let records = reader.records();
-
-            let idx = match key {
-                MetadataContent::Master => Some(2),
-                MetadataContent::Dataset(name) => header.iter().position(|s| s == name),
-                MetadataContent::Resource(name, _) => header.iter().position(|s| s == name),
-            };
-
-            match idx {
-                None => Self::Empty,
-                Some(idx) => {
-                    let iter = records
-                        .map(|record| record.unwrap())
-                        .filter(|record| record.len() > idx)
-                        .map(|record| (record.index(0).to_string(), record.index(idx).to_string()));
-
-                    let pairs = match key {
-                        MetadataContent::Master | MetadataContent::Dataset(..) => iter
-                            .take_while(|(key, _)| *key != "Resource Field")
-                            .collect::<Vec<(String, String)>>(),
-                        MetadataContent::Resource(_, name) => iter
-                            .skip_while(|(key, value)| *key != "Resource Field" || *value != name)
-                            .skip(1)
-                            .take_while(|(key, _)| *key != "Resource Field")
-                            .collect::<Vec<(String, String)>>(),
-                    };
-
-                    if pairs.len() == 0 {
-                        return Self::Empty;
-                    }
-
-                    let data = pairs
-                        .iter()
-                        .filter(|(_, v)| *v != "" || include_empty)
-                        .map(|(k, v)| (k.to_string(), Value::from(v.to_string())))
-                        .collect();
-
-                    Self::Object(Value::Object(data))
-                }
-            }
-        } else {
-            Self::Empty
-        }
+    pub fn for_resource(path: &PathBuf, dataset: &OsStr, name: &str) -> Self {
+        MetadataSheet::load(path).resource(&dataset.to_string_lossy(), name)
     }
 }
 
 impl Default for Metadata {
+    /// Default values of the dataset fields.
     fn default() -> Self {
-        Self::Object(json!({
-            "title": "Title",
-            "name": "dataset",
-            "access_level": "open",
-            "dataset_status": "draft",
-            "publication_date": "2022-01-01",
-            "author": "Name",
-            "theme": "Emergency Management",
-            "language": "en",
-            "license_id": "unspecified",
-            "notes": "Description",
-            "dataset_type": "1",
-            "update_freq": "daily",
-            "flood_studies": "<id>",
-            "owner_org": "<id>"
-        }))
+        Self::Object(Value::Object(schema::defaults(&schema::current().dataset)))
     }
 }
 
@@ -615,6 +537,24 @@ mod tests {
         assert_eq!(0, source.datasets.len());
     }
 
+    #[test]
+    fn test_format_of_resource_without_metadata() {
+        let dir = Dir::new("/tmp/fdp/rust/test/test_format_of_resource_without_metadata".into());
+        fs::write(format!("{}/photo.png", dir.path), b"\x89PNG\r\n\x1A\n\0\0").unwrap();
+        let resource = Resource {
+            path: PathBuf::from(&dir.path),
+            name: "photo.png".into(),
+            metadata: Metadata::Empty,
+            size: 10,
+            suggestion: None,
+        };
+
+        match resource.metadata_with_format() {
+            Metadata::Object(metadata) => assert_eq!(metadata["format"], "PNG"),
+            Metadata::Empty => panic!("format is not filled"),
+        }
+    }
+
     #[test]
     fn test_source_with_dir_only_dataset() {
         let dir = Dir::new("/tmp/fdp/rust/test/test_source_with_dir_only_dataset".into());
@@ -742,9 +682,9 @@ name,,,res-1-2,res-2-2
         );
 
         let metadata = Metadata::for_source(&path);
-        metadata.write(&path, crate::types::dataset_comments());
+        metadata.write(&path).unwrap();
 
-        dbg!(fs::read_to_string(&path));
+        dbg!(fs::read_to_string(&path).unwrap());
         dbg!(path);
     }
 }
@@ -0,0 +1,200 @@
+//! metadata.xlsx with the same layout as metadata.csv.
+//!
+//! Every cell is written as text, so Excel keeps dates and identifiers as
+//! they are typed. Enumerated dataset fields get a dropdown with allowed
+//! values and field names get the description as a note.
+use std::path::Path;
+
+use calamine::{open_workbook, Data, Reader, Xlsx};
+use rust_xlsxwriter::{DataValidation, Format, Note, Workbook, XlsxError};
+
+use super::sheet::{DESCRIPTION_COLUMN, FIELD_COLUMN, MASTER_COLUMN, RESOURCE_FIELD};
+use super::{dataset_choices, dataset_comments, resource_comments};
+use crate::FdpError;
+
+pub const EXTENSION: &str = "xlsx";
+
+impl From<calamine::XlsxError> for FdpError {
+    fn from(source: calamine::XlsxError) -> Self {
+        Self::Csv(source.to_string())
+    }
+}
+
+impl From<XlsxError> for FdpError {
+    fn from(source: XlsxError) -> Self {
+        Self::Csv(source.to_string())
+    }
+}
+
+/// Whether the metadata file is the workbook.
+pub fn is_xlsx(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case(EXTENSION))
+        .unwrap_or_default()
+}
+
+/// Text of the cell as it would appear in metadata.csv.
+fn cell_text(data: &Data) -> String {
+    match data {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => (*f as i64).to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) => {
+            let (year, month, day, hour, minute, second, _) = dt.to_ymd_hms_milli();
+            if (hour, minute, second) == (0, 0, 0) {
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            } else {
+                format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year, month, day, hour, minute, second
+                )
+            }
+        }
+    }
+}
+
+/// Rows of the first worksheet. Cells before the used range are empty.
+pub fn read(path: &Path) -> crate::Result<Vec<Vec<String>>> {
+    let mut workbook: Xlsx<_> = open_workbook(path)?;
+    let range = match workbook.worksheet_range_at(0) {
+        Some(range) => range?,
+        None => return Ok(Vec::new()),
+    };
+    let (top, left) = match range.start() {
+        Some((row, column)) => (row as usize, column as usize),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut rows = vec![Vec::new(); top];
+    for row in range.rows() {
+        let cells = std::iter::repeat_n(String::new(), left)
+            .chain(row.iter().map(cell_text))
+            .collect();
+        rows.push(cells);
+    }
+    Ok(rows)
+}
+
+/// Write the rows as text cells with dropdowns and notes for known fields.
+pub fn write(path: &Path, rows: &[Vec<String>]) -> crate::Result<()> {
+    let width = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let text = Format::new().set_num_format("@");
+    let header = Format::new().set_num_format("@").set_bold();
+    let dataset_comments = dataset_comments();
+    let resource_comments = resource_comments();
+    let choices = dataset_choices();
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    for column in 0..width.max(MASTER_COLUMN + 1) {
+        worksheet.set_column_format(column as u16, &text)?;
+    }
+    worksheet.set_column_width(FIELD_COLUMN as u16, 24)?;
+    worksheet.set_column_width(DESCRIPTION_COLUMN as u16, 40)?;
+
+    let mut in_resource = false;
+    for (row, cells) in rows.iter().enumerate() {
+        let field = cells
+            .get(FIELD_COLUMN)
+            .map(String::as_str)
+            .unwrap_or_default();
+        let is_group = row > 0 && field == RESOURCE_FIELD;
+        in_resource |= is_group;
+
+        let format = if row == 0 || is_group { &header } else { &text };
+        for (column, value) in cells.iter().enumerate() {
+            if !value.is_empty() {
+                worksheet.write_string_with_format(row as u32, column as u16, value, format)?;
+            }
+        }
+        if row == 0 || is_group {
+            continue;
+        }
+
+        let comments = if in_resource {
+            &resource_comments
+        } else {
+            &dataset_comments
+        };
+        if let Some(comment) = comments.get(field) {
+            let note = Note::new(comment.join("\n")).set_width(320);
+            worksheet.insert_note(row as u32, FIELD_COLUMN as u16, &note)?;
+        }
+        if let Some(values) = choices.get(field).filter(|_| !in_resource) {
+            let validation = DataValidation::new().allow_list_strings(values)?;
+            let last = width.max(MASTER_COLUMN + 1) - 1;
+            worksheet.add_data_validation(
+                row as u32,
+                MASTER_COLUMN as u16,
+                row as u32,
+                last as u16,
+                &validation,
+            )?;
+        }
+    }
+
+    let tmp = path.with_extension("xlsx.tmp");
+    workbook.save(&tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|r| r.iter().map(|c| c.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.xlsx");
+        let content = rows(&[
+            &["Dataset Field", "Description", "Master metadata", "rivers"],
+            &["dataset_type", "", "3", "05"],
+            &["publication_date", "", "2022-11-24", ""],
+            &["license_id", "", "cc-by", "internal"],
+            &["Resource Field", "Description", "", "flow.csv"],
+            &["name", "Name", "", "Flow"],
+        ]);
+
+        write(&path, &content).unwrap();
+        assert!(is_xlsx(&path));
+        assert_eq!(content, read(&path).unwrap());
+    }
+
+    #[test]
+    fn test_typed_cells() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metadata.xlsx");
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        worksheet.write_string(1, 1, "title").unwrap();
+        worksheet.write_number(1, 2, 3.0).unwrap();
+        worksheet.write_number(1, 3, 2.5).unwrap();
+        worksheet.write_boolean(2, 2, true).unwrap();
+        let date = rust_xlsxwriter::ExcelDateTime::from_ymd(2022, 11, 24).unwrap();
+        let format = Format::new().set_num_format("yyyy-mm-dd");
+        worksheet
+            .write_datetime_with_format(2, 3, &date, &format)
+            .unwrap();
+        workbook.save(&path).unwrap();
+
+        assert_eq!(
+            rows(&[
+                &[],
+                &["", "title", "3", "2.5"],
+                &["", "", "true", "2022-11-24"],
+            ]),
+            read(&path).unwrap()
+        );
+    }
+}
@@ -0,0 +1,763 @@
+//! Upload of the whole resource, driven by the backend.
+//!
+//! [`Uploader`] registers the upload(or picks up the one that was started
+//! earlier), sends every remaining part of the file and completes the upload.
+//! Transient failures of individual parts are retried with exponential
+//! backoff. Progress is reported through the event listener and the process
+//! can be paused, resumed or cancelled via [`UploadControl`]. Registered
+//! uploads and sent parts are recorded in the [`Journal`] of the source.
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::stream::{self, TryStreamExt};
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::action::{find_resource, FdpClient};
+use crate::chunk::{self, ChunkSizer, DEFAULT_CHUNK_SIZE};
+use crate::journal::{FileState, Journal, JournalEntry};
+use crate::scheduler::Limiter;
+use crate::types::{ProgressedUpload, RegisteredUpload};
+use crate::FdpError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Run,
+    Pause,
+    Cancel,
+}
+
+/// Handle for pausing, resuming and cancelling the upload. Clones control the
+/// same upload.
+#[derive(Debug, Clone)]
+pub struct UploadControl {
+    tx: Arc<watch::Sender<Command>>,
+    rx: watch::Receiver<Command>,
+}
+
+impl Default for UploadControl {
+    fn default() -> Self {
+        let (tx, rx) = watch::channel(Command::Run);
+        Self {
+            tx: Arc::new(tx),
+            rx,
+        }
+    }
+}
+
+impl UploadControl {
+    /// Stop before the next part. The part that is being sent is finished.
+    pub fn pause(&self) {
+        self.set(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.set(Command::Run);
+    }
+
+    pub fn cancel(&self) {
+        self.set(Command::Cancel);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.rx.borrow() == Command::Pause
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.rx.borrow() == Command::Cancel
+    }
+
+    fn set(&self, command: Command) {
+        // receiver is owned by the control itself, so sending never fails.
+        let _ = self.tx.send(command);
+    }
+
+    /// Wait while paused and report cancellation.
+    async fn proceed(&self) -> crate::Result<()> {
+        let mut rx = self.rx.clone();
+        loop {
+            let command = *rx.borrow();
+            match command {
+                Command::Run => return Ok(()),
+                Command::Cancel => return Err(FdpError::Cancelled),
+                Command::Pause => {
+                    if rx.changed().await.is_err() {
+                        return Err(FdpError::Cancelled);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// How many times and how often failed requests are repeated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub attempts: u32,
+    /// Delay before the first retry. Every next delay is doubled.
+    pub delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay after the given(1-based) failed attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UploadStatus {
+    Registered,
+    Uploading {
+        part: u64,
+    },
+    Retrying {
+        part: u64,
+        attempt: u32,
+        error: String,
+    },
+    Paused,
+    Completed,
+    Cancelled,
+    /// The upload stopped and won't be retried.
+    Failed {
+        error: String,
+    },
+}
+
+/// Progress notification sent to the listener of the [`Uploader`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UploadEvent {
+    pub dataset: String,
+    pub name: String,
+    pub size: u64,
+    pub bytes_uploaded: u64,
+    pub status: UploadStatus,
+}
+
+type Listener = Arc<dyn Fn(UploadEvent) + Send + Sync>;
+
+/// Uploads resources of the source located at `path`.
+pub struct Uploader<'a, C: FdpClient + ?Sized> {
+    client: &'a C,
+    path: String,
+    retry: RetryPolicy,
+    control: UploadControl,
+    listener: Option<Listener>,
+    limiter: Limiter,
+    parallel_parts: usize,
+    chunk_size: u64,
+    sizer: Option<ChunkSizer>,
+}
+
+impl<'a, C: FdpClient + ?Sized> Uploader<'a, C> {
+    pub fn new(client: &'a C, path: &str) -> Self {
+        Self {
+            client,
+            path: path.to_string(),
+            retry: RetryPolicy::default(),
+            control: UploadControl::default(),
+            listener: None,
+            limiter: Limiter::default(),
+            parallel_parts: 1,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            sizer: None,
+        }
+    }
+
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use the existing control handle instead of the new one.
+    pub fn control(mut self, control: UploadControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    pub fn on_event<F: Fn(UploadEvent) + Send + Sync + 'static>(mut self, listener: F) -> Self {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Share concurrency and bandwidth limits with other uploads.
+    pub fn limiter(mut self, limiter: Limiter) -> Self {
+        self.limiter = limiter;
+        self
+    }
+
+    /// Number of parts of the file that are sent simultaneously.
+    pub fn parallel_parts(mut self, parallel_parts: usize) -> Self {
+        self.parallel_parts = parallel_parts.max(1);
+        self
+    }
+
+    /// Chunk size requested for new uploads.
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Measure throughput of parts and request the chunk size suggested by
+    /// `sizer` for new uploads.
+    pub fn adaptive(mut self, sizer: ChunkSizer) -> Self {
+        self.sizer = Some(sizer);
+        self
+    }
+
+    pub fn handle(&self) -> UploadControl {
+        self.control.clone()
+    }
+
+    fn emit(&self, event: &Mutex<UploadEvent>, status: UploadStatus) {
+        let mut event = event.lock().unwrap();
+        event.status = status;
+        if let Some(listener) = &self.listener {
+            listener(event.clone());
+        }
+    }
+
+    async fn proceed(&self, event: &Mutex<UploadEvent>) -> crate::Result<()> {
+        if self.control.is_paused() {
+            self.emit(event, UploadStatus::Paused);
+        }
+        match self.control.proceed().await {
+            Err(err) => {
+                self.emit(event, UploadStatus::Cancelled);
+                Err(err)
+            }
+            ok => ok,
+        }
+    }
+
+    async fn with_retry<T, F, Fut>(
+        &self,
+        event: &Mutex<UploadEvent>,
+        part: u64,
+        call: F,
+    ) -> crate::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = crate::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(err) if err.is_transient() && attempt < self.retry.attempts => {
+                    log::warn!("Attempt #{} of part {} failed: {}", attempt, part, err);
+                    self.emit(
+                        event,
+                        UploadStatus::Retrying {
+                            part,
+                            attempt,
+                            error: err.to_string(),
+                        },
+                    );
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                    attempt += 1;
+                    self.proceed(event).await?;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Upload the resource, resuming from the last part known to the portal.
+    ///
+    /// Progress is recorded in the [`Journal`] of the source. Upload that was
+    /// registered earlier is not resumed if the file changed since then.
+    pub async fn upload(&self, dataset: &str, name: &str) -> crate::Result<ProgressedUpload> {
+        let event = Mutex::new(UploadEvent {
+            dataset: dataset.to_string(),
+            name: name.to_string(),
+            size: 0,
+            bytes_uploaded: 0,
+            status: UploadStatus::Registered,
+        });
+        let result = self.transfer(&event, dataset, name).await;
+        match &result {
+            Err(FdpError::Cancelled) | Ok(_) => {}
+            Err(err) => self.emit(
+                &event,
+                UploadStatus::Failed {
+                    error: err.to_string(),
+                },
+            ),
+        }
+        result
+    }
+
+    async fn transfer(
+        &self,
+        event: &Mutex<UploadEvent>,
+        dataset: &str,
+        name: &str,
+    ) -> crate::Result<ProgressedUpload> {
+        let resource = find_resource(&self.path, dataset, name)?;
+        let filepath = resource.path.join(&resource.name);
+        let file = FileState::of(&filepath)?;
+        let journal = Journal::new(&self.path);
+        event.lock().unwrap().size = file.size;
+
+        // parts may be sent out of order, so the journal is more reliable
+        // than the amount of bytes reported by the portal. Chunk size is
+        // taken from the existing upload when it's known.
+        let (chunk, sent): (u64, BTreeSet<u64>) = match self.client.show_upload(dataset, name).await
+        {
+            Ok(upload) if upload.data.completed => {
+                journal.record_completed(dataset, name)?;
+                event.lock().unwrap().bytes_uploaded = upload.data.bytes_uploaded;
+                self.emit(event, UploadStatus::Completed);
+                return Ok(upload);
+            }
+            Ok(upload) => match journal.get(dataset, name)? {
+                Some(entry) => {
+                    entry.verify(&file)?;
+                    (
+                        chunk::negotiated(upload.data.chunk_size, entry.chunk_size),
+                        entry.parts,
+                    )
+                }
+                None => {
+                    let chunk = chunk::negotiated(upload.data.chunk_size, DEFAULT_CHUNK_SIZE);
+                    journal.insert(JournalEntry::new(dataset, name, &upload.id, file, chunk))?;
+                    (chunk, (1..=upload.data.bytes_uploaded / chunk).collect())
+                }
+            },
+            Err(FdpError::NotFound(_)) => {
+                let requested = match &self.sizer {
+                    Some(sizer) => sizer.suggest(self.chunk_size),
+                    None => self.chunk_size,
+                };
+                let registered = self
+                    .with_retry(event, 0, || {
+                        self.client
+                            .register_upload(&self.path, dataset, name, requested)
+                    })
+                    .await?;
+                let chunk = chunk::negotiated(registered.chunk_size, requested);
+                journal.insert(JournalEntry::new(
+                    dataset,
+                    name,
+                    &registered.id,
+                    file,
+                    chunk,
+                ))?;
+                self.emit(event, UploadStatus::Registered);
+                (chunk, BTreeSet::new())
+            }
+            Err(err) => return Err(err),
+        };
+        let part_size = |part: u64| chunk::part_size(part, chunk, file.size);
+        event.lock().unwrap().bytes_uploaded = sent.iter().map(|p| part_size(*p)).sum();
+
+        let parts = chunk::parts(chunk, file.size);
+        let pending = (1..=parts).filter(|p| !sent.contains(p));
+        stream::iter(pending.map(Ok))
+            .try_for_each_concurrent(self.parallel_parts, |part| {
+                let (event, journal, filepath) = (event, &journal, &filepath);
+                async move {
+                    self.proceed(event).await?;
+                    if FileState::of(filepath)? != file {
+                        return Err(FdpError::FileChanged(format!(
+                            "{}/{} was modified during the upload",
+                            dataset, name
+                        )));
+                    }
+                    self.emit(event, UploadStatus::Uploading { part });
+
+                    self.with_retry(event, part, || async move {
+                        let _permit = self.limiter.acquire(part_size(part)).await;
+                        let started = Instant::now();
+                        let flake = self
+                            .client
+                            .upload_part(&self.path, dataset, name, part, chunk)
+                            .await?;
+                        if let Some(sizer) = &self.sizer {
+                            sizer.record(part_size(part), started.elapsed());
+                        }
+                        Ok(flake)
+                    })
+                    .await?;
+                    journal.record_part(dataset, name, part)?;
+                    event.lock().unwrap().bytes_uploaded += part_size(part);
+                    Ok(())
+                }
+            })
+            .await?;
+
+        self.proceed(event).await?;
+        let upload = self
+            .with_retry(event, parts, || {
+                self.client.complete_upload(&self.path, dataset, name)
+            })
+            .await?;
+        journal.record_completed(dataset, name)?;
+        self.emit(event, UploadStatus::Completed);
+        Ok(upload)
+    }
+}
+
+/// Register the upload of the resource, one part at a time. Chunk size
+/// accepted by the portal is recorded in the [`Journal`], so that
+/// [`progress_upload`] splits the file in the same way.
+pub async fn register_upload<C: FdpClient + ?Sized>(
+    client: &C,
+    path: &str,
+    dataset: &str,
+    name: &str,
+    chunk_size: u64,
+) -> crate::Result<RegisteredUpload> {
+    let resource = find_resource(path, dataset, name)?;
+    let file = FileState::of(resource.path.join(&resource.name))?;
+    let registered = client
+        .register_upload(path, dataset, name, chunk_size)
+        .await?;
+    Journal::new(path).insert(JournalEntry::new(
+        dataset,
+        name,
+        &registered.id,
+        file,
+        chunk::negotiated(registered.chunk_size, chunk_size),
+    ))?;
+    Ok(registered)
+}
+
+/// Send the part of the registered upload and complete the upload after
+/// the last part. Chunk size is taken from the [`Journal`] or from the
+/// portal; `chunk_size` is used only when neither of them knows it.
+pub async fn progress_upload<C: FdpClient + ?Sized>(
+    client: &C,
+    path: &str,
+    dataset: &str,
+    name: &str,
+    part: u64,
+    chunk_size: u64,
+) -> crate::Result<ProgressedUpload> {
+    let journal = Journal::new(path);
+    let chunk_size = match journal.get(dataset, name)? {
+        Some(entry) if entry.chunk_size > 0 => entry.chunk_size,
+        _ => chunk::negotiated(
+            client.show_upload(dataset, name).await?.data.chunk_size,
+            chunk_size,
+        ),
+    };
+    let upload = client
+        .progress_upload(path, dataset, name, part, chunk_size)
+        .await?;
+    journal.record_part(dataset, name, part)?;
+    if upload.data.completed {
+        journal.record_completed(dataset, name)?;
+    }
+    Ok(upload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockFdpClient;
+    use crate::testing::source;
+
+    const FILE: &str = "file.bin";
+    const CHUNK: usize = 10;
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 3,
+            delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_all_parts() {
+        let dir = source(&[(FILE, CHUNK * 2 + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+
+        let upload = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .retry(fast())
+            .on_event(move |e| sink.lock().unwrap().push(e.status))
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+
+        assert!(upload.data.completed);
+        assert_eq!(vec![1, 2, 3], portal.parts("dataset", FILE));
+        assert_eq!(
+            Some(&UploadStatus::Registered),
+            events.lock().unwrap().first()
+        );
+        assert_eq!(
+            Some(&UploadStatus::Completed),
+            events.lock().unwrap().last()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_resumes_and_retries() {
+        let dir = source(&[(FILE, CHUNK * 2 + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        portal
+            .register_upload(path, "dataset", FILE, CHUNK as u64)
+            .await
+            .unwrap();
+        portal
+            .upload_part(path, "dataset", FILE, 1, CHUNK as u64)
+            .await
+            .unwrap();
+        portal.clear_parts();
+        portal.fail_part(
+            "dataset",
+            FILE,
+            2,
+            FdpError::ChecksumMismatch("Corrupted part".into()),
+        );
+        portal.fail_part(
+            "dataset",
+            FILE,
+            3,
+            FdpError::Transport("Connection reset".into()),
+        );
+
+        Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .retry(fast())
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+
+        assert_eq!(vec![2, 3], portal.parts("dataset", FILE));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_parts() {
+        let dir = source(&[(FILE, CHUNK * 3 + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().latency(Duration::from_millis(20));
+
+        let upload = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .parallel_parts(2)
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+
+        assert!(upload.data.completed);
+        assert_eq!(2, portal.max_in_flight());
+        let mut parts = portal.parts("dataset", FILE);
+        parts.sort();
+        assert_eq!(vec![1, 2, 3, 4], parts);
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_chunk_size() {
+        let dir = source(&[(FILE, CHUNK)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().chunk_size(4);
+
+        Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+
+        assert_eq!(vec![1, 2, 3], portal.parts("dataset", FILE));
+        let entry = Journal::new(path).get("dataset", FILE).unwrap().unwrap();
+        assert_eq!(4, entry.chunk_size);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_upload() {
+        let dir = source(&[(FILE, 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        let uploader = Uploader::new(&portal, path).chunk_size(CHUNK as u64);
+        uploader.handle().cancel();
+
+        let result = uploader.upload("dataset", FILE).await;
+        assert!(matches!(result, Err(FdpError::Cancelled)));
+        assert!(portal.parts("dataset", FILE).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload() {
+        let dir = source(&[(FILE, CHUNK + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        portal.fail_part("dataset", FILE, 2, FdpError::Auth("Expired".into()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+
+        let result = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .retry(fast())
+            .on_event(move |e| sink.lock().unwrap().push(e.status))
+            .upload("dataset", FILE)
+            .await;
+
+        assert!(matches!(result, Err(FdpError::Auth(_))));
+        assert!(matches!(
+            events.lock().unwrap().last(),
+            Some(UploadStatus::Failed { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_upload_is_journaled() {
+        let dir = source(&[(FILE, CHUNK + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        let uploader = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .retry(fast());
+        let control = uploader.handle();
+        let uploader = uploader.on_event(move |e| {
+            if e.status == (UploadStatus::Uploading { part: 2 }) {
+                control.cancel();
+            }
+        });
+        assert!(uploader.upload("dataset", FILE).await.is_err());
+
+        let journal = Journal::new(path);
+        let entry = journal.get("dataset", FILE).unwrap().unwrap();
+        assert!(!entry.completed);
+        assert_eq!(1, journal.unfinished().unwrap().len());
+
+        Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+        assert!(journal.unfinished().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_changed_file_is_not_resumed() {
+        let dir = source(&[(FILE, CHUNK + 10)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new();
+        portal
+            .register_upload(path, "dataset", FILE, CHUNK as u64)
+            .await
+            .unwrap();
+        let file = FileState::of(dir.path().join("dataset").join(FILE)).unwrap();
+        Journal::new(path)
+            .insert(JournalEntry::new(
+                "dataset",
+                FILE,
+                "upload",
+                FileState {
+                    size: file.size - 1,
+                    ..file
+                },
+                CHUNK as u64,
+            ))
+            .unwrap();
+
+        let result = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .upload("dataset", FILE)
+            .await;
+        assert!(matches!(result, Err(FdpError::FileChanged(_))));
+        assert!(portal.parts("dataset", FILE).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_parts_use_chunk_size_of_portal() {
+        let dir = source(&[(FILE, CHUNK)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().chunk_size(4);
+
+        let registered = register_upload(&portal, path, "dataset", FILE, CHUNK as u64)
+            .await
+            .unwrap();
+        assert_eq!(Some(4), registered.chunk_size);
+
+        for part in 1..=2 {
+            let upload = progress_upload(&portal, path, "dataset", FILE, part, CHUNK as u64)
+                .await
+                .unwrap();
+            assert!(!upload.data.completed);
+            assert_eq!(part * 4, upload.data.bytes_uploaded);
+        }
+        let upload = progress_upload(&portal, path, "dataset", FILE, 3, CHUNK as u64)
+            .await
+            .unwrap();
+        assert!(upload.data.completed);
+        assert!(Journal::new(path).unfinished().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_zero_chunk_size_of_portal() {
+        let dir = source(&[(FILE, CHUNK)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().chunk_size(0);
+
+        let upload = Uploader::new(&portal, path)
+            .chunk_size(CHUNK as u64)
+            .upload("dataset", FILE)
+            .await
+            .unwrap();
+        assert!(upload.data.completed);
+        assert_eq!(vec![1], portal.parts("dataset", FILE));
+
+        let dir = source(&[(FILE, CHUNK)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().chunk_size(0);
+        register_upload(&portal, path, "dataset", FILE, 4)
+            .await
+            .unwrap();
+        let entry = Journal::new(path).get("dataset", FILE).unwrap().unwrap();
+        assert_eq!(4, entry.chunk_size);
+        let upload = progress_upload(&portal, path, "dataset", FILE, 1, 4)
+            .await
+            .unwrap();
+        assert_eq!(4, upload.data.bytes_uploaded);
+    }
+
+    #[tokio::test]
+    async fn test_parts_without_journal() {
+        let dir = source(&[(FILE, CHUNK)]);
+        let path = dir.path().to_str().unwrap();
+        let portal = MockFdpClient::new().chunk_size(4);
+        portal
+            .register_upload(path, "dataset", FILE, CHUNK as u64)
+            .await
+            .unwrap();
+
+        let upload = progress_upload(&portal, path, "dataset", FILE, 1, CHUNK as u64)
+            .await
+            .unwrap();
+        assert_eq!(4, upload.data.bytes_uploaded);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(Duration::from_secs(1), policy.backoff(1));
+        assert_eq!(Duration::from_secs(4), policy.backoff(3));
+        assert_eq!(Duration::from_secs(30), policy.backoff(10));
+    }
+}
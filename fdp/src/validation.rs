@@ -0,0 +1,296 @@
+//! Offline validation of the metadata.
+//!
+//! Every field has a list of [`Rule`]s, taken from the
+//! [`schema`](crate::schema) of the metadata. Result has the same shape as the one
+//! returned by the portal: the checked data and the list of errors for every
+//! invalid field.
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::schema;
+use crate::types::{Metadata, ValidationResult};
+
+/// Constraint of the metadata field. Rules other than `required` and
+/// `required_if` are checked only for non-empty values.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Rule {
+    Required,
+    /// Integer between `min` and `max`, inclusive.
+    Range {
+        min: i64,
+        max: i64,
+    },
+    /// Number of characters between `min` and `max`, inclusive.
+    Length {
+        min: usize,
+        max: usize,
+    },
+    /// Lowercase letters, digits, hyphens and underscores.
+    Slug,
+    /// Date in YYYY-MM-DD format.
+    Date,
+    OneOf {
+        values: Vec<String>,
+    },
+    /// Required when the other field has the value, must be empty
+    /// otherwise.
+    RequiredIf {
+        field: String,
+        value: String,
+    },
+}
+
+/// Rules of every field by its name.
+pub type Rules = BTreeMap<String, Vec<Rule>>;
+
+pub fn dataset_rules() -> Rules {
+    schema::rules(&schema::current().dataset)
+}
+
+pub fn resource_rules() -> Rules {
+    schema::rules(&schema::current().resource)
+}
+
+/// Text of the value, as it would be written into metadata.csv.
+fn text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.trim().to_string(),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return false;
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return false;
+    }
+    // `parse` accepts a leading sign
+    if !parts
+        .iter()
+        .all(|part| part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+    let (Ok(year), Ok(month), Ok(day)) = (year.parse::<u32>(), month.parse(), day.parse()) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
+
+impl Rule {
+    /// Error message if the field breaks the rule.
+    pub fn check(&self, field: &str, data: &Map<String, Value>) -> Option<String> {
+        let value = text(data.get(field));
+        match self {
+            Self::Required if value.is_empty() => Some("Missing value".into()),
+            Self::RequiredIf {
+                field: other,
+                value: expected,
+            } => {
+                let required = text(data.get(other)).eq_ignore_ascii_case(expected);
+                match (required, value.is_empty()) {
+                    (true, true) => Some(format!(
+                        "Missing value, required when {} is {}",
+                        other, expected
+                    )),
+                    (false, false) => {
+                        Some(format!("Must be empty unless {} is {}", other, expected))
+                    }
+                    _ => None,
+                }
+            }
+            _ if value.is_empty() => None,
+            Self::Range { min, max } => match value.parse::<i64>() {
+                Ok(number) if (*min..=*max).contains(&number) => None,
+                _ => Some(format!("Must be a number between {} and {}", min, max)),
+            },
+            Self::Length { min, max } => {
+                let length = value.chars().count();
+                match (*min..=*max).contains(&length) {
+                    true => None,
+                    false => Some(format!(
+                        "Must be between {} and {} characters long",
+                        min, max
+                    )),
+                }
+            }
+            Self::Slug => {
+                let valid = value
+                    .chars()
+                    .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '-' | '_'));
+                match valid {
+                    true => None,
+                    false => Some(
+                        "Must contain only lowercase letters, digits, hyphens and underscores"
+                            .into(),
+                    ),
+                }
+            }
+            Self::Date => match is_date(&value) {
+                true => None,
+                false => Some("Must be a date in YYYY-MM-DD format".into()),
+            },
+            Self::OneOf { values } => match values.contains(&value) {
+                true => None,
+                false => Some(format!("Must be one of: {}", values.join(", "))),
+            },
+            Self::Required => None,
+        }
+    }
+}
+
+/// Check the metadata against the rules.
+pub fn validate(metadata: &Metadata, rules: &Rules) -> ValidationResult {
+    let data = match metadata {
+        Metadata::Object(Value::Object(fields)) => fields.clone(),
+        _ => Map::new(),
+    };
+    let mut errors = Map::new();
+    for (field, rules) in rules {
+        let messages: Vec<Value> = rules
+            .iter()
+            .filter_map(|rule| rule.check(field, &data))
+            .map(Value::from)
+            .collect();
+        if !messages.is_empty() {
+            errors.insert(field.clone(), Value::Array(messages));
+        }
+    }
+    ValidationResult {
+        data: Value::Object(data),
+        errors: Value::Object(errors),
+    }
+}
+
+pub fn validate_dataset(metadata: &Metadata) -> ValidationResult {
+    validate(metadata, &dataset_rules())
+}
+
+pub fn validate_resource(metadata: &Metadata) -> ValidationResult {
+    validate(metadata, &resource_rules())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn errors(metadata: Value) -> Value {
+        validate_dataset(&Metadata::Object(metadata)).errors
+    }
+
+    #[test]
+    fn test_valid_dataset() {
+        let metadata = json!({
+            "title": "Rivers",
+            "name": "rivers_2022",
+            "dataset_type": "18",
+            "publication_date": "2024-02-29",
+            "spatial_data": "yes",
+            "capture_method": "digitised",
+            "data_status": "partiallySuperseded",
+            "license_id": "cc-by",
+            "update_freq": "as_required",
+            "access_level": "open",
+            "notes": "",
+        });
+        assert_eq!(json!({}), errors(metadata.clone()));
+        assert_eq!(
+            metadata,
+            validate_dataset(&Metadata::Object(metadata.clone())).data
+        );
+        assert_eq!(
+            json!({}),
+            errors(json!({"title": "Rivers", "name": "ri", "dataset_type": 3}))
+        );
+    }
+
+    #[test]
+    fn test_invalid_dataset() {
+        assert_eq!(
+            json!({
+                "title": ["Missing value"],
+                "name": [
+                    "Must be between 2 and 100 characters long",
+                    "Must contain only lowercase letters, digits, hyphens and underscores",
+                ],
+                "dataset_type": ["Must be a number between 1 and 18"],
+                "publication_date": ["Must be a date in YYYY-MM-DD format"],
+                "capture_method": ["Missing value, required when spatial_data is yes"],
+                "access_level": ["Must be one of: open, registered, internal, restricted"],
+            }),
+            errors(json!({
+                "name": "R",
+                "dataset_type": "19",
+                "publication_date": "2023-02-29",
+                "spatial_data": "yes",
+                "access_level": "public",
+            }))
+        );
+        assert_eq!(
+            json!({
+                "title": ["Missing value"],
+                "name": ["Missing value"],
+                "capture_method": ["Must be empty unless spatial_data is yes"],
+            }),
+            errors(json!({"spatial_data": "no", "capture_method": "exported"}))
+        );
+        assert_eq!(
+            json!({"title": ["Missing value"], "name": ["Missing value"]}),
+            validate_dataset(&Metadata::Empty).errors
+        );
+    }
+
+    #[test]
+    fn test_dates() {
+        assert!(is_date("2022-11-24"));
+        assert!(is_date("2000-02-29"));
+        assert!(!is_date("1900-02-29"));
+        assert!(!is_date("2022-13-01"));
+        assert!(!is_date("2022-1-01"));
+        assert!(!is_date("24/11/2022"));
+        assert!(!is_date("+022-11-24"));
+        assert!(!is_date("2022-+1-+1"));
+    }
+
+    #[test]
+    fn test_resource() {
+        assert_eq!(
+            json!({}),
+            validate_resource(&Metadata::Object(json!({"format_label": "GIS"}))).errors
+        );
+        assert_eq!(
+            json!({"format_label": ["Must be one of: Word, Excel, Point, Text, CSV, PDF, Zipped, Image, GIS, Model, MISC"]}),
+            validate_resource(&Metadata::Object(json!({"format_label": "Shapefile"}))).errors
+        );
+    }
+
+    #[test]
+    fn test_rules_from_json() {
+        let rules: Rules = serde_json::from_value(json!({
+            "title": [{"rule": "required"}],
+            "code": [{"rule": "one_of", "values": ["a", "b"]}],
+        }))
+        .unwrap();
+        assert_eq!(
+            json!({"title": ["Missing value"], "code": ["Must be one of: a, b"]}),
+            validate(&Metadata::Object(json!({"code": "c"})), &rules).errors
+        );
+    }
+}
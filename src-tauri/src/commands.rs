@@ -1,14 +1,17 @@
 use fdp::action::FdpClient;
-use fdp::state::PortalState;
+use fdp::journal::Journal;
+use fdp::scheduler::{Scheduler, SchedulerEvent, SchedulerOptions, UploadOutcome};
+use fdp::schema::Schema;
+use fdp::state::{PortalState, UploadControls};
 use fdp::types::{
     Portal, ProgressedUpload, Project, RegisteredUpload, Source, User, ValidationResult,
 };
+use fdp::upload::{self, Uploader};
 use serde_json::Value;
 
 #[tauri::command]
 pub async fn login(state: tauri::State<'_, PortalState>, portal: Portal) -> fdp::Result<User> {
-    state.replace(portal);
-    state.client()?.user_info().await
+    state.login(portal).await
 }
 
 #[tauri::command]
@@ -21,7 +24,7 @@ pub async fn list_projects(
     state: tauri::State<'_, PortalState>,
     name: &str,
 ) -> fdp::Result<Vec<Project>> {
-    Ok(state.client()?.available_projects(&name).await)
+    state.client()?.available_projects(name).await
 }
 
 #[tauri::command]
@@ -34,7 +37,9 @@ pub async fn project_set(
 
 #[tauri::command]
 pub async fn read_source_path(path: &str) -> fdp::Result<Source> {
-    fdp::read_source_path(&path)
+    let mut source = fdp::read_source_path(&path)?;
+    source.detect_formats();
+    Ok(source)
 }
 
 #[tauri::command]
@@ -42,6 +47,46 @@ pub async fn save_root_metadata(path: &str, metadata: Value) -> fdp::Result<()>
     fdp::save_root_metadata(path, metadata)
 }
 
+#[tauri::command]
+pub async fn save_dataset_metadata(path: &str, name: &str, metadata: Value) -> fdp::Result<()> {
+    fdp::save_dataset_metadata(path, name, metadata)
+}
+
+#[tauri::command]
+pub async fn save_resource_metadata(
+    path: &str,
+    dataset: &str,
+    name: &str,
+    metadata: Value,
+) -> fdp::Result<()> {
+    fdp::save_resource_metadata(path, dataset, name, metadata)
+}
+
+#[tauri::command]
+pub async fn metadata_schema() -> fdp::Result<Schema> {
+    Ok(fdp::schema::current().as_ref().clone())
+}
+
+#[tauri::command]
+pub async fn convert_metadata_to_toml(path: &str) -> fdp::Result<()> {
+    fdp::convert_metadata_to_toml(path)
+}
+
+#[tauri::command]
+pub async fn convert_metadata_to_sheet(path: &str) -> fdp::Result<()> {
+    fdp::convert_metadata_to_sheet(path)
+}
+
+#[tauri::command]
+pub async fn convert_metadata_to_xlsx(path: &str) -> fdp::Result<()> {
+    fdp::convert_metadata_to_xlsx(path)
+}
+
+#[tauri::command]
+pub async fn generate_template(path: &str) -> fdp::Result<()> {
+    fdp::generate_template(path)
+}
+
 #[tauri::command]
 pub async fn add_dataset(path: &str, name: &str) -> fdp::Result<()> {
     fdp::add_dataset(path, name)
@@ -69,6 +114,20 @@ pub async fn validate_resource(
         .await
 }
 
+#[tauri::command]
+pub async fn validate_dataset_locally(path: &str, name: &str) -> fdp::Result<ValidationResult> {
+    fdp::validate_dataset_locally(path, name)
+}
+
+#[tauri::command]
+pub async fn validate_resource_locally(
+    path: &str,
+    dataset: &str,
+    name: &str,
+) -> fdp::Result<ValidationResult> {
+    fdp::validate_resource_locally(path, dataset, name)
+}
+
 #[tauri::command]
 pub async fn add_resource(path: &str, dataset: &str, name: &str) -> fdp::Result<()> {
     fdp::add_resource(path, dataset, name)
@@ -76,11 +135,7 @@ pub async fn add_resource(path: &str, dataset: &str, name: &str) -> fdp::Result<
 
 #[tauri::command]
 pub async fn show_submission(state: tauri::State<'_, PortalState>) -> fdp::Result<Vec<Value>> {
-    state
-        .client()?
-        .show_submission()
-        .await
-        .ok_or("Cannot get details of the current submission".into())
+    state.client()?.show_submission().await
 }
 
 #[tauri::command]
@@ -89,11 +144,7 @@ pub async fn show_upload(
     dataset: &str,
     name: &str,
 ) -> fdp::Result<ProgressedUpload> {
-    state
-        .client()?
-        .show_upload(dataset, name)
-        .await
-        .ok_or("Upload has not started yet".into())
+    state.client()?.show_upload(dataset, name).await
 }
 
 #[tauri::command]
@@ -103,11 +154,8 @@ pub async fn register_upload(
     dataset: &str,
     name: &str,
 ) -> fdp::Result<RegisteredUpload> {
-    state
-        .client()?
-        .register_upload(path, dataset, name)
-        .await
-        .ok_or("Upload cannot be started".into())
+    let client = state.client()?;
+    upload::register_upload(&*client, path, dataset, name, state.chunk_size()).await
 }
 
 #[tauri::command]
@@ -118,9 +166,130 @@ pub async fn progress_upload(
     name: &str,
     part: u64,
 ) -> fdp::Result<ProgressedUpload> {
-    state
-        .client()?
-        .progress_upload(path, dataset, name, part)
+    let client = state.client()?;
+    upload::progress_upload(&*client, path, dataset, name, part, state.chunk_size()).await
+}
+
+#[tauri::command]
+pub async fn upload_resource(
+    window: tauri::Window,
+    state: tauri::State<'_, PortalState>,
+    controls: tauri::State<'_, UploadControls>,
+    path: &str,
+    dataset: &str,
+    name: &str,
+) -> fdp::Result<ProgressedUpload> {
+    let client = state.client()?;
+    let control = controls.start(dataset, name);
+    let result = Uploader::new(&*client, path)
+        .chunk_size(state.chunk_size())
+        .control(control)
+        .on_event(move |event| {
+            if let Err(err) = window.emit("upload", event) {
+                log::warn!("Cannot emit upload event: {}", err);
+            }
+        })
+        .upload(dataset, name)
+        .await;
+    controls.finish(dataset, name);
+    result
+}
+
+fn emit_progress(window: tauri::Window) -> impl Fn(SchedulerEvent) + Send + Sync + 'static {
+    move |event| {
+        if let Err(err) = window.emit("upload-progress", event) {
+            log::warn!("Cannot emit upload progress: {}", err);
+        }
+    }
+}
+
+fn default_options(state: &PortalState) -> SchedulerOptions {
+    SchedulerOptions {
+        chunk_size: state.chunk_size(),
+        ..Default::default()
+    }
+}
+
+/// Upload every resource of the source, or only `resources`, several at once.
+#[tauri::command]
+pub async fn upload_source(
+    window: tauri::Window,
+    state: tauri::State<'_, PortalState>,
+    controls: tauri::State<'_, UploadControls>,
+    path: &str,
+    options: Option<SchedulerOptions>,
+    resources: Option<Vec<(String, String)>>,
+    prefer: Option<Vec<(String, String)>>,
+) -> fdp::Result<Vec<UploadOutcome>> {
+    let client = state.client()?;
+    let mut scheduler = Scheduler::new(&*client, path)
+        .options(options.unwrap_or_else(|| default_options(&state)))
+        .prefer(prefer.unwrap_or_default())
+        .controls(&controls)
+        .on_event(emit_progress(window));
+    if let Some(resources) = resources {
+        scheduler = scheduler.only(resources);
+    }
+    scheduler.run().await
+}
+
+/// Continue every unfinished upload recorded in the journal of the source.
+#[tauri::command]
+pub async fn resume_uploads(
+    window: tauri::Window,
+    state: tauri::State<'_, PortalState>,
+    controls: tauri::State<'_, UploadControls>,
+    path: &str,
+    options: Option<SchedulerOptions>,
+) -> fdp::Result<Vec<UploadOutcome>> {
+    let client = state.client()?;
+    let unfinished = Journal::new(path)
+        .unfinished()?
+        .into_iter()
+        .map(|entry| (entry.dataset, entry.name))
+        .collect();
+    Scheduler::new(&*client, path)
+        .options(options.unwrap_or_else(|| default_options(&state)))
+        .only(unfinished)
+        .controls(&controls)
+        .on_event(emit_progress(window))
+        .run()
         .await
-        .ok_or("Cannot continue uploading the file".into())
+}
+
+#[tauri::command]
+pub async fn pause_upload(
+    controls: tauri::State<'_, UploadControls>,
+    dataset: &str,
+    name: &str,
+) -> fdp::Result<()> {
+    controls.get(dataset, name)?.pause();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_upload(
+    controls: tauri::State<'_, UploadControls>,
+    dataset: &str,
+    name: &str,
+) -> fdp::Result<()> {
+    controls.get(dataset, name)?.resume();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn cancel_upload(
+    controls: tauri::State<'_, UploadControls>,
+    dataset: &str,
+    name: &str,
+) -> fdp::Result<()> {
+    controls.get(dataset, name)?.cancel();
+    Ok(())
+}
+
+/// Forget progress of the upload recorded in the journal of the source, e.g.
+/// when the file was changed and the upload cannot be resumed.
+#[tauri::command]
+pub async fn discard_upload(path: &str, dataset: &str, name: &str) -> fdp::Result<()> {
+    Journal::new(path).remove(dataset, name)
 }
@@ -6,12 +6,13 @@
 )]
 
 mod commands;
-use fdp::state::PortalState;
+use fdp::state::{PortalState, UploadControls};
 
 fn main() {
     env_logger::init();
     tauri::Builder::default()
         .manage(PortalState::default())
+        .manage(UploadControls::default())
         .invoke_handler(tauri::generate_handler![
             commands::submission_finalize,
             commands::login,
@@ -20,15 +21,31 @@ fn main() {
 
             commands::read_source_path,
             commands::save_root_metadata,
+            commands::save_dataset_metadata,
+            commands::save_resource_metadata,
+            commands::metadata_schema,
+            commands::convert_metadata_to_toml,
+            commands::convert_metadata_to_sheet,
+            commands::convert_metadata_to_xlsx,
+            commands::generate_template,
             commands::add_dataset,
             commands::validate_dataset,
             commands::validate_resource,
+            commands::validate_dataset_locally,
+            commands::validate_resource_locally,
             commands::add_resource,
 
             commands::show_submission,
             commands::show_upload,
             commands::register_upload,
             commands::progress_upload,
+            commands::upload_resource,
+            commands::upload_source,
+            commands::resume_uploads,
+            commands::pause_upload,
+            commands::resume_upload,
+            commands::cancel_upload,
+            commands::discard_upload,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
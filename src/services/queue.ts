@@ -5,9 +5,16 @@ import Source from "./source";
 import Flakes from "./flakes";
 import Toaster from "./toaster";
 import Submission from "./submission";
+import Tauri from "./tauri";
+
+const key = (dataset: TDataset, resource: TResource) =>
+  `${dataset.name}/${resource.name}`;
 
 const store = writable({
   items: new Map<string, [TDataset, TResource]>(),
+  // Uploads currently sent by the backend and the ones paused among them.
+  active: new Set<string>(),
+  paused: new Set<string>(),
   processing: false,
   finalized: false,
 });
@@ -21,12 +28,106 @@ const add = (dataset: TDataset, resource: TResource) => {
 
 const drop = (dataset: TDataset, resource: TResource) => {
   store.update((queue) => {
-    queue.items.delete(`${dataset.name}/${resource.name}`);
+    queue.items.delete(key(dataset, resource));
     return queue;
   });
 };
 
-const clear = () => store.update((queue) => ({ ...queue, items: new Map() }));
+const setActive = (name: string, active: boolean) => {
+  store.update((queue) => {
+    if (active) {
+      queue.active.add(name);
+    } else {
+      queue.active.delete(name);
+      queue.paused.delete(name);
+    }
+    return queue;
+  });
+};
+
+const setPaused = (name: string, paused: boolean) => {
+  store.update((queue) => {
+    paused ? queue.paused.add(name) : queue.paused.delete(name);
+    return queue;
+  });
+};
+
+const isActive = (dataset: TDataset, resource: TResource) =>
+  get(store).active.has(key(dataset, resource));
+const isPaused = (dataset: TDataset, resource: TResource) =>
+  get(store).paused.has(key(dataset, resource));
+
+// Pause the running upload, or remove the resource from the queue.
+const pause = async (dataset: TDataset, resource: TResource) => {
+  if (isActive(dataset, resource)) {
+    await Submission.pauseUpload(dataset.name, resource.name)
+      .then(() => setPaused(key(dataset, resource), true))
+      .catch((err) => Toaster.error(err, resource.name));
+  } else {
+    drop(dataset, resource);
+  }
+};
+
+const resume = async (dataset: TDataset, resource: TResource) => {
+  await Submission.resumeUpload(dataset.name, resource.name)
+    .then(() => setPaused(key(dataset, resource), false))
+    .catch((err) => Toaster.error(err, resource.name));
+};
+
+// Cancel the running upload. Upload that is neither running nor queued is
+// discarded, so it's not resumed anymore.
+const cancel = async (dataset: TDataset, resource: TResource) => {
+  if (isActive(dataset, resource)) {
+    drop(dataset, resource);
+    await Submission.cancelUpload(dataset.name, resource.name).catch((err) =>
+      Toaster.error(err, resource.name)
+    );
+  } else if (contains(dataset, resource)) {
+    drop(dataset, resource);
+  } else {
+    await Submission.discardUpload(dataset.name, resource.name);
+  }
+};
+
+// Uploads resumed by the backend are not started by the queue, so their
+// state is taken from upload events.
+const track = ({ dataset, name, status }) => {
+  const id = `${dataset}/${name}`;
+  switch (status.type) {
+    case "completed":
+    case "cancelled":
+    case "failed":
+      setActive(id, false);
+      break;
+    case "paused":
+      setActive(id, true);
+      setPaused(id, true);
+      break;
+    default:
+      setActive(id, true);
+      setPaused(id, false);
+  }
+};
+
+const trackUploadsListener = () =>
+  Promise.all([
+    Submission.onUploadEvent(track),
+    Submission.onUploadProgress(({ upload }) => track(upload)),
+  ]).catch((err) => {
+    if (Tauri.testMode) return;
+    console.warn("Cannot listen upload events: %o", err);
+  });
+
+const clear = () => {
+  const { active } = get(store);
+  store.update((queue) => ({ ...queue, items: new Map() }));
+  active.forEach((name) => {
+    const [dataset, resource] = name.split("/");
+    Submission.pauseUpload(dataset, resource)
+      .then(() => setPaused(name, true))
+      .catch((err) => Toaster.error(err, resource));
+  });
+};
 
 const pop = () => {
   let value: [TDataset, TResource] | null;
@@ -41,7 +142,7 @@ const pop = () => {
   return value;
 };
 const contains = (dataset: TDataset, resource: TResource) => {
-  return get(store).items.has(`${dataset.name}/${resource.name}`);
+  return get(store).items.has(key(dataset, resource));
 };
 
 const process = async () => {
@@ -90,27 +191,31 @@ const fullUpload = async (finalize: boolean) => {
   }
 };
 
+// Everything queued so far is sent to the backend at once, so that uploads
+// share the concurrency and bandwidth limits of the portal.
 const exhaustQueue = async () => {
   while (true) {
-    let key = pop();
-    if (!key) {
+    const batch: [TDataset, TResource][] = [];
+    for (let entry = pop(); entry; entry = pop()) {
+      const [dataset, resource] = entry;
+      const upload = get(Flakes).uploads[key(dataset, resource)];
+      if (upload && upload.data.completed) {
+        Toaster.info(`Completed uploading ${resource.name}`, dataset.name);
+      } else {
+        batch.push(entry);
+      }
+    }
+    if (!batch.length) {
       break;
     }
-    const [dataset, resource] = key;
-    const upload = get(Flakes).uploads[`${dataset.name}/${resource.name}`];
-    if (!upload) {
-      Toaster.info(`Prepare ${resource.name} for upload`, dataset.name);
-      add(dataset, resource);
-      await Submission.registerUpload(dataset.name, resource.name);
-    } else if (!upload.data.completed) {
-      add(dataset, resource);
-      await Submission.progressUpload(
-        dataset.name,
-        resource.name,
-        upload.data.chunks_uploaded + 1
+    batch.forEach(([d, r]) => setActive(key(d, r), true));
+    try {
+      await Submission.uploadSource(
+        undefined,
+        batch.map(([d, r]) => [d.name, r.name])
       );
-    } else {
-      Toaster.info(`Completed uploading ${resource.name}`, dataset.name);
+    } finally {
+      batch.forEach(([d, r]) => setActive(key(d, r), false));
     }
   }
 };
@@ -122,6 +227,12 @@ export default {
   subscribe: store.subscribe,
   add,
   drop,
+  pause,
+  resume,
+  cancel,
+  isActive,
+  isPaused,
+  trackUploadsListener,
   pop,
   contains,
   process,
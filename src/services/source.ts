@@ -1,5 +1,5 @@
 
-import type { TSource, TUser } from "src/types";
+import type { TField, TMetadata, TResource, TSchema, TSource, TUser } from "src/types";
 import { writable, get } from "svelte/store"
 import Storage from "./storage"
 import Tauri from "./tauri"
@@ -14,29 +14,14 @@ const checkPath = async (path: string): Promise<TSource> => {
   return await Tauri.invoke("read_source_path", { path })
 }
 
-const defaultResource = () => ({
-  "name": "<resource name>",
-  "description": "<resource description>"
-})
-const defaultMetadata = () => ({
-  "dataset_type": "",
-  "title": "",
-  "name": "",
-  "notes": "",
-  "publication_date": "",
-  "tag_string": "",
-  "spatial_data": "",
-  "capture_method": "",
-  "data_status": "",
-  "license_id": "",
-  // "spatial": "",
-  "dataset_status": "",
-  "update_freq": "",
-  // "additional_lga": "",
-  "author": "",
-  "data_comment": "",
-  "access_level": "",
-});
+const schema = () => Tauri.invoke<TSchema>("metadata_schema")
+
+const defaults = (fields: TField[] | undefined) =>
+  Object.fromEntries((fields || []).map(f => [f.name, f.default ?? ""]))
+
+const defaultResource = async (resource?: TResource) =>
+  ({ ...defaults((await schema()).resource), ...resource?.suggestion })
+const defaultMetadata = async () => defaults((await schema()).dataset)
 
 const change = async (path: string) => {
   let source: TSource;
@@ -65,18 +50,19 @@ const restore = (_user: TUser) => Storage.getItem(key).then((path: string | null
 const saveRoot = async () => {
   const source = get(service)
   if (source) {
-    await Tauri.invoke("save_root_metadata", { path: source.path, metadata: source.metadata || defaultMetadata() })
+    await Tauri.invoke("save_root_metadata", { path: source.path, metadata: source.metadata || await defaultMetadata() })
     await change(source.path);
   }
 
 }
 
-const saveDataset = async (name: string) => {
+const saveDataset = async (name: string, metadata?: TMetadata) => {
   const source = get(service)
   if (source) {
+    const dataset = source.datasets.find(d => d.name === name)
     await Tauri.invoke(
       "save_dataset_metadata",
-      { path: source.path, name, metadata: source.metadata || defaultMetadata() }
+      { path: source.path, name, metadata: metadata || dataset?.metadata || await defaultMetadata() }
     ).catch(e => Toaster.error(e, "Error"))
     await change(source.path);
   }
@@ -94,12 +80,15 @@ const addDataset = async (name: string) => {
 
 
 
-const saveResource = async (dataset: string, name: string) => {
+const saveResource = async (dataset: string, name: string, metadata?: TMetadata) => {
   const source = get(service)
   if (source) {
+    const resource = source.datasets
+      .find(d => d.name === dataset)?.resources
+      .find(r => r.name === name)
     await Tauri.invoke(
       "save_resource_metadata",
-      { path: source.path, dataset, name, metadata: defaultResource() }
+      { path: source.path, dataset, name, metadata: metadata || resource?.metadata || await defaultResource(resource) }
     ).catch(e => Toaster.error(e, "Error"))
     await change(source.path);
   }
@@ -116,6 +105,23 @@ const addResource = async (dataset: string, name: string) => {
 }
 
 
+const convertMetadata = async (format: "toml" | "sheet" | "xlsx") => {
+  const source = get(service)
+  if (source) {
+    await Tauri.invoke(`convert_metadata_to_${format}`, { path: source.path })
+      .catch(e => Toaster.error(e, "Error"))
+    await change(source.path);
+  }
+}
+
+const generateTemplate = async () => {
+  const source = get(service)
+  if (source) {
+    await Tauri.invoke("generate_template", { path: source.path }).catch(e => Toaster.error(e, "Error"))
+    await change(source.path);
+  }
+}
+
 const browse = async () => await Tauri.dialog.open({ directory: true, multiple: false })
 const open = (...fragments: string[]) => {
   const source = get(service)
@@ -123,6 +129,13 @@ const open = (...fragments: string[]) => {
     Tauri.open(source.path, ...fragments)
   }
 }
+// metadata.xlsx or metadata.csv, whichever is used by the source
+const openMetadata = () => {
+  const source = get(service)
+  if (source?.metadata_path) {
+    Tauri.open(source.metadata_path)
+  }
+}
 const refreshOnFocusListener = () => Tauri.window
   .listen("tauri://focus", () => refresh())
   .catch((err) => {
@@ -144,6 +157,10 @@ const service = {
   saveResource,
   addDataset,
   addResource,
+  convertMetadata,
+  openMetadata,
+  generateTemplate,
+  schema,
   browse,
   open,
 }
@@ -17,15 +17,6 @@ const refresh = async () => {
   }
 };
 
-const registerUpload = async (dataset: string, name: string) => {
-  await Tauri.invoke("register_upload", {
-    path: get(Source).path,
-    dataset,
-    name,
-  })
-  await refresh();
-};
-
 const finalize = async () => {
   try {
     await Tauri.invoke("submission_finalize");
@@ -38,17 +29,91 @@ const finalize = async () => {
   Project.reset(get(User));
 };
 
-const progressUpload = async (dataset: string, name: string, part: number) => {
-  console.debug("Uploading part #%s of %s/%s", part, dataset, name)
-  await Tauri.invoke("progress_upload", {
+const uploadResource = async (dataset: string, name: string) => {
+  try {
+    await Tauri.invoke("upload_resource", {
+      path: get(Source).path,
+      dataset,
+      name,
+    });
+  } catch (err) {
+    if (err?.type !== "cancelled") {
+      Toaster.error(err, `[${dataset}] ${name}`);
+    }
+  }
+  await refresh();
+};
+
+const resumeUploads = async () => {
+  const source = get(Source);
+  if (!source?.path) return;
+  try {
+    const resumed: any[] = await Tauri.invoke("resume_uploads", {
+      path: source.path,
+    });
+    resumed
+      .filter((r) => r.error)
+      .forEach((r) => Toaster.error(r.error, `[${r.dataset}] ${r.name}`));
+  } catch (err) {
+    Toaster.error(err, "Error");
+  }
+  await refresh();
+};
+
+// Upload `resources`, given as `[dataset, name]` pairs, or the whole source.
+// Portal limits on concurrency and bandwidth are shared by all of them.
+const uploadSource = async (
+  options?: object,
+  resources?: [string, string][]
+) => {
+  const source = get(Source);
+  if (!source?.path) return;
+  try {
+    const outcomes: any[] = await Tauri.invoke("upload_source", {
+      path: source.path,
+      options,
+      resources,
+    });
+    outcomes
+      .filter((o) => o.error && o.error.type !== "cancelled")
+      .forEach((o) => Toaster.error(o.error, `[${o.dataset}] ${o.name}`));
+  } catch (err) {
+    Toaster.error(err, "Error");
+  }
+  await refresh();
+};
+
+const onUploadProgress = (handler: (event: any) => void) =>
+  Tauri.window.listen("upload-progress", ({ payload }) => handler(payload));
+
+const pauseUpload = (dataset: string, name: string) =>
+  Tauri.invoke("pause_upload", { dataset, name });
+const resumeUpload = (dataset: string, name: string) =>
+  Tauri.invoke("resume_upload", { dataset, name });
+const cancelUpload = (dataset: string, name: string) =>
+  Tauri.invoke("cancel_upload", { dataset, name });
+const discardUpload = async (dataset: string, name: string) => {
+  await Tauri.invoke("discard_upload", {
     path: get(Source).path,
     dataset,
     name,
-    part,
-  });
+  }).catch((err) => Toaster.error(err, `[${dataset}] ${name}`));
   await refresh();
 };
 
+const onUploadEvent = (handler: (event: any) => void) =>
+  Tauri.window.listen("upload", ({ payload }) => handler(payload));
+
+// Show progress of uploads running in the backend.
+const refreshOnUploadListener = () =>
+  Promise.all([
+    onUploadEvent(() => refresh()),
+    onUploadProgress(() => refresh()),
+  ]).catch((err) => {
+    if (Tauri.testMode) return;
+    console.warn("Cannot listen upload events: %o", err);
+  });
+
 const validateDataset = async (name: string) => {
   const source = get(Source);
   if (source) {
@@ -87,8 +152,16 @@ export default {
   subscribe: store.subscribe,
   refresh,
   byType,
-  registerUpload,
-  progressUpload,
+  uploadResource,
+  resumeUploads,
+  uploadSource,
+  onUploadProgress,
+  pauseUpload,
+  resumeUpload,
+  cancelUpload,
+  discardUpload,
+  onUploadEvent,
+  refreshOnUploadListener,
   validateDataset,
   validateResource,
   finalize,
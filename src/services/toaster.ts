@@ -27,6 +27,26 @@ export class Toast {
 
 }
 
+export type TError = {
+  type: string,
+  message?: string | Object,
+}
+
+const describeError = (err: string | TError): string => {
+  if (typeof err !== "object" || err === null) {
+    return String(err);
+  }
+  if (typeof err.message === "string") {
+    return err.message;
+  }
+  if (typeof err.message !== "object" || err.message === null) {
+    return String(err.type);
+  }
+  return Object.entries(err.message)
+    .map(([field, errors]) => `${field}: ${[].concat(errors).join(", ")}`)
+    .join("\n");
+}
+
 export class ToasterService {
   private store: Writable<Toast[]>;
   constructor() {
@@ -52,8 +72,8 @@ export class ToasterService {
     return toast;
   }
 
-  error(body: string, header: string = "") {
-    const toast = this.add(body, header)
+  error(body: string | TError, header: string = "") {
+    const toast = this.add(describeError(body), header)
     toast.color = Color.danger;
     return toast;
   }
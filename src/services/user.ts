@@ -34,6 +34,8 @@ const restore = async (user: TUser) => {
   await Source.restore(user);
   set(user)
   await Submission.refresh();
+  // continue uploads interrupted when the application was closed
+  Submission.resumeUploads();
 }
 
 const logout = () => {
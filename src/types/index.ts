@@ -1,7 +1,9 @@
 
 export type TPortal = {
   url: string | null,
-  token: string | null
+  token: string | null,
+  profile?: string | null,
+  user?: string | null,
 }
 
 export type TUser = {
@@ -16,10 +18,31 @@ export type TProject = {
 }
 
 export type TMetadata = Object;
+export type TField = {
+  name: string,
+  type: "text" | "long_text" | "integer" | "date" | "choice",
+  required: boolean,
+  choices: string[],
+  default: string | null,
+  min: number | null,
+  max: number | null,
+  help: string[],
+}
+export type TSchema = {
+  dataset: TField[],
+  resource: TField[],
+}
+export type TDiagnostic = {
+  line: number | null,
+  message: string,
+  file?: string | null,
+}
 export type TSource = {
   path: string,
   metadata: TMetadata | null,
   datasets: TDataset[],
+  metadata_path?: string,
+  diagnostics?: TDiagnostic[],
 }
 export type TDataset = {
   path: string,
@@ -27,9 +50,15 @@ export type TDataset = {
   metadata: TMetadata | null,
   resources: TResource[],
 }
+export type TFormatSuggestion = {
+  format: string,
+  format_label: string,
+}
+
 export type TResource = {
   path: string,
   name: string,
   metadata: TMetadata | null,
   size: number,
+  suggestion?: TFormatSuggestion | null,
 }